@@ -35,7 +35,7 @@ fn commit_info() -> String {
 fn commit_hash() -> Result<String, IgnoredError> {
     Ok(String::from_utf8(
         Command::new("git")
-            .args(&["rev-parse", "--short", "HEAD"])
+            .args(["rev-parse", "--short", "HEAD"])
             .output()?
             .stdout,
     )?)
@@ -44,7 +44,7 @@ fn commit_hash() -> Result<String, IgnoredError> {
 fn commit_date() -> Result<String, IgnoredError> {
     Ok(String::from_utf8(
         Command::new("git")
-            .args(&["log", "-1", "--date=short", "--pretty=format:%cd"])
+            .args(["log", "-1", "--date=short", "--pretty=format:%cd"])
             .output()?
             .stdout,
     )?)
@@ -32,6 +32,11 @@ test_svd_for_target() {
     popd
 
     cargo check --manifest-path $td/Cargo.toml
+
+    # `cargo check` only covers the host, so also build the crate for its architecture
+    if [ -n "${CROSS_TARGET-}" ]; then
+        cargo build --manifest-path $td/Cargo.toml --target $CROSS_TARGET ${CROSS_FLAGS-}
+    fi
 }
 
 main() {
@@ -506,6 +511,9 @@ main() {
 
         # test other targets (architectures)
         OTHER)
+            # the generated crates don't use the Cortex-M crates, which don't build for MSP430
+            sed -i '/^cortex-m/d' $td/Cargo.toml
+
             echo '[dependencies.msp430]' >> $td/Cargo.toml
             echo 'version = "0.3.0"' >> $td/Cargo.toml
 
@@ -515,6 +523,9 @@ main() {
 
         # Community-provided RISC-V SVDs
         RISC-V)
+            # the generated crates don't use the Cortex-M crates, which don't build for RISC-V
+            sed -i '/^cortex-m/d' $td/Cargo.toml
+
             echo '[dependencies.bare-metal]' >> $td/Cargo.toml
             echo 'version = "1.0.0"' >> $td/Cargo.toml
 
@@ -17,6 +17,7 @@ pub struct ArrayProxy<T, const COUNT: usize, const STRIDE: usize> {
 
 impl<T, const C: usize, const S: usize> ArrayProxy<T, C, S> {
     /// Get a reference from an [ArrayProxy] with no bounds checking.
+    #[inline(always)]
     pub unsafe fn get_ref(&self, index: usize) -> &T {
         let base = self as *const Self as usize;
         let address = base + S * index;
@@ -24,6 +25,7 @@ impl<T, const C: usize, const S: usize> ArrayProxy<T, C, S> {
     }
     /// Get a reference from an [ArrayProxy], or return `None` if the index
     /// is out of bounds.
+    #[inline(always)]
     pub fn get(&self, index: usize) -> Option<&T> {
         if index < C {
             Some(unsafe { self.get_ref(index) })
@@ -33,11 +35,18 @@ impl<T, const C: usize, const S: usize> ArrayProxy<T, C, S> {
         }
     }
     /// Return the number of items.
+    #[inline(always)]
     pub fn len(&self) -> usize { C }
+    /// Iterate over the items of an [ArrayProxy].
+    #[inline(always)]
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        (0..C).map(move |index| unsafe { self.get_ref(index) })
+    }
 }
 
 impl<T, const C: usize, const S: usize> core::ops::Index<usize> for ArrayProxy<T, C, S> {
     type Output = T;
+    #[inline(always)]
     fn index(&self, index: usize) -> &T {
         // Do a real array dereference for the bounds check.
         [(); C][index];
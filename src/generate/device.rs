@@ -1,4 +1,4 @@
-use crate::svd::Device;
+use crate::svd::{Cluster, Device, Peripheral, Register, RegisterCluster};
 use proc_macro2::{Ident, Span, TokenStream};
 use quote::{quote, ToTokens};
 
@@ -6,14 +6,19 @@ use log::debug;
 use std::fs::File;
 use std::io::Write;
 
-use crate::util::{self, Config, ToSanitizedUpperCase};
+use crate::util::{self, Config, ToSanitizedSnakeCase, ToSanitizedUpperCase};
 use crate::Target;
 use anyhow::{Context, Result};
 
-use crate::generate::{interrupt, peripheral};
+use crate::generate::{interrupt, peripheral, Skip};
 
 /// Whole device generation
-pub fn render(d: &Device, config: &Config, device_x: &mut String) -> Result<TokenStream> {
+pub fn render(
+    d: &Device,
+    config: &Config,
+    device_x: &mut String,
+    skipped: &mut Vec<Skip>,
+) -> Result<TokenStream> {
     let mut out = TokenStream::new();
 
     let commit_info = {
@@ -69,12 +74,24 @@ pub fn render(d: &Device, config: &Config, device_x: &mut String) -> Result<Toke
             // Explicitly allow a few warnings that may be verbose
             #![allow(non_camel_case_types)]
             #![allow(non_snake_case)]
-            #![no_std]
+        });
+        out.extend(if config.std_feature {
+            quote! { #![cfg_attr(not(feature = "std"), no_std)] }
+        } else {
+            quote! { #![no_std] }
         });
     }
 
+    // with `--feature_peripheral` there may be no peripheral using them
+    let allow_unused = if config.feature_peripheral {
+        quote! { #[allow(unused_imports)] }
+    } else {
+        quote! {}
+    };
     out.extend(quote! {
+        #allow_unused
         use core::ops::Deref;
+        #allow_unused
         use core::marker::PhantomData;
     });
 
@@ -89,21 +106,50 @@ pub fn render(d: &Device, config: &Config, device_x: &mut String) -> Result<Toke
             pub const NVIC_PRIO_BITS: u8 = #bits;
         });
 
+        // the features of the core, the optional ones only if the SVD has them
+        let features = [
+            ("HAS_FPU", "a floating point unit", Some(cpu.fpu_present)),
+            ("HAS_MPU", "a memory protection unit", Some(cpu.mpu_present)),
+            ("HAS_DSP", "the DSP extension", cpu.dsp_present),
+            ("HAS_ICACHE", "an instruction cache", cpu.icache_present),
+            ("HAS_DCACHE", "a data cache", cpu.dcache_present),
+            (
+                "HAS_ITCM",
+                "an instruction tightly coupled memory",
+                cpu.itcm_present,
+            ),
+            (
+                "HAS_DTCM",
+                "a data tightly coupled memory",
+                cpu.dtcm_present,
+            ),
+            (
+                "HAS_VTOR",
+                "a vector table offset register",
+                cpu.vtor_present,
+            ),
+        ];
+        for (name, desc, present) in features.iter() {
+            if let Some(present) = present {
+                let name = Ident::new(name, Span::call_site());
+                let doc = format!("Whether the core has {}", desc);
+                out.extend(quote! {
+                    #[doc = #doc]
+                    pub const #name: bool = #present;
+                });
+            }
+        }
+
         fpu_present = cpu.fpu_present;
     }
 
-    let core_peripherals: &[_] = if fpu_present {
-        &[
-            "CBP", "CPUID", "DCB", "DWT", "FPB", "FPU", "ITM", "MPU", "NVIC", "SCB", "SYST", "TPIU",
-        ]
-    } else {
-        &[
-            "CBP", "CPUID", "DCB", "DWT", "FPB", "ITM", "MPU", "NVIC", "SCB", "SYST", "TPIU",
-        ]
-    };
+    let core_peripherals = core_peripherals(d);
 
     let mut fields = TokenStream::new();
     let mut exprs = TokenStream::new();
+    let mut ids = TokenStream::new();
+    let mut bases = TokenStream::new();
+    let mut features = String::new();
     if config.target == Target::CortexM {
         out.extend(quote! {
             pub use cortex_m::peripheral::Peripherals as CorePeripherals;
@@ -146,17 +192,40 @@ pub fn render(d: &Device, config: &Config, device_x: &mut String) -> Result<Toke
         });
     }
 
-    let generic_file = std::str::from_utf8(include_bytes!("generic.rs"))?;
-    if config.generic_mod {
+    // sources of the generic runtime, with the inline attributes of `--inline`
+    let runtime =
+        |source: &[u8]| -> Result<String> { Ok(config.inline.apply(std::str::from_utf8(source)?)) };
+    let generic_file = runtime(include_bytes!("generic.rs"))?;
+    // the peripheral modules of `--module_per_peripheral` are next to `generic.rs` anyway
+    if config.generic_mod || config.module_per_peripheral {
         let mut file = File::create(config.output_dir.join("generic.rs"))?;
         writeln!(file, "{}", generic_file)?;
+        if config.target == Target::Msp430 {
+            let msp430_file = runtime(include_bytes!("generic_msp430.rs"))?;
+            writeln!(file, "\n{}", msp430_file)?;
+        }
         if config.target == Target::Msp430 && config.nightly {
-            let msp430_atomic_file =
-                std::str::from_utf8(include_bytes!("generic_msp430_atomic.rs"))?;
+            let msp430_atomic_file = runtime(include_bytes!("generic_msp430_atomic.rs"))?;
             writeln!(file, "\n{}", msp430_atomic_file)?;
         }
+        if config.target == Target::CortexM && config.bitband {
+            let bitband_file = runtime(include_bytes!("generic_bitband.rs"))?;
+            writeln!(file, "\n{}", bitband_file)?;
+        }
+        if config.ufmt {
+            let ufmt_file = runtime(include_bytes!("generic_ufmt.rs"))?;
+            writeln!(file, "\n{}", ufmt_file)?;
+        }
+        if config.critical_section {
+            let cs_file = runtime(include_bytes!("generic_cs.rs"))?;
+            writeln!(file, "\n{}", cs_file)?;
+        }
+        if config.address_blocks {
+            let blocks_file = runtime(include_bytes!("generic_address_blocks.rs"))?;
+            writeln!(file, "\n{}", blocks_file)?;
+        }
         if config.const_generic {
-            let array_proxy = std::str::from_utf8(include_bytes!("array_proxy.rs"))?;
+            let array_proxy = runtime(include_bytes!("array_proxy.rs"))?;
             writeln!(file, "{}", array_proxy)?;
         }
 
@@ -169,16 +238,40 @@ pub fn render(d: &Device, config: &Config, device_x: &mut String) -> Result<Toke
             });
         }
     } else {
-        let mut tokens = syn::parse_file(generic_file)?.into_token_stream();
+        let mut tokens = syn::parse_file(&generic_file)?.into_token_stream();
+        if config.target == Target::Msp430 {
+            let msp430_file = runtime(include_bytes!("generic_msp430.rs"))?;
+            let generic_msp430 = syn::parse_file(&msp430_file)?.into_token_stream();
+            tokens.extend(generic_msp430);
+        }
         if config.target == Target::Msp430 && config.nightly {
-            let msp430_atomic_file =
-                std::str::from_utf8(include_bytes!("generic_msp430_atomic.rs"))?;
-            let generic_msp430_atomic = syn::parse_file(msp430_atomic_file)?.into_token_stream();
+            let msp430_atomic_file = runtime(include_bytes!("generic_msp430_atomic.rs"))?;
+            let generic_msp430_atomic = syn::parse_file(&msp430_atomic_file)?.into_token_stream();
             tokens.extend(generic_msp430_atomic);
         }
+        if config.target == Target::CortexM && config.bitband {
+            let bitband_file = runtime(include_bytes!("generic_bitband.rs"))?;
+            let generic_bitband = syn::parse_file(&bitband_file)?.into_token_stream();
+            tokens.extend(generic_bitband);
+        }
+        if config.ufmt {
+            let ufmt_file = runtime(include_bytes!("generic_ufmt.rs"))?;
+            let generic_ufmt = syn::parse_file(&ufmt_file)?.into_token_stream();
+            tokens.extend(generic_ufmt);
+        }
+        if config.critical_section {
+            let cs_file = runtime(include_bytes!("generic_cs.rs"))?;
+            let generic_cs = syn::parse_file(&cs_file)?.into_token_stream();
+            tokens.extend(generic_cs);
+        }
+        if config.address_blocks {
+            let blocks_file = runtime(include_bytes!("generic_address_blocks.rs"))?;
+            let generic_blocks = syn::parse_file(&blocks_file)?.into_token_stream();
+            tokens.extend(generic_blocks);
+        }
         if config.const_generic {
-            let array_proxy = std::str::from_utf8(include_bytes!("array_proxy.rs"))?;
-            let generic_array_proxy = syn::parse_file(array_proxy)?.into_token_stream();
+            let array_proxy = runtime(include_bytes!("array_proxy.rs"))?;
+            let generic_array_proxy = syn::parse_file(&array_proxy)?.into_token_stream();
             tokens.extend(generic_array_proxy);
         }
 
@@ -193,16 +286,26 @@ pub fn render(d: &Device, config: &Config, device_x: &mut String) -> Result<Toke
     }
 
     debug!("Rendering interrupts");
-    out.extend(interrupt::render(config.target, &d.peripherals, device_x)?);
+    out.extend(interrupt::render(config, &d.peripherals, device_x)?);
 
     for p in &d.peripherals {
         if config.target == Target::CortexM && core_peripherals.contains(&&*p.name.to_uppercase()) {
             // Core peripherals are handled above
+            skipped.push(Skip::new(
+                &p.name,
+                "core peripheral of the `cortex-m` crate",
+            ));
             continue;
         }
 
         debug!("Rendering peripheral {}", p.name);
-        match peripheral::render(p, &d.peripherals, &d.default_register_properties, config) {
+        match peripheral::render(
+            p,
+            &d.peripherals,
+            &d.default_register_properties,
+            config,
+            skipped,
+        ) {
             Ok(periph) => out.extend(periph),
             Err(e) => {
                 let descrip = p.description.as_deref().unwrap_or("No description");
@@ -233,16 +336,91 @@ pub fn render(d: &Device, config: &Config, device_x: &mut String) -> Result<Toke
         {
             // No register block will be generated so don't put this peripheral
             // in the `Peripherals` struct
+            skipped.push(Skip::new(&p.name, "no registers"));
             continue;
         }
 
+        let feature_attribute = if config.feature_peripheral {
+            let feature = p.name.to_lowercase();
+            let requires = required_peripherals(p)
+                .iter()
+                .map(|name| format!("{:?}", name.to_lowercase()))
+                .collect::<Vec<_>>();
+            features += &format!("{} = [{}]\n", feature, requires.join(", "));
+            quote! { #[cfg(feature = #feature)] }
+        } else {
+            quote! {}
+        };
+
+        let address = util::hex(p.base_address);
         let p = p.name.to_sanitized_upper_case();
         let id = Ident::new(&p, Span::call_site());
         fields.extend(quote! {
             #[doc = #p]
+            #feature_attribute
             pub #id: #id,
         });
-        exprs.extend(quote!(#id: #id { _marker: PhantomData },));
+        exprs.extend(quote!(#feature_attribute #id: #id { _marker: PhantomData },));
+        ids.extend(quote! {
+            #[doc = #p]
+            #feature_attribute
+            #id,
+        });
+        bases.extend(quote!(#feature_attribute Self::#id => #address,));
+    }
+
+    if config.feature_peripheral {
+        let mut file = File::create(config.output_dir.join("features.toml"))?;
+        write!(file, "[features]\n{}", features)?;
+    }
+
+    if config.addresses {
+        let mut peripherals: Vec<_> = d.peripherals.iter().collect();
+        peripherals.sort_by_key(|p| p.base_address);
+        let mut consts = TokenStream::new();
+        for p in peripherals {
+            let id = Ident::new(&p.name.to_sanitized_upper_case(), Span::call_site());
+            let address = util::hex(p.base_address);
+            let doc = format!("Base address of {}", p.name);
+            consts.extend(quote! {
+                #[doc = #doc]
+                pub const #id: usize = #address;
+            });
+
+            let registers = p.registers.as_ref().or_else(|| {
+                let original = p.derived_from.as_ref()?;
+                d.peripherals
+                    .iter()
+                    .find(|x| &x.name == original)?
+                    .registers
+                    .as_ref()
+            });
+            if let Some(registers) = registers {
+                let mut registers_consts = TokenStream::new();
+                register_addresses(
+                    registers,
+                    p.base_address,
+                    &[],
+                    "",
+                    config,
+                    &mut registers_consts,
+                );
+                let id = Ident::new(&p.name.to_sanitized_snake_case(), Span::call_site());
+                let doc = format!("Addresses of the registers of {}", p.name);
+                consts.extend(quote! {
+                    #[doc = #doc]
+                    pub mod #id {
+                        #registers_consts
+                    }
+                });
+            }
+        }
+        out.extend(quote! {
+            ///Base addresses of all the peripherals, and addresses of their registers
+            pub mod addresses {
+                #consts
+            }
+        });
     }
 
     let span = Span::call_site();
@@ -254,19 +432,25 @@ pub fn render(d: &Device, config: &Config, device_x: &mut String) -> Result<Toke
         Target::Mips => Some(Ident::new("mips_mcu", span)),
         Target::None => None,
     }
-    .map(|krate| {
+    .map(|krate| quote!(#krate::interrupt::free));
+    // with `--critical_section` the `critical-section` feature replaces the guard of the target
+    let (take_cs, not_cs) = if config.critical_section {
+        let take = take_with(quote!(critical_section::with));
+        (
+            Some(quote! {
+                #[cfg(feature = "critical-section")]
+                #take
+            }),
+            Some(quote!(#[cfg(not(feature = "critical-section"))])),
+        )
+    } else {
+        (None, None)
+    };
+    let take = take.map(|guard| {
+        let take = take_with(guard);
         quote! {
-            ///Returns all the peripherals *once*
-            #[inline]
-            pub fn take() -> Option<Self> {
-                #krate::interrupt::free(|_| {
-                    if unsafe { DEVICE_PERIPHERALS } {
-                        None
-                    } else {
-                        Some(unsafe { Peripherals::steal() })
-                    }
-                })
-            }
+            #not_cs
+            #take
         }
     });
 
@@ -284,6 +468,7 @@ pub fn render(d: &Device, config: &Config, device_x: &mut String) -> Result<Toke
         }
 
         impl Peripherals {
+            #take_cs
             #take
 
             ///Unchecked version of `Peripherals::take`
@@ -296,7 +481,148 @@ pub fn render(d: &Device, config: &Config, device_x: &mut String) -> Result<Toke
                 }
             }
         }
+
+        ///Identity of a peripheral, e.g. to index tables of state of drivers of several instances
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+        pub enum PeripheralId {
+            #ids
+        }
+
+        impl PeripheralId {
+            ///Base address of the register block of the peripheral
+            #[inline(always)]
+            pub const fn base(&self) -> usize {
+                match *self {
+                    #bases
+                }
+            }
+        }
     });
 
     Ok(out)
 }
+
+/// Other peripherals the generated code of `p` refers to
+fn required_peripherals(p: &Peripheral) -> Vec<&str> {
+    let mut peripherals = vec![];
+    // the register block of a derived peripheral is in the module of the original
+    if let (Some(df), None) = (&p.derived_from, &p.registers) {
+        peripherals.push(df.as_str());
+    }
+    // enumerated values derived from fields of other peripherals
+    for register in p.all_registers() {
+        for field in register.fields.iter().flatten() {
+            for evs in &field.enumerated_values {
+                if let Some(path) = &evs.derived_from {
+                    let parts = path.split('.').collect::<Vec<_>>();
+                    if parts.len() == 4 && parts[0] != p.name {
+                        peripherals.push(parts[0]);
+                    }
+                }
+            }
+        }
+    }
+    peripherals.sort_unstable();
+    peripherals.dedup();
+    peripherals
+}
+
+/// Adds the addresses of the registers in `ercs` to `consts`, as `const`s or, for registers in
+/// arrays, as `const fn`s taking an index per array in `arrays` (name and increment)
+fn register_addresses(
+    ercs: &[RegisterCluster],
+    address: u64,
+    arrays: &[(Ident, u64)],
+    prefix: &str,
+    config: &Config,
+    consts: &mut TokenStream,
+) {
+    for erc in ercs {
+        match erc {
+            RegisterCluster::Register(register) => {
+                let name = format!(
+                    "{}{}",
+                    prefix,
+                    util::name_of(register, config.ignore_groups)
+                );
+                let address = address + register.address_offset as u64;
+                let mut arrays = arrays.to_vec();
+                if let Register::Array(_, dim) = register {
+                    let index = Ident::new(&name.to_sanitized_snake_case(), Span::call_site());
+                    arrays.push((index, dim.dim_increment as u64));
+                }
+                let doc = format!("Address of the {} register", name);
+                let address = util::hex(address);
+                if arrays.is_empty() {
+                    let id = Ident::new(&name.to_sanitized_upper_case(), Span::call_site());
+                    consts.extend(quote! {
+                        #[doc = #doc]
+                        pub const #id: usize = #address;
+                    });
+                } else {
+                    let id = Ident::new(&name.to_sanitized_snake_case(), Span::call_site());
+                    let indexes = arrays.iter().map(|(index, _)| index);
+                    let terms = arrays.iter().map(|(index, increment)| {
+                        let increment = util::hex(*increment);
+                        quote! { + #index * #increment }
+                    });
+                    consts.extend(quote! {
+                        #[doc = #doc]
+                        #[inline(always)]
+                        pub const fn #id(#(#indexes: usize),*) -> usize {
+                            #address #(#terms)*
+                        }
+                    });
+                }
+            }
+            RegisterCluster::Cluster(cluster) => {
+                let name = util::replace_suffix(&cluster.name, "");
+                let mut arrays = arrays.to_vec();
+                if let Cluster::Array(_, dim) = cluster {
+                    let index = Ident::new(&name.to_sanitized_snake_case(), Span::call_site());
+                    arrays.push((index, dim.dim_increment as u64));
+                }
+                register_addresses(
+                    &cluster.children,
+                    address + cluster.address_offset as u64,
+                    &arrays,
+                    &format!("{}{}_", prefix, name),
+                    config,
+                    consts,
+                );
+            }
+        }
+    }
+}
+
+/// Names of the Cortex-M core peripherals, which come from the `cortex-m` crate instead of the
+/// SVD file
+pub(crate) fn core_peripherals(d: &Device) -> &'static [&'static str] {
+    // Retaining the previous assumption of an FPU without a `<cpu>`
+    if d.cpu.as_ref().map_or(true, |cpu| cpu.fpu_present) {
+        &[
+            "CBP", "CPUID", "DCB", "DWT", "FPB", "FPU", "ITM", "MPU", "NVIC", "SCB", "SYST", "TPIU",
+        ]
+    } else {
+        &[
+            "CBP", "CPUID", "DCB", "DWT", "FPB", "ITM", "MPU", "NVIC", "SCB", "SYST", "TPIU",
+        ]
+    }
+}
+
+/// Returns `Peripherals::take`, guarded by the critical section of `guard`
+fn take_with(guard: TokenStream) -> TokenStream {
+    quote! {
+        ///Returns all the peripherals *once*
+        #[inline]
+        pub fn take() -> Option<Self> {
+            #guard(|_| {
+                if unsafe { DEVICE_PERIPHERALS } {
+                    None
+                } else {
+                    Some(unsafe { Peripherals::steal() })
+                }
+            })
+        }
+    }
+}
@@ -16,7 +16,7 @@ pub trait Readable: RegisterSpec {
 
 /// Trait implemented by writeable registers.
 ///
-/// This enables the  `write`, `write_with_zero` and `reset` methods.
+/// This enables the  `write`, `write_with_zero`, `write_from` and `reset` methods.
 ///
 /// Registers marked with `Readable` can be also `modify`'ed.
 pub trait Writable: RegisterSpec {
@@ -24,29 +24,76 @@ pub trait Writable: RegisterSpec {
     type Writer: From<W<Self>> + core::ops::DerefMut<Target = W<Self>>;
 }
 
+/// Trait implemented by writeable registers which can only be written once, like lock bits.
+///
+/// These are the `writeOnce` and `read-writeOnce` registers of the SVD, whose later writes are
+/// ignored by the hardware, usually until the next reset. This enables the `write_once` method.
+pub trait WriteOnce: Writable {}
+
 /// Reset value of the register.
 ///
 /// This value is the initial value for the `write` method. It can also be directly written to the
 /// register by using the `reset` method.
 pub trait Resettable: RegisterSpec {
     /// Reset value of the register.
-    fn reset_value() -> Self::Ux;
+    ///
+    /// Unlike `reset_value` this can be used in constant expressions.
+    const RESET_VALUE: Self::Ux;
+
+    /// Bits of the register defined by `RESET_VALUE`, from the `resetMask` of the register.
+    ///
+    /// All ones, unless the register is readable and its `resetMask` doesn't cover all of it.
+    const RESET_MASK: Self::Ux;
+
+    /// Reset value of the register.
+    #[inline(always)]
+    fn reset_value() -> Self::Ux {
+        Self::RESET_VALUE
+    }
 }
 
+/// Storage of the register value.
+#[cfg(not(feature = "mock"))]
+type RegCell<T> = vcell::VolatileCell<T>;
+
+/// Storage of the register value, in host memory for tests.
+#[cfg(feature = "mock")]
+type RegCell<T> = core::cell::Cell<T>;
+
 /// This structure provides volatile access to registers.
+///
+/// With the `mock` feature the register is a plain `Cell` instead, so code accessing registers
+/// can run on the host, e.g. in unit tests.
+///
+/// `Reg` is `Send` but not `Sync`: `modify` is a read followed by a write, which would race
+/// if two threads or interrupt handlers shared a `&Reg`. Exclusive access is up to the owner
+/// of the peripheral, or is enforced by the owned registers of `--split_registers`.
 #[repr(transparent)]
 pub struct Reg<REG: RegisterSpec> {
-    register: vcell::VolatileCell<REG::Ux>,
+    register: RegCell<REG::Ux>,
     _marker: marker::PhantomData<REG>,
 }
 
 unsafe impl<REG: RegisterSpec> Send for Reg<REG> where REG::Ux: Send {}
 
+#[cfg(feature = "mock")]
+impl<REG: RegisterSpec> Reg<REG> {
+    /// Creates a mock register holding `value`.
+    #[inline(always)]
+    pub fn new(value: REG::Ux) -> Self {
+        Self {
+            register: RegCell::new(value),
+            _marker: marker::PhantomData,
+        }
+    }
+}
+
 impl<REG: RegisterSpec> Reg<REG> {
     /// Returns the underlying memory address of register.
     ///
+    /// This is the address to give to a DMA channel reading or writing the register:
     /// ```ignore
-    /// let reg_ptr = periph.reg.as_ptr();
+    /// dma.ch1.par.write(|w| unsafe { w.bits(usart1.dr.as_ptr() as u32) });
     /// ```
     #[inline(always)]
     pub fn as_ptr(&self) -> *mut REG::Ux {
@@ -54,6 +101,34 @@ impl<REG: RegisterSpec> Reg<REG> {
     }
 }
 
+/// Owned register, handed out once by the `split` method of a peripheral.
+///
+/// The register is only reachable through `&mut self`, so holding the `Owned` gives exclusive
+/// access to it, and it can be moved to another thread or interrupt handler.
+pub struct Owned<REG: RegisterSpec + 'static> {
+    reg: &'static Reg<REG>,
+}
+
+unsafe impl<REG: RegisterSpec + 'static> Send for Owned<REG> where REG::Ux: Send {}
+
+impl<REG: RegisterSpec + 'static> Owned<REG> {
+    /// Takes ownership of `reg`.
+    ///
+    /// # Safety
+    ///
+    /// No other `Owned` or reference may be used to access `reg`.
+    #[inline(always)]
+    pub unsafe fn new(reg: &'static Reg<REG>) -> Self {
+        Self { reg }
+    }
+
+    /// Borrows the register for as long as `self` is borrowed mutably.
+    #[inline(always)]
+    pub fn reg(&mut self) -> &Reg<REG> {
+        self.reg
+    }
+}
+
 impl<REG: Readable> Reg<REG> {
     /// Reads the contents of a `Readable` register.
     ///
@@ -79,10 +154,24 @@ impl<REG: Readable> Reg<REG> {
 impl<REG: Resettable + Writable> Reg<REG> {
     /// Writes the reset value to `Writable` register.
     ///
-    /// Resets the register to its initial state.
+    /// Resets the register to its initial state. If the reset value only defines the bits of
+    /// `RESET_MASK`, the register is read first and its other bits are written back unchanged.
     #[inline(always)]
-    pub fn reset(&self) {
-        self.register.set(REG::reset_value())
+    pub fn reset(&self)
+    where
+        REG::Ux: Default
+            + PartialEq
+            + core::ops::BitOr<Output = REG::Ux>
+            + core::ops::BitAnd<Output = REG::Ux>
+            + core::ops::Not<Output = REG::Ux>,
+    {
+        let mask = REG::RESET_MASK;
+        if mask == !REG::Ux::default() {
+            self.register.set(REG::reset_value())
+        } else {
+            let bits = self.register.get();
+            self.register.set((bits & !mask) | (REG::reset_value() & mask))
+        }
     }
 
     /// Writes bits to a `Writable` register.
@@ -103,7 +192,7 @@ impl<REG: Resettable + Writable> Reg<REG> {
     #[inline(always)]
     pub fn write<F>(&self, f: F)
     where
-        F: FnOnce(&mut REG::Writer) -> &mut W<REG>
+        F: FnOnce(&mut REG::Writer) -> &mut W<REG>,
     {
         self.register.set(
             f(&mut REG::Writer::from(W {
@@ -115,6 +204,23 @@ impl<REG: Resettable + Writable> Reg<REG> {
     }
 }
 
+impl<REG: Resettable + WriteOnce> Reg<REG> {
+    /// Writes bits to a `WriteOnce` register, like `write`.
+    ///
+    /// Only the first write to the register has an effect, so all the fields to set have to be
+    /// set by this one:
+    /// ```ignore
+    /// periph.lock.write_once(|w| w.key().bits(KEY).lock().set_bit());
+    /// ```
+    #[inline(always)]
+    pub fn write_once<F>(&self, f: F)
+    where
+        F: FnOnce(&mut REG::Writer) -> &mut W<REG>,
+    {
+        self.write(f)
+    }
+}
+
 impl<REG: Writable> Reg<REG>
 where
     REG::Ux: Default,
@@ -125,7 +231,7 @@ where
     #[inline(always)]
     pub unsafe fn write_with_zero<F>(&self, f: F)
     where
-        F: FnOnce(&mut REG::Writer) -> &mut W<REG>
+        F: FnOnce(&mut REG::Writer) -> &mut W<REG>,
     {
         self.register.set(
             (*f(&mut REG::Writer::from(W {
@@ -137,6 +243,69 @@ where
     }
 }
 
+impl<REG: Writable> Reg<REG> {
+    /// Writes bits to a `Writable` register starting from `init`.
+    ///
+    /// Similar to `write`, but unused bits will contain the bits of `init` instead of the reset
+    /// value. The register is not read, e.g. to copy the contents of another register:
+    /// ```ignore
+    /// let bits = periph.reg1.read().bits();
+    /// unsafe { periph.reg2.write_from(bits, |w| w.field1().set_bit()) };
+    /// ```
+    #[inline(always)]
+    pub unsafe fn write_from<F>(&self, init: REG::Ux, f: F)
+    where
+        F: FnOnce(&mut REG::Writer) -> &mut W<REG>,
+    {
+        self.register.set(
+            f(&mut REG::Writer::from(W {
+                bits: init,
+                _reg: marker::PhantomData,
+            }))
+            .bits,
+        );
+    }
+
+    /// Writes a writer that was set up beforehand, outside of a closure.
+    ///
+    /// With `--impl_default` writers of registers with a reset value implement `Default`:
+    /// ```ignore
+    /// let mut w = periph::reg::W::default();
+    /// w.field1().set_bit();
+    /// periph.reg.write_value(w);
+    /// ```
+    #[inline(always)]
+    pub fn write_value(&self, w: REG::Writer) {
+        self.register.set(w.bits);
+    }
+}
+
+impl<REG: Readable + Resettable + Writable> Reg<REG>
+where
+    REG::Ux: core::ops::BitOr<Output = REG::Ux>
+        + core::ops::BitAnd<Output = REG::Ux>
+        + core::ops::Not<Output = REG::Ux>,
+{
+    /// Resets the bits of `mask` to their reset value, leaving the other bits unchanged.
+    ///
+    /// E.g. to restore the defaults of a group of fields:
+    /// ```ignore
+    /// unsafe { periph.reg.reset_masked(0xff00) };
+    /// ```
+    /// This is a read-modify-write like `modify`, not atomic: a write to the register between the
+    /// read and the write is lost.
+    ///
+    /// # Safety
+    ///
+    /// `mask` must cover whole fields, otherwise the value of a field mixes bits of its current
+    /// and reset values, which may not be valid.
+    #[inline(always)]
+    pub unsafe fn reset_masked(&self, mask: REG::Ux) {
+        let bits = self.register.get();
+        self.register.set((bits & !mask) | (REG::reset_value() & mask));
+    }
+}
+
 impl<REG: Readable + Writable> Reg<REG> {
     /// Modifies the contents of the register by reading and then writing it.
     ///
@@ -158,7 +327,7 @@ impl<REG: Readable + Writable> Reg<REG> {
     #[inline(always)]
     pub fn modify<F>(&self, f: F)
     where
-        for<'w> F: FnOnce(&REG::Reader, &'w mut REG::Writer) -> &'w mut W<REG>
+        for<'w> F: FnOnce(&REG::Reader, &'w mut REG::Writer) -> &'w mut W<REG>,
     {
         let bits = self.register.get();
         self.register.set(
@@ -175,6 +344,133 @@ impl<REG: Readable + Writable> Reg<REG> {
             .bits,
         );
     }
+
+    /// Modifies the contents of the register like `modify`, with a closure that doesn't have to
+    /// return the writer.
+    ///
+    /// This reads better when the fields to change depend on the value read:
+    /// ```ignore
+    /// periph.reg.modify_ext(|r, w| {
+    ///     if r.field1().bit_is_set() {
+    ///         w.field2().set_bit();
+    ///     }
+    /// });
+    /// ```
+    /// Other fields will have the value they had before the call to `modify_ext`.
+    #[inline(always)]
+    pub fn modify_ext<F>(&self, f: F)
+    where
+        F: FnOnce(&REG::Reader, &mut REG::Writer),
+    {
+        let bits = self.register.get();
+        let mut writer = REG::Writer::from(W {
+            bits,
+            _reg: marker::PhantomData,
+        });
+        f(
+            &REG::Reader::from(R {
+                bits,
+                _reg: marker::PhantomData,
+            }),
+            &mut writer,
+        );
+        self.register.set(writer.bits);
+    }
+
+    /// Modifies the contents of the register like `modify`, but only writes it if the new value
+    /// differs from the one read.
+    ///
+    /// Returns `true` if the register was written. This avoids the side effects of writing, or
+    /// just a bus access, when nothing changes:
+    /// ```ignore
+    /// if periph.reg.modify_if_changed(|_, w| w.field1().set_bit()) {
+    ///     // field1 was clear
+    /// }
+    /// ```
+    /// The whole register is compared, including bits that the closure didn't write. Like
+    /// `modify`, this is not atomic: the register can change between the read and the write.
+    #[inline(always)]
+    pub fn modify_if_changed<F>(&self, f: F) -> bool
+    where
+        REG::Ux: PartialEq,
+        for<'w> F: FnOnce(&REG::Reader, &'w mut REG::Writer) -> &'w mut W<REG>,
+    {
+        let bits = self.register.get();
+        let new_bits = f(
+            &REG::Reader::from(R {
+                bits,
+                _reg: marker::PhantomData,
+            }),
+            &mut REG::Writer::from(W {
+                bits,
+                _reg: marker::PhantomData,
+            }),
+        )
+        .bits;
+        let changed = new_bits != bits;
+        if changed {
+            self.register.set(new_bits);
+        }
+        changed
+    }
+
+    /// Modifies the contents of the register like `modify`, returning the value written.
+    ///
+    /// Handy to log or check the result of a read-modify-write:
+    /// ```ignore
+    /// let bits = periph.reg.modify_bits(|_, w| w.field1().set_bit());
+    /// defmt::debug!("reg = {:#x}", bits);
+    /// ```
+    #[inline(always)]
+    pub fn modify_bits<F>(&self, f: F) -> REG::Ux
+    where
+        for<'w> F: FnOnce(&REG::Reader, &'w mut REG::Writer) -> &'w mut W<REG>,
+    {
+        let bits = self.register.get();
+        let new_bits = f(
+            &REG::Reader::from(R {
+                bits,
+                _reg: marker::PhantomData,
+            }),
+            &mut REG::Writer::from(W {
+                bits,
+                _reg: marker::PhantomData,
+            }),
+        )
+        .bits;
+        self.register.set(new_bits);
+        new_bits
+    }
+
+    /// Writes to the register like `write_with_zero`, but keeps the bits of `keep_mask` as read.
+    ///
+    /// The register is read first, the bits outside of `keep_mask` are cleared and then the
+    /// closure runs, so it can still change the kept bits. E.g. to clear a register except for a
+    /// latched field:
+    /// ```ignore
+    /// unsafe { periph.reg.write_preserving(0x0000_0100, |w| w.field1().set_bit()) };
+    /// ```
+    /// This is a read-modify-write like `modify`, not atomic: a write to the register between the
+    /// read and the write is lost.
+    ///
+    /// # Safety
+    ///
+    /// Like `write_with_zero`, fields that are not kept are written 0, which may not be valid.
+    #[inline(always)]
+    pub unsafe fn write_preserving<F>(&self, keep_mask: REG::Ux, f: F)
+    where
+        REG::Ux: core::ops::BitAnd<Output = REG::Ux>,
+        F: FnOnce(&mut REG::Writer) -> &mut W<REG>,
+    {
+        let bits = self.register.get() & keep_mask;
+        self.register.set(
+            f(&mut REG::Writer::from(W {
+                bits,
+                _reg: marker::PhantomData,
+            }))
+            .bits,
+        );
+    }
 }
 
 /// Register reader.
@@ -192,6 +488,36 @@ impl<REG: RegisterSpec> R<REG> {
     pub fn bits(&self) -> REG::Ux {
         self.bits
     }
+    /// Consumes the reader, returning the raw bits read from the register.
+    #[inline(always)]
+    pub fn into_bits(self) -> REG::Ux {
+        self.bits
+    }
+}
+
+impl<REG: RegisterSpec> R<REG>
+where
+    REG::Ux: Default
+        + core::ops::BitAnd<Output = REG::Ux>
+        + core::ops::Not<Output = REG::Ux>
+        + core::ops::Shl<usize, Output = REG::Ux>
+        + core::ops::Shr<usize, Output = REG::Ux>,
+{
+    /// Reads the raw bits `range` of the register, shifted down to bit 0.
+    ///
+    /// This bypasses the fields of the register, for the bits the SVD file doesn't describe:
+    /// ```ignore
+    /// let revision = periph.reg.read().bits_range(8..12);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// If `range` is empty or decreasing, or ends past the last bit of the register.
+    #[inline(always)]
+    pub fn bits_range(&self, range: core::ops::Range<usize>) -> REG::Ux {
+        let mask = range_mask::<REG::Ux>(&range);
+        (self.bits >> range.start) & mask
+    }
 }
 
 impl<REG: RegisterSpec, FI> PartialEq<FI> for R<REG>
@@ -205,6 +531,15 @@ where
     }
 }
 
+impl<REG: RegisterSpec> core::fmt::Debug for R<REG>
+where
+    REG::Ux: core::fmt::LowerHex,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "{:#x}", self.bits)
+    }
+}
+
 /// Register writer.
 ///
 /// Used as an argument to the closures in the `write` and `modify` methods of the register.
@@ -221,11 +556,139 @@ impl<REG: RegisterSpec> W<REG> {
         self.bits = bits;
         self
     }
+
+    /// Value the register will be written with, as set up so far.
+    #[inline(always)]
+    pub fn current_bits(&self) -> REG::Ux {
+        self.bits
+    }
+
+    /// Creates a writer holding the reset value, for the `Default` of `--impl_default`.
+    #[allow(unused)]
+    #[inline(always)]
+    pub(crate) fn reset() -> Self
+    where
+        REG: Resettable,
+    {
+        Self {
+            bits: REG::reset_value(),
+            _reg: marker::PhantomData,
+        }
+    }
+
+    /// Replaces the value being written with the bits read from another register.
+    ///
+    /// # Safety
+    ///
+    /// The bits are copied as they are, so `SRC` must have its fields at the same offsets and
+    /// with the same widths as this register, and its value must be a valid value of this one.
+    #[inline(always)]
+    pub unsafe fn copy_from<SRC>(&mut self, from: &R<SRC>) -> &mut Self
+    where
+        SRC: RegisterSpec<Ux = REG::Ux>,
+    {
+        self.bits = from.bits;
+        self
+    }
+}
+
+impl<REG: Readable> W<REG> {
+    /// Replaces the value being written with a value read from the register.
+    ///
+    /// Fields not set after this keep the value they had when `from` was read.
+    #[inline(always)]
+    pub fn preserve(&mut self, from: &R<REG>) -> &mut Self {
+        self.bits = from.bits;
+        self
+    }
+}
+
+impl<REG: RegisterSpec> W<REG>
+where
+    REG::Ux: core::ops::BitOr<Output = REG::Ux>
+        + core::ops::BitAnd<Output = REG::Ux>
+        + core::ops::Not<Output = REG::Ux>,
+{
+    /// Sets the bits of `mask`, leaving the other bits unchanged.
+    ///
+    /// Like `bits`, this doesn't check that the result is a valid value of the register.
+    #[inline(always)]
+    pub unsafe fn set_bits(&mut self, mask: REG::Ux) -> &mut Self {
+        self.bits = self.bits | mask;
+        self
+    }
+
+    /// Clears the bits of `mask`, leaving the other bits unchanged.
+    ///
+    /// Like `bits`, this doesn't check that the result is a valid value of the register.
+    #[inline(always)]
+    pub unsafe fn clear_bits(&mut self, mask: REG::Ux) -> &mut Self {
+        self.bits = self.bits & !mask;
+        self
+    }
+}
+
+impl<REG: RegisterSpec> W<REG>
+where
+    REG::Ux: Default
+        + core::ops::BitOr<Output = REG::Ux>
+        + core::ops::BitAnd<Output = REG::Ux>
+        + core::ops::Not<Output = REG::Ux>
+        + core::ops::Shl<usize, Output = REG::Ux>,
+{
+    /// Writes `value` to the raw bits `range` of the register, leaving the other bits unchanged.
+    ///
+    /// This bypasses the fields of the register, for the bits the SVD file doesn't describe:
+    /// ```ignore
+    /// periph.reg.write(|w| unsafe { w.bits_range(8..12, 0b1010) });
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// Like `bits`, this doesn't check that the result is a valid value of the register. The bits
+    /// of `value` which don't fit in `range` are dropped.
+    ///
+    /// # Panics
+    ///
+    /// If `range` is empty or decreasing, or ends past the last bit of the register.
+    #[inline(always)]
+    pub unsafe fn bits_range(
+        &mut self,
+        range: core::ops::Range<usize>,
+        value: REG::Ux,
+    ) -> &mut Self {
+        let mask = range_mask::<REG::Ux>(&range);
+        self.bits = (self.bits & !(mask << range.start)) | ((value & mask) << range.start);
+        self
+    }
+}
+
+/// Mask of as many low bits as `range` has.
+///
+/// Panics if `range` isn't a non-empty range of the bits of `U`, also in release builds, as the
+/// mask and the shift by `range.start` would silently be wrong otherwise.
+#[inline(always)]
+fn range_mask<U>(range: &core::ops::Range<usize>) -> U
+where
+    U: Default + core::ops::Not<Output = U> + core::ops::Shl<usize, Output = U>,
+{
+    let bits = core::mem::size_of::<U>() * 8;
+    assert!(
+        range.start < range.end && range.end <= bits,
+        "bit range out of the register"
+    );
+    let width = range.end - range.start;
+    if width >= bits {
+        !U::default()
+    } else {
+        !(!U::default() << width)
+    }
 }
 
 /// Field reader.
 ///
-/// Result of the `read` methods of fields.
+/// Result of the field methods of the register reader. `T` is the enumeration returned by
+/// `variant`, or the raw field type if the field has no enumerated values.
 pub struct FieldReader<U, T> {
     pub(crate) bits: U,
     _reg: marker::PhantomData<T>,
@@ -250,6 +713,11 @@ where
     pub fn bits(&self) -> U {
         self.bits
     }
+    /// Consumes the reader, returning the raw bits read from the field.
+    #[inline(always)]
+    pub fn into_bits(self) -> U {
+        self.bits
+    }
 }
 
 impl<U, T, FI> PartialEq<FI> for FieldReader<U, T>
@@ -279,4 +747,513 @@ impl<FI> FieldReader<bool, FI> {
     pub fn bit_is_set(&self) -> bool {
         self.bit()
     }
+    /// Returns `true` if the bit is 1. Same as `bit_is_set`.
+    #[inline(always)]
+    pub fn is_one(&self) -> bool {
+        self.bit()
+    }
+    /// Returns `true` if the bit is 0. Same as `bit_is_clear`.
+    #[inline(always)]
+    pub fn is_zero(&self) -> bool {
+        !self.bit()
+    }
+    /// Value of the field as `0` or `1`.
+    #[inline(always)]
+    pub fn bits_u8(&self) -> u8 {
+        self.bit() as u8
+    }
+}
+
+/// Value read from a field whose enumerated values don't cover every bit pattern.
+///
+/// Returned by the `variant` method of such field readers.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Variant<U, T> {
+    /// Bit pattern with a matching enumerated value.
+    Val(T),
+    /// Reserved bit pattern.
+    Res(U),
+}
+
+/// Marker for field writers which accept every value that fits in the field.
+pub struct Safe;
+
+/// Marker for field writers where some raw values are reserved and writing them is `unsafe`.
+pub struct Unsafe;
+
+/// Error returned by the `checked_bits` method of field writers if the value is not allowed by
+/// the write constraint of the field.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OutOfRange;
+
+/// Bound check of the `I` index of an array of `N` elements at build time.
+///
+/// Used by the const generic accessors of register and cluster arrays, e.g. `ch::<3>()`.
+pub struct ConstIndex<const I: usize, const N: usize>;
+
+impl<const I: usize, const N: usize> ConstIndex<I, N> {
+    /// Fails to evaluate, and so to build, if `I` is not less than `N`. Like other constants of
+    /// generic types it is only evaluated by `cargo build`, not by `cargo check`.
+    pub const CHECK: usize = N - 1 - I;
+}
+
+/// Size in bytes of the register block `B`.
+///
+/// With `--check_layout`, the size a block has in the SVD is compared to its `size_of` by the
+/// type of a `BlockSize` constant, so that a mismatch fails to compile with both sizes.
+pub struct BlockSize<B, const SIZE: usize>(marker::PhantomData<B>);
+
+impl<B, const SIZE: usize> BlockSize<B, SIZE> {
+    /// The only value of the type.
+    pub const SIZE: Self = Self(marker::PhantomData);
+}
+
+/// Mask of a field.
+///
+/// The mask covers the bits of the field, shifted to bit 0 of the register type `U`. It is
+/// implemented by all field writers, so the mask of a field can be computed in generic code:
+/// ```ignore
+/// fn field_mask<F: Mask<u32>>(offset: usize) -> u32 {
+///     F::MASK << offset
+/// }
+/// ```
+pub trait Mask<U> {
+    /// Mask of the field.
+    const MASK: U;
+}
+
+/// Error returned by the `TryFrom<&str>` implementation of enumerated values with the
+/// `str-names` feature if no variant has the given name.
+#[cfg(feature = "str-names")]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct UnknownName;
+
+/// Error returned by the `_wait_set_timeout` and `_wait_clear_timeout` methods of registers if
+/// the field didn't change within the given number of reads.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Timeout;
+
+/// Description of a field, in the `FIELDS` table of each register with the `reflect` feature.
+#[cfg(feature = "reflect")]
+#[derive(Clone, Copy, Debug)]
+pub struct FieldInfo {
+    /// Name of the field in the SVD, with the index for elements of field arrays.
+    pub name: &'static str,
+    /// Position of the lowest bit of the field.
+    pub offset: u8,
+    /// Number of bits of the field.
+    pub width: u8,
+    /// Names of the enumerated values of the field, empty if it has none.
+    pub values: &'static [&'static str],
+}
+
+/// Field writer.
+///
+/// Result of the field methods of the register writer. `N` is the raw field type (`bool` for
+/// single-bit fields), `FI` the enumeration accepted by `variant` and `S` either `Safe` or
+/// `Unsafe`. The position of the field inside the register is given by `WIDTH` and `OFFSET`.
+///
+/// Nothing is written to the field until one of its methods is called, so a field writer which
+/// is dropped unused is a mistake.
+#[must_use = "the field is only written by calling a method of its writer, e.g. `bits` or `set_bit`"]
+pub struct WProxy<'a, U, REG, N, FI, S, const WIDTH: u8, const OFFSET: usize>
+where
+    REG: Writable + RegisterSpec<Ux = U>,
+{
+    pub(crate) w: &'a mut REG::Writer,
+    _field: marker::PhantomData<(N, FI, S)>,
+}
+
+impl<'a, U, REG, N, FI, S, const WIDTH: u8, const OFFSET: usize>
+    WProxy<'a, U, REG, N, FI, S, WIDTH, OFFSET>
+where
+    REG: Writable + RegisterSpec<Ux = U>,
+{
+    /// Field width.
+    pub const WIDTH: u8 = WIDTH;
+
+    /// Field offset.
+    pub const OFFSET: usize = OFFSET;
+
+    /// Creates a new instance of the writer.
+    #[allow(unused)]
+    #[inline(always)]
+    pub(crate) fn new(w: &'a mut REG::Writer) -> Self {
+        Self {
+            w,
+            _field: marker::PhantomData,
+        }
+    }
+}
+
+macro_rules! impl_bit_proxy {
+    ($U:ty) => {
+        impl<'a, REG, FI, S, const OFFSET: usize> Mask<$U>
+            for WProxy<'a, $U, REG, bool, FI, S, 1, OFFSET>
+        where
+            REG: Writable + RegisterSpec<Ux = $U>,
+        {
+            const MASK: $U = 1;
+        }
+
+        impl<'a, REG, FI, S, const OFFSET: usize> WProxy<'a, $U, REG, bool, FI, S, 1, OFFSET>
+        where
+            REG: Writable + RegisterSpec<Ux = $U>,
+        {
+            /// Writes `value` to the field, regardless of the safety of the writer.
+            #[inline(always)]
+            pub(crate) fn write_raw(self, value: bool) -> &'a mut REG::Writer {
+                self.w.bits = (self.w.bits & !(1 << OFFSET)) | ((value as $U & 1) << OFFSET);
+                self.w
+            }
+        }
+
+        impl<'a, REG, FI, S, const OFFSET: usize> WProxy<'a, $U, REG, bool, FI, S, 1, OFFSET>
+        where
+            REG: Writable + RegisterSpec<Ux = $U>,
+            FI: Into<bool>,
+        {
+            /// Writes `variant` to the field
+            #[inline(always)]
+            pub fn variant(self, variant: FI) -> &'a mut REG::Writer {
+                self.write_raw(variant.into())
+            }
+        }
+
+        impl<'a, REG, FI, const OFFSET: usize> WProxy<'a, $U, REG, bool, FI, Safe, 1, OFFSET>
+        where
+            REG: Writable + RegisterSpec<Ux = $U>,
+        {
+            /// Writes raw bit to the field
+            #[inline(always)]
+            pub fn bit(self, value: bool) -> &'a mut REG::Writer {
+                self.write_raw(value)
+            }
+            /// Sets the field bit
+            #[inline(always)]
+            pub fn set_bit(self) -> &'a mut REG::Writer {
+                self.write_raw(true)
+            }
+            /// Clears the field bit
+            #[inline(always)]
+            pub fn clear_bit(self) -> &'a mut REG::Writer {
+                self.write_raw(false)
+            }
+            /// Inverts the field bit
+            ///
+            /// The writer of `modify` starts from the value read, so there this writes the inverse
+            /// of the current value of the field. In `write` it inverts the reset value.
+            #[inline(always)]
+            pub fn toggle_bit(self) -> &'a mut REG::Writer {
+                self.w.bits ^= 1 << OFFSET;
+                self.w
+            }
+        }
+
+        impl<'a, REG, FI, const OFFSET: usize> WProxy<'a, $U, REG, bool, FI, Unsafe, 1, OFFSET>
+        where
+            REG: Writable + RegisterSpec<Ux = $U>,
+        {
+            /// Writes raw bit to the field
+            #[inline(always)]
+            pub unsafe fn bit(self, value: bool) -> &'a mut REG::Writer {
+                self.write_raw(value)
+            }
+            /// Sets the field bit
+            #[inline(always)]
+            pub unsafe fn set_bit(self) -> &'a mut REG::Writer {
+                self.write_raw(true)
+            }
+            /// Clears the field bit
+            #[inline(always)]
+            pub unsafe fn clear_bit(self) -> &'a mut REG::Writer {
+                self.write_raw(false)
+            }
+            /// Inverts the field bit
+            ///
+            /// The writer of `modify` starts from the value read, so there this writes the inverse
+            /// of the current value of the field. In `write` it inverts the reset value.
+            #[inline(always)]
+            pub unsafe fn toggle_bit(self) -> &'a mut REG::Writer {
+                self.w.bits ^= 1 << OFFSET;
+                self.w
+            }
+        }
+    };
+}
+
+macro_rules! impl_proxy {
+    ($U:ty, $N:ty) => {
+        impl<'a, REG, FI, S, const WIDTH: u8, const OFFSET: usize> Mask<$U>
+            for WProxy<'a, $U, REG, $N, FI, S, WIDTH, OFFSET>
+        where
+            REG: Writable + RegisterSpec<Ux = $U>,
+        {
+            const MASK: $U = <$U>::MAX >> (core::mem::size_of::<$U>() * 8 - WIDTH as usize);
+        }
+
+        impl<'a, REG, FI, S, const WIDTH: u8, const OFFSET: usize>
+            WProxy<'a, $U, REG, $N, FI, S, WIDTH, OFFSET>
+        where
+            REG: Writable + RegisterSpec<Ux = $U>,
+        {
+            /// Writes `value` to the field, regardless of the safety of the writer.
+            #[inline(always)]
+            pub(crate) fn write_raw(self, value: $N) -> &'a mut REG::Writer {
+                let mask = <Self as Mask<$U>>::MASK;
+                self.w.bits = (self.w.bits & !(mask << OFFSET)) | ((value as $U & mask) << OFFSET);
+                self.w
+            }
+        }
+
+        impl<'a, REG, FI, S, const WIDTH: u8, const OFFSET: usize>
+            WProxy<'a, $U, REG, $N, FI, S, WIDTH, OFFSET>
+        where
+            REG: Writable + RegisterSpec<Ux = $U>,
+            FI: Into<$N>,
+        {
+            /// Writes `variant` to the field
+            #[inline(always)]
+            pub fn variant(self, variant: FI) -> &'a mut REG::Writer {
+                self.write_raw(variant.into())
+            }
+        }
+    };
+}
+
+macro_rules! impl_proxy_safe {
+    ($U:ty, $N:ty) => {
+        impl<'a, REG, FI, const WIDTH: u8, const OFFSET: usize>
+            WProxy<'a, $U, REG, $N, FI, Safe, WIDTH, OFFSET>
+        where
+            REG: Writable + RegisterSpec<Ux = $U>,
+        {
+            /// Writes raw bits to the field
+            #[inline(always)]
+            pub fn bits(self, value: $N) -> &'a mut REG::Writer {
+                self.write_raw(value)
+            }
+        }
+    };
+}
+
+macro_rules! impl_proxy_unsafe {
+    ($U:ty, $N:ty) => {
+        impl<'a, REG, FI, const WIDTH: u8, const OFFSET: usize>
+            WProxy<'a, $U, REG, $N, FI, Unsafe, WIDTH, OFFSET>
+        where
+            REG: Writable + RegisterSpec<Ux = $U>,
+        {
+            /// Writes raw bits to the field
+            #[inline(always)]
+            pub unsafe fn bits(self, value: $N) -> &'a mut REG::Writer {
+                self.write_raw(value)
+            }
+        }
+    };
+}
+
+macro_rules! impl_proxies {
+    ($U:ty: $($N:ty),+) => {
+        impl_bit_proxy!($U);
+        $(
+            impl_proxy!($U, $N);
+            impl_proxy_safe!($U, $N);
+            impl_proxy_unsafe!($U, $N);
+        )+
+    };
 }
+
+impl_proxies!(u8: u8);
+impl_proxies!(u16: u8, u16);
+impl_proxies!(u32: u8, u16, u32);
+impl_proxies!(u64: u8, u16, u32, u64);
+
+/// Writer of an element of a field array picked at run time.
+///
+/// Result of the indexed field methods of the register writer, e.g. `ch(n)`. It is a `WProxy`
+/// whose offset is only known at run time, so it has the same methods, and it doesn't check that
+/// the offset is inside the register.
+#[must_use = "the field is only written by calling a method of its writer, e.g. `bits` or `set_bit`"]
+pub struct DynWProxy<'a, U, REG, N, FI, S, const WIDTH: u8>
+where
+    REG: Writable + RegisterSpec<Ux = U>,
+{
+    pub(crate) w: &'a mut REG::Writer,
+    offset: usize,
+    _field: marker::PhantomData<(N, FI, S)>,
+}
+
+impl<'a, U, REG, N, FI, S, const WIDTH: u8> DynWProxy<'a, U, REG, N, FI, S, WIDTH>
+where
+    REG: Writable + RegisterSpec<Ux = U>,
+{
+    /// Field width.
+    pub const WIDTH: u8 = WIDTH;
+
+    /// Creates a new instance of the writer of the field at `offset`.
+    #[allow(unused)]
+    #[inline(always)]
+    pub(crate) fn new(w: &'a mut REG::Writer, offset: usize) -> Self {
+        Self {
+            w,
+            offset,
+            _field: marker::PhantomData,
+        }
+    }
+
+    /// Field offset.
+    #[inline(always)]
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+macro_rules! impl_dyn_bit_proxy {
+    ($U:ty) => {
+        impl<'a, REG, FI, S> DynWProxy<'a, $U, REG, bool, FI, S, 1>
+        where
+            REG: Writable + RegisterSpec<Ux = $U>,
+        {
+            /// Writes `value` to the field, regardless of the safety of the writer.
+            #[inline(always)]
+            pub(crate) fn write_raw(self, value: bool) -> &'a mut REG::Writer {
+                let offset = self.offset;
+                self.w.bits = (self.w.bits & !(1 << offset)) | ((value as $U & 1) << offset);
+                self.w
+            }
+        }
+
+        impl<'a, REG, FI, S> DynWProxy<'a, $U, REG, bool, FI, S, 1>
+        where
+            REG: Writable + RegisterSpec<Ux = $U>,
+            FI: Into<bool>,
+        {
+            /// Writes `variant` to the field
+            #[inline(always)]
+            pub fn variant(self, variant: FI) -> &'a mut REG::Writer {
+                self.write_raw(variant.into())
+            }
+        }
+
+        impl<'a, REG, FI> DynWProxy<'a, $U, REG, bool, FI, Safe, 1>
+        where
+            REG: Writable + RegisterSpec<Ux = $U>,
+        {
+            /// Writes raw bit to the field
+            #[inline(always)]
+            pub fn bit(self, value: bool) -> &'a mut REG::Writer {
+                self.write_raw(value)
+            }
+            /// Sets the field bit
+            #[inline(always)]
+            pub fn set_bit(self) -> &'a mut REG::Writer {
+                self.write_raw(true)
+            }
+            /// Clears the field bit
+            #[inline(always)]
+            pub fn clear_bit(self) -> &'a mut REG::Writer {
+                self.write_raw(false)
+            }
+            /// Inverts the field bit, like `WProxy::toggle_bit`
+            #[inline(always)]
+            pub fn toggle_bit(self) -> &'a mut REG::Writer {
+                self.w.bits ^= 1 << self.offset;
+                self.w
+            }
+        }
+
+        impl<'a, REG, FI> DynWProxy<'a, $U, REG, bool, FI, Unsafe, 1>
+        where
+            REG: Writable + RegisterSpec<Ux = $U>,
+        {
+            /// Writes raw bit to the field
+            #[inline(always)]
+            pub unsafe fn bit(self, value: bool) -> &'a mut REG::Writer {
+                self.write_raw(value)
+            }
+            /// Sets the field bit
+            #[inline(always)]
+            pub unsafe fn set_bit(self) -> &'a mut REG::Writer {
+                self.write_raw(true)
+            }
+            /// Clears the field bit
+            #[inline(always)]
+            pub unsafe fn clear_bit(self) -> &'a mut REG::Writer {
+                self.write_raw(false)
+            }
+            /// Inverts the field bit, like `WProxy::toggle_bit`
+            #[inline(always)]
+            pub unsafe fn toggle_bit(self) -> &'a mut REG::Writer {
+                self.w.bits ^= 1 << self.offset;
+                self.w
+            }
+        }
+    };
+}
+
+macro_rules! impl_dyn_proxy {
+    ($U:ty, $N:ty) => {
+        impl<'a, REG, FI, S, const WIDTH: u8> DynWProxy<'a, $U, REG, $N, FI, S, WIDTH>
+        where
+            REG: Writable + RegisterSpec<Ux = $U>,
+        {
+            /// Writes `value` to the field, regardless of the safety of the writer.
+            #[inline(always)]
+            pub(crate) fn write_raw(self, value: $N) -> &'a mut REG::Writer {
+                let mask = <$U>::MAX >> (core::mem::size_of::<$U>() * 8 - WIDTH as usize);
+                let offset = self.offset;
+                self.w.bits = (self.w.bits & !(mask << offset)) | ((value as $U & mask) << offset);
+                self.w
+            }
+        }
+
+        impl<'a, REG, FI, S, const WIDTH: u8> DynWProxy<'a, $U, REG, $N, FI, S, WIDTH>
+        where
+            REG: Writable + RegisterSpec<Ux = $U>,
+            FI: Into<$N>,
+        {
+            /// Writes `variant` to the field
+            #[inline(always)]
+            pub fn variant(self, variant: FI) -> &'a mut REG::Writer {
+                self.write_raw(variant.into())
+            }
+        }
+
+        impl<'a, REG, FI, const WIDTH: u8> DynWProxy<'a, $U, REG, $N, FI, Safe, WIDTH>
+        where
+            REG: Writable + RegisterSpec<Ux = $U>,
+        {
+            /// Writes raw bits to the field
+            #[inline(always)]
+            pub fn bits(self, value: $N) -> &'a mut REG::Writer {
+                self.write_raw(value)
+            }
+        }
+
+        impl<'a, REG, FI, const WIDTH: u8> DynWProxy<'a, $U, REG, $N, FI, Unsafe, WIDTH>
+        where
+            REG: Writable + RegisterSpec<Ux = $U>,
+        {
+            /// Writes raw bits to the field
+            #[inline(always)]
+            pub unsafe fn bits(self, value: $N) -> &'a mut REG::Writer {
+                self.write_raw(value)
+            }
+        }
+    };
+}
+
+macro_rules! impl_dyn_proxies {
+    ($U:ty: $($N:ty),+) => {
+        impl_dyn_bit_proxy!($U);
+        $(
+            impl_dyn_proxy!($U, $N);
+        )+
+    };
+}
+
+impl_dyn_proxies!(u8: u8);
+impl_dyn_proxies!(u16: u8, u16);
+impl_dyn_proxies!(u32: u8, u16, u32);
+impl_dyn_proxies!(u64: u8, u16, u32, u64);
@@ -0,0 +1,42 @@
+/// Usage of an address block of a peripheral
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AddressBlockUsage {
+    /// Registers
+    Registers,
+    /// Buffer or memory
+    Buffer,
+    /// Reserved
+    Reserved,
+}
+
+/// Permission needed to access an address block of a peripheral
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AddressBlockProtection {
+    /// Secure permission
+    Secure,
+    /// Secure or non-secure permission
+    NonSecure,
+    /// Privileged permission
+    Privileged,
+}
+
+/// Address block of a peripheral, as described by the SVD file
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AddressBlock {
+    /// Address of the start of the block
+    pub base: usize,
+    /// Size of the block in bytes
+    pub size: usize,
+    /// What the block is used for
+    pub usage: AddressBlockUsage,
+    /// Permission needed to access the block, if the SVD file gives it
+    pub protection: Option<AddressBlockProtection>,
+}
+
+impl AddressBlock {
+    /// Returns the `(base, size)` of the block, e.g. to configure a region of the MPU
+    #[inline(always)]
+    pub const fn region(&self) -> (usize, usize) {
+        (self.base, self.size)
+    }
+}
@@ -0,0 +1,64 @@
+/// Single-bit field of a register which can be changed atomically.
+///
+/// If the register lies in one of the Cortex-M bit-band regions the bit is changed through its
+/// alias word with a single store. Otherwise the register is modified inside a critical section.
+pub struct BitBand<'a, REG: RegisterSpec, const OFFSET: usize> {
+    reg: &'a Reg<REG>,
+}
+
+impl<'a, REG: RegisterSpec, const OFFSET: usize> BitBand<'a, REG, OFFSET> {
+    /// Creates a new instance of the field.
+    #[allow(unused)]
+    #[inline(always)]
+    pub(crate) fn new(reg: &'a Reg<REG>) -> Self {
+        Self { reg }
+    }
+
+    /// Returns the address of the bit-band alias word of the field, if there is one.
+    #[inline(always)]
+    pub fn alias(&self) -> Option<*mut u32> {
+        let byte = self.reg.as_ptr() as usize + OFFSET / 8;
+        let region = match byte {
+            0x2000_0000..=0x200F_FFFF => 0x2000_0000,
+            0x4000_0000..=0x400F_FFFF => 0x4000_0000,
+            _ => return None,
+        };
+        Some((region + 0x0200_0000 + (byte - region) * 32 + (OFFSET % 8) * 4) as *mut u32)
+    }
+}
+
+impl<'a, REG, const OFFSET: usize> BitBand<'a, REG, OFFSET>
+where
+    REG: Readable + Writable,
+    REG::Ux: From<bool>
+        + core::ops::Shl<usize, Output = REG::Ux>
+        + core::ops::BitOr<Output = REG::Ux>
+        + core::ops::BitAnd<Output = REG::Ux>
+        + core::ops::Not<Output = REG::Ux>,
+{
+    /// Sets the field bit without touching the other bits of the register.
+    #[inline(always)]
+    pub fn set_bit_atomic(&self) {
+        self.write_atomic(true)
+    }
+
+    /// Clears the field bit without touching the other bits of the register.
+    #[inline(always)]
+    pub fn clear_bit_atomic(&self) {
+        self.write_atomic(false)
+    }
+
+    #[inline(always)]
+    fn write_atomic(&self, value: bool) {
+        if let Some(alias) = self.alias() {
+            unsafe { core::ptr::write_volatile(alias, value as u32) }
+        } else {
+            let mask = REG::Ux::from(true) << OFFSET;
+            cortex_m::interrupt::free(|_| {
+                self.reg.modify(|r, w| unsafe {
+                    w.bits((r.bits() & !mask) | (REG::Ux::from(value) << OFFSET))
+                })
+            })
+        }
+    }
+}
@@ -0,0 +1,21 @@
+#[cfg(feature = "critical-section")]
+impl<REG: Readable + Writable> Reg<REG> {
+    /// Modifies the contents of the register like `modify`, inside the critical section `cs`.
+    ///
+    /// Borrowing the token of a critical section proves that no interrupt handler, or other core,
+    /// can change the register between the read and the write. The token is only that proof: it
+    /// isn't used at run time, and this doesn't enter a critical section of its own, so the code
+    /// is the one of `modify`:
+    /// ```ignore
+    /// critical_section::with(|cs| {
+    ///     periph.reg.modify_cs(&cs, |_, w| w.field1().set_bit());
+    /// });
+    /// ```
+    #[inline(always)]
+    pub fn modify_cs<F>(&self, _cs: &critical_section::CriticalSection, f: F)
+    where
+        for<'w> F: FnOnce(&REG::Reader, &'w mut REG::Writer) -> &'w mut W<REG>,
+    {
+        self.modify(f)
+    }
+}
@@ -0,0 +1,13 @@
+impl<REG: Readable + Writable> Reg<REG> {
+    /// Modifies the contents of the register like `modify`, with interrupts disabled.
+    ///
+    /// An interrupt handler changing the same register can't run between the read and the write,
+    /// so its changes aren't overwritten.
+    #[inline(always)]
+    pub fn modify_critical<F>(&self, f: F)
+    where
+        for<'w> F: FnOnce(&REG::Reader, &'w mut REG::Writer) -> &'w mut W<REG>,
+    {
+        msp430::interrupt::free(|_| self.modify(f))
+    }
+}
@@ -0,0 +1,16 @@
+#[cfg(feature = "ufmt")]
+impl<U, T> ufmt::uDebug for Variant<U, T>
+where
+    U: ufmt::uDebug,
+    T: ufmt::uDebug,
+{
+    fn fmt<W>(&self, f: &mut ufmt::Formatter<'_, W>) -> core::result::Result<(), W::Error>
+    where
+        W: ufmt::uWrite + ?Sized,
+    {
+        match self {
+            Variant::Val(variant) => f.debug_tuple("Val")?.field(variant)?.finish(),
+            Variant::Res(bits) => f.debug_tuple("Res")?.field(bits)?.finish(),
+        }
+    }
+}
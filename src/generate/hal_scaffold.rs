@@ -0,0 +1,182 @@
+//! Empty drivers for the peripherals of the `--hal_scaffold` flag, as a starting point for a HAL
+
+use crate::svd::Device;
+use crate::util::{Config, ToSanitizedPascalCase, ToSanitizedUpperCase};
+use proc_macro2::{Ident, Span, TokenStream};
+use quote::quote;
+
+/// Renders a driver for each peripheral whose kind is recognized by its group or name
+pub fn render(d: &Device, config: &Config) -> TokenStream {
+    let mut out = TokenStream::new();
+    let doc = format!(
+        "Driver scaffolding for the {} peripherals, generated by `svd2rust --hal_scaffold`\n\n\
+         The kinds of the peripherals are guessed from their names, and the methods only \
+         outline a driver: all of them are `todo!()`. The device crate is expected to be \
+         available as `crate::pac`.",
+        d.name
+    );
+    out.extend(quote! {
+        #![doc = #doc]
+        #![allow(unused_variables)]
+    });
+
+    for p in &d.peripherals {
+        // peripherals without a register block are left out of the device crate too
+        let has_registers = p.registers.as_ref().map_or(false, |r| !r.is_empty());
+        if !has_registers && p.derived_from.is_none() {
+            continue;
+        }
+        let kind = p.group_name.as_deref().unwrap_or(&p.name).to_uppercase();
+        let (traits, methods) = match class(&kind).or_else(|| class(&p.name.to_uppercase())) {
+            Some(class) => class,
+            None => continue,
+        };
+
+        // the peripheral only exists with its feature
+        let cfg = if config.feature_peripheral {
+            let feature = p.name.to_lowercase();
+            quote! { #[cfg(feature = #feature)] }
+        } else {
+            quote! {}
+        };
+        let periph = Ident::new(&p.name.to_sanitized_upper_case(), Span::call_site());
+        let name = Ident::new(&p.name.to_sanitized_pascal_case(), Span::call_site());
+        let doc = format!(
+            "Driver of `{}` (scaffolding)\n\nTODO: implement {}",
+            p.name, traits
+        );
+        out.extend(quote! {
+            #[doc = #doc]
+            #cfg
+            pub struct #name {
+                regs: crate::pac::#periph,
+            }
+
+            #cfg
+            impl #name {
+                /// Takes ownership of the peripheral
+                pub fn new(regs: crate::pac::#periph) -> Self {
+                    Self { regs }
+                }
+
+                /// Releases the peripheral
+                pub fn free(self) -> crate::pac::#periph {
+                    self.regs
+                }
+
+                #methods
+            }
+        });
+    }
+    out
+}
+
+/// Returns the `embedded-hal` traits usually implemented by drivers of the peripheral `name`
+/// and stubs of their methods, if its kind is recognized
+fn class(name: &str) -> Option<(&'static str, TokenStream)> {
+    let starts = |prefixes: &[&str]| prefixes.iter().any(|p| name.starts_with(p));
+    let class = if starts(&["GPIO", "PIO"]) {
+        (
+            "`embedded_hal::digital::v2::OutputPin` and `InputPin` for its pins",
+            quote! {
+                /// Drives `pin` high
+                pub fn set_high(&mut self, pin: u8) {
+                    todo!()
+                }
+
+                /// Drives `pin` low
+                pub fn set_low(&mut self, pin: u8) {
+                    todo!()
+                }
+
+                /// Returns `true` if `pin` is high
+                pub fn is_high(&self, pin: u8) -> bool {
+                    todo!()
+                }
+            },
+        )
+    } else if starts(&["USART", "UART", "LPUART", "SERCOM"]) {
+        (
+            "`embedded_hal::serial::Read<u8>` and `Write<u8>`",
+            quote! {
+                /// Sends `word`, blocking until it is accepted
+                pub fn write(&mut self, word: u8) {
+                    todo!()
+                }
+
+                /// Returns the received word, if there is one
+                pub fn read(&mut self) -> Option<u8> {
+                    todo!()
+                }
+            },
+        )
+    } else if starts(&["SPI", "QSPI"]) {
+        (
+            "`embedded_hal::blocking::spi::Transfer<u8>` and `Write<u8>`",
+            quote! {
+                /// Sends `word` and returns the word received at the same time
+                pub fn transfer(&mut self, word: u8) -> u8 {
+                    todo!()
+                }
+            },
+        )
+    } else if starts(&["I2C", "TWI"]) {
+        (
+            "`embedded_hal::blocking::i2c::Read` and `Write`",
+            quote! {
+                /// Writes `bytes` to the device at `address`
+                pub fn write(&mut self, address: u8, bytes: &[u8]) {
+                    todo!()
+                }
+
+                /// Reads `buffer.len()` bytes from the device at `address`
+                pub fn read(&mut self, address: u8, buffer: &mut [u8]) {
+                    todo!()
+                }
+            },
+        )
+    } else if starts(&["IWDG", "WWDG", "WDT", "WDOG"]) {
+        (
+            "`embedded_hal::watchdog::WatchdogEnable` and `Watchdog`",
+            quote! {
+                /// Starts the watchdog with a timeout of `ticks`
+                pub fn start(&mut self, ticks: u32) {
+                    todo!()
+                }
+
+                /// Restarts the timeout
+                pub fn feed(&mut self) {
+                    todo!()
+                }
+            },
+        )
+    } else if starts(&["TIM", "LPTIM", "TC"]) {
+        (
+            "`embedded_hal::timer::CountDown`",
+            quote! {
+                /// Starts counting down from `ticks`
+                pub fn start(&mut self, ticks: u32) {
+                    todo!()
+                }
+
+                /// Returns `true` once the count down has expired
+                pub fn wait(&mut self) -> bool {
+                    todo!()
+                }
+            },
+        )
+    } else if starts(&["ADC"]) {
+        (
+            "`embedded_hal::adc::OneShot`",
+            quote! {
+                /// Converts the input `channel` once
+                pub fn read(&mut self, channel: u8) -> u16 {
+                    todo!()
+                }
+            },
+        )
+    } else {
+        return None;
+    };
+    Some(class)
+}
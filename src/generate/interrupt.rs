@@ -6,23 +6,44 @@ use cast::u64;
 use proc_macro2::{Ident, Span, TokenStream};
 use quote::quote;
 
-use crate::util::{self, ToSanitizedUpperCase};
+use crate::util::{self, Config, ToSanitizedUpperCase};
 use crate::Target;
-use anyhow::Result;
+use anyhow::{bail, Result};
 
 /// Generates code for `src/interrupt.rs`
 pub fn render(
-    target: Target,
+    config: &Config,
     peripherals: &[Peripheral],
     device_x: &mut String,
 ) -> Result<TokenStream> {
-    let interrupts = peripherals
-        .iter()
-        .flat_map(|p| p.interrupt.iter())
-        .map(|i| (i.value, i))
-        .collect::<HashMap<_, _>>();
+    let target = config.target;
+    // interrupts shared by several peripherals appear once per peripheral
+    let mut by_value = HashMap::new();
+    let mut by_name = HashMap::new();
+    for i in peripherals.iter().flat_map(|p| p.interrupt.iter()) {
+        if let Some(other) = by_value.insert(i.value, i) {
+            if other.name != i.name {
+                bail!(
+                    "Interrupts {} and {} have the same number {}",
+                    other.name,
+                    i.name,
+                    i.value
+                );
+            }
+        }
+        if let Some(other) = by_name.insert(&i.name, i) {
+            if other.value != i.value {
+                bail!(
+                    "Interrupt {} has different numbers {} and {}",
+                    i.name,
+                    other.value,
+                    i.value
+                );
+            }
+        }
+    }
 
-    let mut interrupts = interrupts.into_iter().map(|(_, v)| v).collect::<Vec<_>>();
+    let mut interrupts = by_value.into_iter().map(|(_, v)| v).collect::<Vec<_>>();
     interrupts.sort_by_key(|i| i.value);
 
     let mut root = TokenStream::new();
@@ -30,6 +51,8 @@ pub fn render(
     let mut elements = TokenStream::new();
     let mut names = vec![];
     let mut variants = TokenStream::new();
+    let mut name_arms = TokenStream::new();
+    let mut number_arms = TokenStream::new();
 
     // Current position in the vector table
     let mut pos = 0;
@@ -65,6 +88,14 @@ pub fn render(
             #value => Ok(Interrupt::#name_uc),
         });
 
+        let name = &interrupt.name;
+        name_arms.extend(quote! {
+            #value => Some(#name),
+        });
+        number_arms.extend(quote! {
+            #name => Some(#value),
+        });
+
         elements.extend(quote!(Vector { _handler: #name_uc },));
         names.push(name_uc);
     }
@@ -76,8 +107,15 @@ pub fn render(
                 writeln!(device_x, "PROVIDE({} = DefaultHandler);", name)?;
             }
 
+            // without "rt", the "vector-table" feature provides the table and `DefaultHandler`
+            // that `cortex-m-rt` would
+            let cfg = if config.vector_table {
+                quote!(#[cfg(any(feature = "rt", feature = "vector-table"))])
+            } else {
+                quote!(#[cfg(feature = "rt")])
+            };
             root.extend(quote! {
-                #[cfg(feature = "rt")]
+                #cfg
                 extern "C" {
                     #(fn #names();)*
                 }
@@ -88,7 +126,7 @@ pub fn render(
                     _reserved: u32,
                 }
 
-                #[cfg(feature = "rt")]
+                #cfg
                 #[doc(hidden)]
                 #[link_section = ".vector_table.interrupts"]
                 #[no_mangle]
@@ -96,6 +134,19 @@ pub fn render(
                     #elements
                 ];
             });
+            if config.vector_table {
+                root.extend(quote! {
+                    #[cfg(all(feature = "vector-table", not(feature = "rt")))]
+                    #[doc(hidden)]
+                    #[no_mangle]
+                    pub unsafe extern "C" fn DefaultHandler() {
+                        // the side effect keeps the loop from turning into a trap
+                        loop {
+                            core::sync::atomic::compiler_fence(core::sync::atomic::Ordering::SeqCst);
+                        }
+                    }
+                });
+            }
         }
         Target::Msp430 => {
             for name in &names {
@@ -185,6 +236,26 @@ pub fn render(
         (quote!(#[repr(u16)]), quote!(#self_token as u16))
     };
 
+    let lookup = quote! {
+        ///Returns the name in the SVD file of the interrupt `number`, or `None` if no interrupt has
+        ///this number
+        pub const fn interrupt_name(number: u16) -> Option<&'static str> {
+            match number {
+                #name_arms
+                _ => None,
+            }
+        }
+
+        ///Returns the number of the interrupt called `name` in the SVD file, or `None` if there is
+        ///no such interrupt
+        pub fn interrupt_number(name: &str) -> Option<u16> {
+            match name {
+                #number_arms
+                _ => None,
+            }
+        }
+    };
+
     if target == Target::Msp430 {
         let interrupt_enum = quote! {
             ///Enumeration of all the interrupts. This enum is seldom used in application or library crates. It is present primarily for documenting the device's implemented interrupts.
@@ -193,6 +264,8 @@ pub fn render(
             pub enum Interrupt {
                 #variants
             }
+
+            #lookup
         };
 
         root.extend(interrupt_enum);
@@ -204,6 +277,8 @@ pub fn render(
             pub enum Interrupt {
                 #variants
             }
+
+            #lookup
         };
 
         match target {
@@ -248,6 +323,22 @@ pub fn render(
                 });
             }
             _ => {
+                if target == Target::RISCV {
+                    let nr_expr = if variants.is_empty() {
+                        quote!(match *#self_token {})
+                    } else {
+                        quote!(*#self_token as u8)
+                    };
+                    mod_items.extend(quote! {
+                        unsafe impl bare_metal::Nr for Interrupt {
+                            #[inline(always)]
+                            fn nr(&#self_token) -> u8 {
+                                #nr_expr
+                            }
+                        }
+                    });
+                }
+
                 mod_items.extend(quote! {
                     #interrupt_enum
 
@@ -381,7 +472,7 @@ pub fn render(
         });
 
         root.extend(quote! {
-            pub use self::interrupt::Interrupt;
+            pub use self::interrupt::{interrupt_name, interrupt_number, Interrupt};
         });
     }
 
@@ -1,4 +1,30 @@
 pub mod device;
+pub mod hal_scaffold;
 pub mod interrupt;
 pub mod peripheral;
 pub mod register;
+
+/// An item of the SVD file which no code is generated for, and why
+#[derive(Clone, Debug, PartialEq)]
+pub struct Skip {
+    /// Path of the item, from its peripheral and through its clusters, register and field
+    pub item: String,
+    pub reason: String,
+}
+
+impl Skip {
+    pub(crate) fn new(item: &str, reason: &str) -> Self {
+        Skip {
+            item: item.into(),
+            reason: reason.into(),
+        }
+    }
+}
+
+/// Appends the `inner` skips of the item `parent` to `skipped`, with their paths from it
+pub(crate) fn skipped_in(skipped: &mut Vec<Skip>, parent: &str, inner: Vec<Skip>) {
+    skipped.extend(inner.into_iter().map(|s| Skip {
+        item: format!("{}.{}", parent, s.item),
+        ..s
+    }));
+}
@@ -1,10 +1,11 @@
 use std::borrow::Cow;
 use std::cmp::Ordering;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::fs;
 
 use crate::svd::{
-    Cluster, ClusterInfo, DeriveFrom, DimElement, Peripheral, Register, RegisterCluster,
-    RegisterProperties,
+    AddressBlock, AddressBlockUsage, Cluster, ClusterInfo, DeriveFrom, DimArrayIndex, DimElement,
+    Peripheral, Protection, Register, RegisterCluster, RegisterInfo, RegisterProperties,
 };
 use log::{debug, trace, warn};
 use proc_macro2::{Ident, Punct, Spacing, Span, TokenStream};
@@ -17,13 +18,14 @@ use crate::util::{
 };
 use anyhow::{anyhow, bail, Context, Result};
 
-use crate::generate::register;
+use crate::generate::{register, Skip};
 
 pub fn render(
     p_original: &Peripheral,
     all_peripherals: &[Peripheral],
     defaults: &RegisterProperties,
     config: &Config,
+    skipped: &mut Vec<Skip>,
 ) -> Result<TokenStream> {
     let mut out = TokenStream::new();
 
@@ -40,13 +42,15 @@ pub fn render(
             "Couldn't find derivedFrom original: {} for {}, skipping",
             df, p_original.name
         );
+        let reason = format!("derived from the missing peripheral {}", df);
+        skipped.push(Skip::new(&p_original.name, &reason));
         return Ok(out);
     }
 
     let span = Span::call_site();
     let name_str = p.name.to_sanitized_upper_case();
     let name_pc = Ident::new(&name_str, span);
-    let address = util::hex(p.base_address as u64);
+    let address = util::hex(p.base_address);
     let description = util::respace(p.description.as_ref().unwrap_or(&p.name));
 
     let name_sc = Ident::new(&p.name.to_sanitized_snake_case(), span);
@@ -56,24 +60,104 @@ pub fn render(
         (false, name_sc.clone())
     };
 
+    let feature_attribute = if config.feature_peripheral {
+        let feature = p.name.to_lowercase();
+        quote! { #[cfg(feature = #feature)] }
+    } else {
+        quote! {}
+    };
+
+    let split = if config.split_registers {
+        quote! {
+            ///Moves the registers out of the peripheral as owned tokens
+            #[inline(always)]
+            pub fn split(self) -> #base::Parts {
+                unsafe { (&*Self::PTR).parts() }
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    let alias = util::doc_alias(&p.name, &name_str);
+
     // Insert the peripheral structure
     out.extend(quote! {
         #[doc = #description]
+        #alias
+        #feature_attribute
         pub struct #name_pc { _marker: PhantomData<*const ()> }
 
+        #feature_attribute
         unsafe impl Send for #name_pc {}
 
+        #feature_attribute
         impl #name_pc {
             ///Pointer to the register block
             pub const PTR: *const #base::RegisterBlock = #address as *const _;
 
+            ///Identity of this peripheral
+            pub const ID: crate::PeripheralId = crate::PeripheralId::#name_pc;
+
             ///Return the pointer to the register block
             #[inline(always)]
             pub const fn ptr() -> *const #base::RegisterBlock {
                 Self::PTR
             }
+
+            ///Steal an instance of this peripheral
+            ///
+            ///# Safety
+            ///
+            ///Ensure that the new instance of the peripheral cannot be used in a way
+            ///that may race with any existing instances, for example by only
+            ///accessing read-only or write-only registers, or by consuming the
+            ///original peripheral and using critical sections to coordinate
+            ///access between multiple new instances.
+            ///
+            ///Additionally, other software such as HALs may rely on only one
+            ///peripheral instance existing to ensure memory safety; ensure
+            ///no stolen instances are passed to such software.
+            #[inline(always)]
+            pub unsafe fn steal() -> Self {
+                Self { _marker: PhantomData }
+            }
+
+            ///Read the 32-bit word at `offset` bytes from the start of the register block
+            ///
+            ///This bypasses the register API and is meant for bring-up and debugging only.
+            ///
+            ///# Safety
+            ///
+            ///`offset` must be a multiple of 4 within the address range of the peripheral,
+            ///and the register there must allow 32-bit reads without side effects the
+            ///code using the peripheral doesn't expect.
+            #[inline(always)]
+            pub unsafe fn peek(&self, offset: usize) -> u32 {
+                core::ptr::read_volatile((Self::PTR as *const u8).add(offset) as *const u32)
+            }
+
+            ///Write `value` to the 32-bit word at `offset` bytes from the start of the
+            ///register block
+            ///
+            ///This bypasses the register API and is meant for bring-up and debugging only.
+            ///
+            ///# Safety
+            ///
+            ///`offset` must be a multiple of 4 within the address range of the peripheral,
+            ///and the register there must allow 32-bit writes. The write may change the
+            ///state of the peripheral behind the back of the code using it.
+            #[inline(always)]
+            pub unsafe fn poke(&self, offset: usize, value: u32) {
+                core::ptr::write_volatile((Self::PTR as *mut u8).add(offset) as *mut u32, value)
+            }
+
+            #split
         }
 
+        // the proxy holds no data, so any instance of it can hand out a reference to the
+        // registers: all the accesses through it are volatile and go through `&self`
+        #feature_attribute
         impl Deref for #name_pc {
             type Target = #base::RegisterBlock;
 
@@ -83,6 +167,7 @@ pub fn render(
             }
         }
 
+        #feature_attribute
         impl core::fmt::Debug for #name_pc {
             fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
                 f.debug_struct(#name_str).finish()
@@ -90,12 +175,38 @@ pub fn render(
         }
     });
 
+    if config.ptr_consts {
+        let ptr_name = Ident::new(&format!("{}_PTR", name_str), span);
+        let doc = format!(
+            "Pointer to the register block of `{0}`, the same as `{0}::PTR`, for `const` and \
+             `static` initializers\n\nDereferencing it is `unsafe`, like [`{0}::steal`].",
+            name_str
+        );
+        out.extend(quote! {
+            #[doc = #doc]
+            #feature_attribute
+            pub const #ptr_name: *const #base::RegisterBlock = #name_pc::PTR;
+        });
+    }
+
+    if config.address_blocks {
+        // derived peripherals without address blocks of their own use the ones of the original
+        let blocks = p_original
+            .address_block
+            .as_ref()
+            .or_else(|| p_derivedfrom?.address_block.as_ref());
+        if let Some(blocks) = blocks.filter(|blocks| !blocks.is_empty()) {
+            out.extend(address_blocks(p, &name_str, blocks, &feature_attribute));
+        }
+    }
+
     // Derived peripherals may not require re-implementation, and will instead
     // use a single definition of the non-derived version.
     if derive_regs {
         // re-export the base module to allow deriveFrom this one
         out.extend(quote! {
             #[doc = #description]
+            #feature_attribute
             pub use #base as #name_sc;
         });
         return Ok(out);
@@ -116,6 +227,9 @@ pub fn render(
         erc_map.insert(util::erc_name(erc), erc.clone());
     }
 
+    // skips of the registers and clusters, with their paths from the peripheral
+    let mut inner = Vec::new();
+
     // Build up an alternate erc list by expanding any derived registers/clusters
     let ercs: Vec<RegisterCluster> = ercs
         .iter()
@@ -129,6 +243,9 @@ pub fn render(
                             util::erc_name(erc),
                             derived
                         );
+                        let reason =
+                            format!("derived from the missing register/cluster {}", derived);
+                        inner.push(Skip::new(util::erc_name(erc), &reason));
                         return None;
                     }
                 };
@@ -147,6 +264,8 @@ pub fn render(
                             util::erc_name(erc),
                             util::erc_name(ancestor)
                         );
+                        let reason = format!("can't derive from {}", util::erc_name(ancestor));
+                        inner.push(Skip::new(util::erc_name(erc), &reason));
                         None
                     }
                 }
@@ -162,10 +281,11 @@ pub fn render(
     // No `struct RegisterBlock` can be generated
     if registers.is_empty() && clusters.is_empty() {
         // Drop the definition of the peripheral
+        super::skipped_in(skipped, &p.name, inner);
         return Ok(TokenStream::new());
     }
 
-    let defaults = p.default_register_properties.derive_from(defaults);
+    let defaults = util::inherit(&p.default_register_properties, defaults);
 
     // Push any register or cluster blocks into the output
     debug!(
@@ -173,44 +293,210 @@ pub fn render(
         ercs.len()
     );
     let mut mod_items = TokenStream::new();
-    mod_items.extend(register_or_cluster_block(&ercs, &defaults, None, config)?);
+    mod_items.extend(register_or_cluster_block(
+        &ercs,
+        &defaults,
+        None,
+        "RegisterBlock",
+        None,
+        config,
+    )?);
 
     debug!("Pushing cluster information into output");
     // Push all cluster related information into the peripheral module
     for c in &clusters {
         trace!("Cluster: {}", c.name);
-        mod_items.extend(cluster_block(c, &defaults, p, all_peripherals, config)?);
+        mod_items.extend(cluster_block(
+            c,
+            &defaults,
+            p,
+            all_peripherals,
+            config,
+            &mut inner,
+        )?);
     }
 
     debug!("Pushing register information into output");
     // Push all register related information into the peripheral module
     for reg in registers {
         trace!("Register: {}", reg.name);
-        match register::render(reg, registers, p, all_peripherals, &defaults, config) {
+        match register::render(
+            reg,
+            registers,
+            p,
+            all_peripherals,
+            &defaults,
+            config,
+            &mut inner,
+        ) {
             Ok(rendered_reg) => mod_items.extend(rendered_reg),
             Err(e) => {
                 let res: Result<TokenStream> = Err(e);
-                return handle_reg_error("Error rendering register", *reg, res);
+                return handle_reg_error("Error rendering register", reg, res);
             }
         };
     }
+    super::skipped_in(skipped, &p.name, inner);
+
+    // reset values of the registers, for simulations and self-tests
+    let mut reset_state = Vec::new();
+    reset_values(&ercs, 0, &defaults, &mut reset_state);
+    reset_state.sort_by_key(|(offset, _, _)| *offset);
+    let value_ty = if reset_state.iter().any(|(_, _, size)| *size > 32) {
+        quote! { u64 }
+    } else {
+        quote! { u32 }
+    };
+    let len = util::unsuffixed(reset_state.len() as u64);
+    let entries = reset_state.iter().map(|(offset, value, _)| {
+        let offset = util::hex(*offset);
+        let value = util::hex(*value);
+        quote! { (#offset, #value) }
+    });
+    mod_items.extend(quote! {
+        ///Offsets from the start of the register block and reset values of the registers with
+        ///a reset value, in the order of their offsets
+        pub const RESET_STATE: [(usize, #value_ty); #len] = [#(#entries),*];
+    });
 
     let description =
         util::escape_brackets(util::respace(p.description.as_ref().unwrap_or(&p.name)).as_ref());
 
-    let open = Punct::new('{', Spacing::Alone);
-    let close = Punct::new('}', Spacing::Alone);
+    if config.module_per_peripheral {
+        let path = config.output_dir.join(format!("{}.rs", name_sc));
+        let data = mod_items.to_string().replace("] ", "]\n");
+        fs::write(&path, data).with_context(|| format!("Could not write {}", path.display()))?;
+        out.extend(quote! {
+            #[doc = #description]
+            #alias
+            #feature_attribute
+            pub mod #name_sc;
+        });
+    } else {
+        out.extend(quote! {
+            #[doc = #description]
+            #alias
+            #feature_attribute
+            pub mod #name_sc {
+                #mod_items
+            }
+        });
+    }
 
-    out.extend(quote! {
-        #[doc = #description]
-        pub mod #name_sc #open
-    });
+    Ok(out)
+}
+
+/// Adds the offset from the start of the register block, the reset value and the size of each
+/// register of `ercs`, or element of register arrays, which has a reset value to `out`
+fn reset_values(
+    ercs: &[RegisterCluster],
+    offset: u64,
+    defs: &RegisterProperties,
+    out: &mut Vec<(u64, u64, u32)>,
+) {
+    for erc in ercs {
+        match erc {
+            RegisterCluster::Register(r) => {
+                let properties = util::inherit(&r.properties, defs);
+                if let (Some(value), Some(size)) = (properties.reset_value, properties.size) {
+                    let base = offset + u64::from(r.address_offset);
+                    let (dim, increment) = match r {
+                        Register::Single(_) => (1, 0),
+                        Register::Array(_, dim) => (dim.dim, dim.dim_increment),
+                    };
+                    for i in 0..dim {
+                        out.push((base + u64::from(i * increment), value, size));
+                    }
+                }
+            }
+            RegisterCluster::Cluster(c) => {
+                let defs = util::inherit(&c.default_register_properties, defs);
+                let base = offset + u64::from(c.address_offset);
+                let (dim, increment) = match c {
+                    Cluster::Single(_) => (1, 0),
+                    Cluster::Array(_, dim) => (dim.dim, dim.dim_increment),
+                };
+                for i in 0..dim {
+                    reset_values(&c.children, base + u64::from(i * increment), &defs, out);
+                }
+            }
+        }
+    }
+}
 
-    out.extend(mod_items);
+/// Returns the `<PERIPHERAL>_SIZE` and `<PERIPHERAL>_ADDRESS_BLOCKS` consts of `--address_blocks`
+/// for the address blocks `blocks` of `p`
+fn address_blocks(
+    p: &Peripheral,
+    name_str: &str,
+    blocks: &[AddressBlock],
+    feature_attribute: &TokenStream,
+) -> TokenStream {
+    let span = Span::call_site();
+    // the address space of the peripheral runs from its base address to the end of its last block
+    let size = blocks
+        .iter()
+        .map(|b| u64::from(b.offset) + u64::from(b.size))
+        .max()
+        .unwrap_or(0);
+    let size_name = Ident::new(&format!("{}_SIZE", name_str), span);
+    let size_doc = format!(
+        "Size in bytes of the address space of `{}`, from its base address to the end of its \
+         last address block",
+        name_str
+    );
+    let size = util::hex(size);
+
+    let elements = blocks.iter().map(|b| {
+        let base = util::hex(p.base_address + u64::from(b.offset));
+        let size = util::hex(u64::from(b.size));
+        let usage = Ident::new(
+            match b.usage {
+                AddressBlockUsage::Registers => "Registers",
+                AddressBlockUsage::Buffer => "Buffer",
+                AddressBlockUsage::Reserved => "Reserved",
+            },
+            span,
+        );
+        let protection = match b.protection {
+            Some(protection) => {
+                let protection = Ident::new(
+                    match protection {
+                        Protection::Secure => "Secure",
+                        Protection::NonSecure => "NonSecure",
+                        Protection::Privileged => "Privileged",
+                    },
+                    span,
+                );
+                quote!(Some(crate::AddressBlockProtection::#protection))
+            }
+            None => quote!(None),
+        };
+        quote! {
+            crate::AddressBlock {
+                base: #base,
+                size: #size,
+                usage: crate::AddressBlockUsage::#usage,
+                protection: #protection,
+            }
+        }
+    });
+    let blocks_name = Ident::new(&format!("{}_ADDRESS_BLOCKS", name_str), span);
+    let blocks_doc = format!(
+        "Address blocks of `{}`, e.g. to configure the regions of the MPU",
+        name_str
+    );
+    let len = unsuffixed(blocks.len() as u64);
 
-    close.to_tokens(&mut out);
+    quote! {
+        #[doc = #size_doc]
+        #feature_attribute
+        pub const #size_name: usize = #size;
 
-    Ok(out)
+        #[doc = #blocks_doc]
+        #feature_attribute
+        pub const #blocks_name: [crate::AddressBlock; #len] = [#(#elements,)*];
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -220,6 +506,30 @@ struct RegisterBlockField {
     offset: u32,
     size: u32,
     accessors: Option<TokenStream>,
+    /// `true` for registers only accessible in secure mode with `--secure_feature`
+    secure: bool,
+    /// Names of the elements of arrays, for an index enum, and the length of the array
+    dim_array_index: Option<(DimArrayIndex, u32)>,
+}
+
+impl RegisterBlockField {
+    /// Attribute gating the items of this field behind the `secure` feature if needed
+    fn cfg(&self) -> TokenStream {
+        secure_cfg(self.secure)
+    }
+}
+
+fn dim_array_index(array_info: &DimElement) -> Option<(DimArrayIndex, u32)> {
+    let index = array_info.dim_array_index.clone()?;
+    Some((index, array_info.dim))
+}
+
+fn secure_cfg(secure: bool) -> TokenStream {
+    if secure {
+        quote! { #[cfg(feature = "secure")] }
+    } else {
+        quote! {}
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -457,10 +767,29 @@ fn register_or_cluster_block(
     ercs: &[RegisterCluster],
     defs: &RegisterProperties,
     name: Option<&str>,
+    ty_name: &str,
+    increment: Option<u32>,
     config: &Config,
 ) -> Result<TokenStream> {
     let mut rbfs = TokenStream::new();
     let mut accessors = TokenStream::new();
+    // index enums of arrays with a `dimArrayIndex`
+    let mut index_enums = TokenStream::new();
+    // fields and initializers of `Parts` with `--split_registers`
+    let mut parts_fields = TokenStream::new();
+    let mut parts_init = TokenStream::new();
+    // clusters have the `<BLOCK>Parts` of their registers next to their block
+    let split = config.split_registers;
+    let (parts_name, parts_doc) = if name.is_none() {
+        let doc = "Registers of the peripheral as owned tokens, returned by `split`";
+        (Ident::new("Parts", Span::call_site()), doc)
+    } else {
+        let doc = "Registers of the cluster as owned tokens, returned by `parts`";
+        (
+            Ident::new(&format!("{}Parts", ty_name), Span::call_site()),
+            doc,
+        )
+    };
 
     let ercs_expanded = expand(ercs, defs, name, config)
         .with_context(|| "Could not expand register or cluster block")?;
@@ -479,6 +808,12 @@ fn register_or_cluster_block(
     regions.resolve_idents()?;
     // The end of the region for which we previously emitted a rbf into `rbfs`
     let mut last_end = 0;
+    // with `--check_layout` the padding is hidden from the docs, including private items
+    let hidden = if config.check_layout {
+        quote! { #[doc(hidden)] }
+    } else {
+        quote! {}
+    };
 
     let span = Span::call_site();
     for (i, region) in regions.regions.iter().enumerate() {
@@ -488,6 +823,7 @@ fn register_or_cluster_block(
             let name = Ident::new(&format!("_reserved{}", i), span);
             let pad = util::hex(pad as u64);
             rbfs.extend(quote! {
+                #hidden
                 #name : [u8; #pad],
             });
         }
@@ -505,9 +841,13 @@ fn register_or_cluster_block(
             if is_region_a_union {
                 let name = &reg_block_field.field.ident;
                 let ty = &reg_block_field.field.ty;
+                let attrs = &reg_block_field.field.attrs;
                 let offset = reg_block_field.offset as usize;
+                let cfg = reg_block_field.cfg();
                 accessors.extend(quote! {
                     #[doc = #comment]
+                    #(#attrs)*
+                    #cfg
                     #[inline(always)]
                     pub fn #name(&self) -> &#ty {
                         unsafe {
@@ -519,9 +859,74 @@ fn register_or_cluster_block(
                 region_rbfs.extend(quote! {
                     #[doc = #comment]
                 });
+                region_rbfs.extend(reg_block_field.cfg());
 
                 reg_block_field.field.to_tokens(&mut region_rbfs);
                 Punct::new(',', Spacing::Alone).to_tokens(&mut region_rbfs);
+
+                // `[T; N]` arrays also get an accessor with the index checked at build time
+                if let syn::Type::Array(array) = &reg_block_field.field.ty {
+                    let name = &reg_block_field.field.ident;
+                    let ty = &array.elem;
+                    let len = &array.len;
+                    let cfg = reg_block_field.cfg();
+                    let doc = format!(
+                        "Element `I` of `{}`, with `I` checked at build time",
+                        name.as_ref().unwrap()
+                    );
+                    accessors.extend(quote! {
+                        #[doc = #doc]
+                        #cfg
+                        #[inline(always)]
+                        pub fn #name<const I: usize>(&self) -> &#ty {
+                            let _ = crate::ConstIndex::<I, #len>::CHECK;
+                            &self.#name[I]
+                        }
+                    });
+                }
+
+                if let Some((index, dim)) = &reg_block_field.dim_array_index {
+                    if let Some((items, accessor)) =
+                        index_enum(reg_block_field, index, *dim, config)?
+                    {
+                        index_enums.extend(items);
+                        accessors.extend(accessor);
+                    }
+                }
+
+                // registers in unions are left out of `Parts`, as they alias each other
+                if split {
+                    let name = reg_block_field.field.ident.as_ref().unwrap();
+                    if let Some((ty, init)) = owned_field(name, &reg_block_field.field.ty) {
+                        let cfg = reg_block_field.cfg();
+                        parts_fields.extend(quote! {
+                            #[doc = #comment]
+                            #cfg
+                            pub #name: #ty,
+                        });
+                        parts_init.extend(quote! {
+                            #cfg
+                            #name: #init,
+                        });
+                    }
+                }
+
+                // keep the layout without the `secure` feature
+                if reg_block_field.secure {
+                    let name = Ident::new(
+                        &format!(
+                            "_reserved_{}",
+                            reg_block_field.field.ident.as_ref().unwrap()
+                        ),
+                        span,
+                    );
+                    let pad = util::hex((region.end - region.offset) as u64);
+                    region_rbfs.extend(quote! {
+                        #[cfg(not(feature = "secure"))]
+                        #hidden
+                        #name: [u8; #pad],
+                    });
+                }
             }
         }
 
@@ -552,19 +957,41 @@ fn register_or_cluster_block(
             );
             let pad = util::hex((region.end - region.offset) as u64);
             rbfs.extend(quote! {
+                #hidden
                 #name: [u8; #pad],
             })
         }
         last_end = region.end;
     }
 
-    let name = Ident::new(
-        &match name {
-            Some(name) => name.to_sanitized_upper_case(),
-            None => "RegisterBlock".into(),
-        },
-        span,
-    );
+    let name = Ident::new(ty_name, span);
+
+    let parts = if split {
+        quote! {
+            #[doc = #parts_doc]
+            pub struct #parts_name {
+                #parts_fields
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    if split {
+        accessors.extend(quote! {
+            ///Takes ownership of the registers
+            ///
+            ///# Safety
+            ///
+            ///This register block must not be accessed otherwise while the parts exist
+            #[inline(always)]
+            pub unsafe fn parts(&'static self) -> #parts_name {
+                #parts_name {
+                    #parts_init
+                }
+            }
+        });
+    }
 
     let accessors = if !accessors.is_empty() {
         quote! {
@@ -576,6 +1003,31 @@ fn register_or_cluster_block(
         quote! {}
     };
 
+    // the size of the block is rounded up to the alignment of its largest register, the padding
+    // at its end is explicit too with `--check_layout`
+    let align = block_align_in_bits(ercs, defs) / BITS_PER_BYTE;
+    let size = (last_end + align - 1) / align * align;
+    if config.check_layout && size != last_end {
+        let name = Ident::new(&format!("_reserved{}", regions.regions.len()), span);
+        let pad = util::hex((size - last_end) as u64);
+        rbfs.extend(quote! {
+            #hidden
+            #name : [u8; #pad],
+        });
+    }
+
+    // fails to compile with both sizes if the alignment of a register moved the ones after it,
+    // or if the elements of a cluster array are larger than their `dimIncrement`
+    let size_check = if config.check_layout {
+        let size = util::hex(increment.map_or(size, |increment| size.min(increment)) as u64);
+        quote! {
+            const _: crate::BlockSize<#name, #size> =
+                crate::BlockSize::<#name, { core::mem::size_of::<#name>() }>::SIZE;
+        }
+    } else {
+        quote! {}
+    };
+
     Ok(quote! {
         ///Register block
         #[repr(C)]
@@ -584,9 +1036,174 @@ fn register_or_cluster_block(
         }
 
         #accessors
+
+        #index_enums
+
+        #parts
+
+        #size_check
     })
 }
 
+/// Enum of the elements named by the `dimArrayIndex` of an array, and a `<array>_get`
+/// accessor of the block taking it. Both `[T; N]` and `ArrayProxy` fields are indexed by `usize`
+fn index_enum(
+    rbf: &RegisterBlockField,
+    index: &DimArrayIndex,
+    dim: u32,
+    config: &Config,
+) -> Result<Option<(TokenStream, TokenStream)>> {
+    let span = Span::call_site();
+    let name = rbf.field.ident.as_ref().unwrap();
+    let name_str = name.to_string();
+    let name_str = name_str.trim_start_matches("r#");
+    let elem = match &rbf.field.ty {
+        syn::Type::Array(array) => array.elem.to_token_stream(),
+        syn::Type::Path(path) => match &path.path.segments.last().unwrap().arguments {
+            syn::PathArguments::AngleBracketed(args) => args.args[0].to_token_stream(),
+            _ => bail!("Array `{}` has no element type", name),
+        },
+        _ => bail!("Array `{}` has no element type", name),
+    };
+    let header = index.header_enum_name.as_ref().and_then(|header| {
+        let name = util::to_type_name(header);
+        if name.is_none() {
+            warn!(
+                "Ignoring headerEnumName `{}` of `{}`, as it isn't a valid Rust identifier",
+                header, name_str
+            );
+        }
+        name
+    });
+    let enum_name = header.unwrap_or_else(|| {
+        format!("{}_INDEX", name_str)
+            .to_sanitized_upper_case()
+            .into_owned()
+    });
+    let enum_name = Ident::new(&enum_name, span);
+
+    let mut variants = TokenStream::new();
+    let mut names = HashSet::new();
+    let mut values = HashSet::new();
+    for ev in &index.values {
+        match ev.value {
+            Some(value) if value < u64::from(dim) => {
+                let pc = ev.name.to_sanitized_upper_case().into_owned();
+                if parse_str::<Ident>(&pc).is_err() {
+                    bail!(
+                        "`dimArrayIndex` value `{}` of `{}` isn't a valid Rust identifier",
+                        ev.name,
+                        name_str
+                    );
+                }
+                if !names.insert(pc.clone()) {
+                    bail!(
+                        "`dimArrayIndex` of `{}` has several values named `{}`",
+                        name_str,
+                        pc
+                    );
+                }
+                if !values.insert(value) {
+                    bail!(
+                        "`dimArrayIndex` of `{}` names the index {} several times",
+                        name_str,
+                        value
+                    );
+                }
+                let pc = Ident::new(&pc, span);
+                let doc = ev.description.clone().unwrap_or_else(|| ev.name.clone());
+                let doc = format!("{}: {}", value, util::respace(&doc));
+                let value = unsuffixed(value);
+                variants.extend(quote! {
+                    #[doc = #doc]
+                    #pc = #value,
+                });
+            }
+            _ => warn!(
+                "Ignoring `dimArrayIndex` value `{}` of `{}`, which is not an index of the array",
+                ev.name, name_str
+            ),
+        }
+    }
+
+    if variants.is_empty() {
+        return Ok(None);
+    }
+
+    let doc = format!("Elements of `{}`", name_str);
+    let items = quote! {
+        #[doc = #doc]
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        #[repr(usize)]
+        pub enum #enum_name {
+            #variants
+        }
+    };
+    let fn_name = Ident::new(&format!("{}_get", name_str), span);
+    let doc = format!("Element `index` of `{}`", name_str);
+    let cfg = rbf.cfg();
+    let inline = config.inline.attr();
+    let accessor = quote! {
+        #[doc = #doc]
+        #cfg
+        #inline
+        pub fn #fn_name(&self, index: #enum_name) -> &#elem {
+            &self.#name[index as usize]
+        }
+    };
+    Ok(Some((items, accessor)))
+}
+
+/// `crate::Owned` type and initializer of `Parts` for a `crate::Reg` or `[crate::Reg; N]` field,
+/// or the `<BLOCK>Parts` of a cluster or array of clusters. `ArrayProxy`s are left out
+fn owned_field(name: &Ident, ty: &syn::Type) -> Option<(TokenStream, TokenStream)> {
+    // the type of the part, and whether it is a register rather than a cluster
+    fn owned_ty(ty: &syn::Type) -> Option<(syn::TypePath, bool)> {
+        let mut path = match ty {
+            syn::Type::Path(path) => path.clone(),
+            _ => return None,
+        };
+        let last = path.path.segments.last_mut()?;
+        let span = Span::call_site();
+        if last.ident == "Reg" {
+            last.ident = Ident::new("Owned", span);
+            Some((path, true))
+        } else if last.ident == "ArrayProxy" {
+            None
+        } else {
+            last.ident = Ident::new(&format!("{}Parts", last.ident), span);
+            Some((path, false))
+        }
+    }
+    fn init(field: TokenStream, register: bool) -> TokenStream {
+        if register {
+            quote! { crate::Owned::new(&#field) }
+        } else {
+            quote! { #field.parts() }
+        }
+    }
+
+    match ty {
+        syn::Type::Array(array) => {
+            let (ty, register) = owned_ty(&array.elem)?;
+            let len = match &array.len {
+                syn::Expr::Lit(syn::ExprLit {
+                    lit: syn::Lit::Int(len),
+                    ..
+                }) => len.base10_parse::<usize>().ok()?,
+                _ => return None,
+            };
+            let elems = (0..len).map(|i| init(quote! { self.#name[#i] }, register));
+            let len = &array.len;
+            Some((quote! { [#ty; #len] }, quote! { [#(#elems),*] }))
+        }
+        ty => {
+            let (ty, register) = owned_ty(ty)?;
+            Some((quote! { #ty }, init(quote! { self.#name }, register)))
+        }
+    }
+}
+
 /// Expand a list of parsed `Register`s or `Cluster`s, and render them to
 /// `RegisterBlockField`s containing `Field`s.
 fn expand(
@@ -687,7 +1304,24 @@ fn cluster_info_size_in_bits(
 
         size = size.max(end);
     }
-    Ok(size)
+    // like the `#[repr(C)]` block of the cluster, which is padded to its alignment
+    let align = block_align_in_bits(&info.children, defs);
+    Ok((size + align - 1) / align * align)
+}
+
+/// Alignment in bits of a register block, the size of its largest register
+fn block_align_in_bits(ercs: &[RegisterCluster], defs: &RegisterProperties) -> u32 {
+    ercs.iter()
+        .map(|erc| match erc {
+            RegisterCluster::Register(reg) => reg.properties.size.or(defs.size).unwrap_or(0),
+            RegisterCluster::Cluster(c) => block_align_in_bits(
+                &c.children,
+                &util::inherit(&c.default_register_properties, defs),
+            ),
+        })
+        .max()
+        .unwrap_or(0)
+        .max(BITS_PER_BYTE)
 }
 
 /// Render a given cluster (and any children) into `RegisterBlockField`s
@@ -699,7 +1333,7 @@ fn expand_cluster(
 ) -> Result<Vec<RegisterBlockField>> {
     let mut cluster_expanded = vec![];
 
-    let defs = cluster.default_register_properties.derive_from(defs);
+    let defs = util::inherit(&cluster.default_register_properties, defs);
 
     let cluster_size = cluster_info_size_in_bits(cluster, &defs, config)
         .with_context(|| format!("Cluster {} has no determinable `size` field", cluster.name))?;
@@ -711,6 +1345,8 @@ fn expand_cluster(
             offset: info.address_offset,
             size: cluster_size,
             accessors: None,
+            secure: false,
+            dim_array_index: None,
         }),
         Cluster::Array(info, array_info) => {
             let sequential_addresses = cluster_size == array_info.dim_increment * BITS_PER_BYTE;
@@ -741,6 +1377,8 @@ fn expand_cluster(
                         offset: info.address_offset,
                         size: cluster_size * array_info.dim,
                         accessors: None,
+                        secure: false,
+                        dim_array_index: dim_array_index(array_info),
                     });
                 } else {
                     let mut accessors = TokenStream::new();
@@ -774,6 +1412,8 @@ fn expand_cluster(
                         offset: info.address_offset,
                         size: cluster_size * array_info.dim,
                         accessors: Some(accessors),
+                        secure: false,
+                        dim_array_index: dim_array_index(array_info),
                     });
                 }
             } else if sequential_indexes && config.const_generic {
@@ -788,6 +1428,8 @@ fn expand_cluster(
                         offset: info.address_offset + field_num as u32 * array_info.dim_increment,
                         size: cluster_size,
                         accessors: None,
+                        secure: false,
+                        dim_array_index: None,
                     });
                 }
             }
@@ -812,15 +1454,19 @@ fn expand_register(
         .size
         .or(defs.size)
         .ok_or_else(|| anyhow!("Register {} has no `size` field", register.name))?;
+    let secure = config.secure_feature
+        && register.properties.protection.or(defs.protection) == Some(Protection::Secure);
 
     match register {
         Register::Single(info) => register_expanded.push(RegisterBlockField {
             field: convert_svd_register(register, name, config.ignore_groups)
                 .with_context(|| "syn error occured")?,
-            description: info.description.clone().unwrap_or_default(),
+            description: register_description(info, config.ignore_groups),
             offset: info.address_offset,
             size: register_size,
             accessors: None,
+            secure,
+            dim_array_index: None,
         }),
         Register::Array(info, array_info) => {
             let sequential_addresses = register_size == array_info.dim_increment * BITS_PER_BYTE;
@@ -847,10 +1493,12 @@ fn expand_register(
                 if sequential_indexes {
                     register_expanded.push(RegisterBlockField {
                         field: convert_svd_register(register, name, config.ignore_groups)?,
-                        description: info.description.clone().unwrap_or_default(),
+                        description: register_description(info, config.ignore_groups),
                         offset: info.address_offset,
                         size: register_size * array_info.dim,
                         accessors: None,
+                        secure,
+                        dim_array_index: dim_array_index(array_info),
                     });
                 } else {
                     let mut accessors = TokenStream::new();
@@ -858,7 +1506,7 @@ fn expand_register(
                     let ty = name_to_wrapped_ty(&nb_name, name)?;
                     let nb_name_cs =
                         Ident::new(&nb_name.to_sanitized_snake_case(), Span::call_site());
-                    let description = info.description.clone().unwrap_or_default();
+                    let description = register_description(info, config.ignore_groups);
                     for (i, idx) in array_info.indexes().enumerate() {
                         let idx_name = Ident::new(
                             &util::replace_suffix(&info.fullname(config.ignore_groups), &idx)
@@ -871,8 +1519,10 @@ fn expand_register(
                             &description,
                         );
                         let i = unsuffixed(i as _);
+                        let cfg = secure_cfg(secure);
                         accessors.extend(quote! {
                             #[doc = #comment]
+                            #cfg
                             #[inline(always)]
                             pub fn #idx_name(&self) -> &#ty {
                                 &self.#nb_name_cs[#i]
@@ -885,6 +1535,8 @@ fn expand_register(
                         offset: info.address_offset,
                         size: register_size * array_info.dim,
                         accessors: Some(accessors),
+                        secure,
+                        dim_array_index: dim_array_index(array_info),
                     });
                 }
             } else {
@@ -894,10 +1546,12 @@ fn expand_register(
                 {
                     register_expanded.push(RegisterBlockField {
                         field: field.clone(),
-                        description: info.description.clone().unwrap_or_default(),
+                        description: register_description(info, config.ignore_groups),
                         offset: info.address_offset + field_num as u32 * array_info.dim_increment,
                         size: register_size,
                         accessors: None,
+                        secure,
+                        dim_array_index: None,
                     });
                 }
             }
@@ -907,6 +1561,25 @@ fn expand_register(
     Ok(register_expanded)
 }
 
+/// Description of a register, naming the register it aliases if it is an alternate one
+fn register_description(info: &RegisterInfo, ignore_group: bool) -> String {
+    let description = info.description.clone().unwrap_or_default();
+    match (&info.alternate_register, &info.alternate_group) {
+        (Some(register), _) => format!(
+            "{} (alternate of `{}`)",
+            description,
+            register.to_sanitized_snake_case()
+        ),
+        (None, Some(group)) if !ignore_group => format!(
+            "{} (`{}` group alternate of `{}`)",
+            description,
+            group,
+            info.name.to_sanitized_snake_case()
+        ),
+        _ => description,
+    }
+}
+
 /// Render a Cluster Block into `TokenStream`
 fn cluster_block(
     c: &Cluster,
@@ -914,8 +1587,10 @@ fn cluster_block(
     p: &Peripheral,
     all_peripherals: &[Peripheral],
     config: &Config,
+    skipped: &mut Vec<Skip>,
 ) -> Result<TokenStream> {
     let mut mod_items = TokenStream::new();
+    let mut inner = Vec::new();
 
     // name_sc needs to take into account array type.
     let description =
@@ -931,20 +1606,42 @@ fn cluster_block(
     );
     let name_sc = Ident::new(&mod_name.to_sanitized_snake_case(), Span::call_site());
 
-    let defaults = c.default_register_properties.derive_from(defaults);
+    let defaults = util::inherit(&c.default_register_properties, defaults);
 
-    let reg_block = register_or_cluster_block(&c.children, &defaults, Some(&mod_name), config)?;
+    let ty_name = match c {
+        Cluster::Single(info) | Cluster::Array(info, _) => cluster_ty_name(info),
+    };
+    let increment = match c {
+        Cluster::Single(_) => None,
+        Cluster::Array(_, dim) => Some(dim.dim_increment),
+    };
+    let reg_block = register_or_cluster_block(
+        &c.children,
+        &defaults,
+        Some(&mod_name),
+        &ty_name,
+        increment,
+        config,
+    )?;
 
     // Generate definition for each of the registers.
     let registers = util::only_registers(&c.children);
     for reg in &registers {
-        match register::render(reg, &registers, p, all_peripherals, &defaults, config) {
+        match register::render(
+            reg,
+            &registers,
+            p,
+            all_peripherals,
+            &defaults,
+            config,
+            &mut inner,
+        ) {
             Ok(rendered_reg) => mod_items.extend(rendered_reg),
             Err(e) => {
                 let res: Result<TokenStream> = Err(e);
                 return handle_reg_error(
                     "Error generating register definition for a register cluster",
-                    *reg,
+                    reg,
                     res,
                 );
             }
@@ -954,8 +1651,16 @@ fn cluster_block(
     // Generate the sub-cluster blocks.
     let clusters = util::only_clusters(&c.children);
     for c in &clusters {
-        mod_items.extend(cluster_block(c, &defaults, p, all_peripherals, config)?);
+        mod_items.extend(cluster_block(
+            c,
+            &defaults,
+            p,
+            all_peripherals,
+            config,
+            &mut inner,
+        )?);
     }
+    super::skipped_in(skipped, &c.name, inner);
 
     Ok(quote! {
         #reg_block
@@ -987,7 +1692,7 @@ fn expand_svd_register(
 
                 let ty = name_to_wrapped_ty(&ty_name, name)?;
 
-                out.push(new_syn_field(&nb_name.to_sanitized_snake_case(), ty));
+                out.push(new_syn_field(&nb_name, ty));
             }
         }
     }
@@ -1004,7 +1709,7 @@ fn convert_svd_register(
         Register::Single(info) => {
             let info_name = info.fullname(ignore_group);
             new_syn_field(
-                &info_name.to_sanitized_snake_case(),
+                &info_name,
                 name_to_wrapped_ty(&info_name, name)
                     .with_context(|| format!("Error converting info name {}", info_name))?,
             )
@@ -1017,7 +1722,7 @@ fn convert_svd_register(
                 u64::from(array_info.dim)
             ))?);
 
-            new_syn_field(&nb_name.to_sanitized_snake_case(), ty)
+            new_syn_field(&nb_name, ty)
         }
     })
 }
@@ -1029,7 +1734,8 @@ fn array_proxy(
     name: Option<&str>,
 ) -> Result<RegisterBlockField, syn::Error> {
     let ty_name = util::replace_suffix(&info.name, "");
-    let tys = name_to_ty_str(&ty_name, name);
+    let struct_name = cluster_ty_name(info);
+    let tys = name_to_ty_str(&struct_name, name);
 
     let ap_path = parse_str::<syn::TypePath>(&format!(
         "crate::ArrayProxy<{}, {}, {}>",
@@ -1039,11 +1745,13 @@ fn array_proxy(
     ))?;
 
     Ok(RegisterBlockField {
-        field: new_syn_field(&ty_name.to_sanitized_snake_case(), ap_path.into()),
+        field: new_syn_field(&ty_name, ap_path.into()),
         description: info.description.as_ref().unwrap_or(&info.name).into(),
         offset: info.address_offset,
         size: 0,
         accessors: None,
+        secure: false,
+        dim_array_index: dim_array_index(array_info),
     })
 }
 
@@ -1058,14 +1766,14 @@ fn expand_svd_cluster(
     match &cluster {
         Cluster::Single(_info) => out.push(convert_svd_cluster(cluster, name)?),
         Cluster::Array(info, array_info) => {
-            let ty_name = util::replace_suffix(&info.name, "");
+            let ty_name = cluster_ty_name(info);
 
             for idx in array_info.indexes() {
                 let nb_name = util::replace_suffix(&info.name, &idx);
 
                 let ty = name_to_ty(&ty_name, name)?;
 
-                out.push(new_syn_field(&nb_name.to_sanitized_snake_case(), ty));
+                out.push(new_syn_field(&nb_name, ty));
             }
         }
     }
@@ -1076,52 +1784,61 @@ fn expand_svd_cluster(
 fn convert_svd_cluster(cluster: &Cluster, name: Option<&str>) -> Result<syn::Field, syn::Error> {
     Ok(match cluster {
         Cluster::Single(info) => {
-            let ty_name = util::replace_suffix(&info.name, "");
-            let ty = name_to_ty(&ty_name, name)?;
-            new_syn_field(&info.name.to_sanitized_snake_case(), ty)
+            let ty = name_to_ty(&cluster_ty_name(info), name)?;
+            new_syn_field(&info.name, ty)
         }
         Cluster::Array(info, array_info) => {
             let ty_name = util::replace_suffix(&info.name, "");
 
             let ty = syn::Type::Array(parse_str::<syn::TypeArray>(&format!(
                 "[{};{}]",
-                name_to_ty_str(&ty_name, name),
+                name_to_ty_str(&cluster_ty_name(info), name),
                 u64::from(array_info.dim)
             ))?);
 
-            new_syn_field(&ty_name.to_sanitized_snake_case(), ty)
+            new_syn_field(&ty_name, ty)
         }
     })
 }
 
-fn new_syn_field(ident: &str, ty: syn::Type) -> syn::Field {
+/// Field of a register block named after the register or cluster `name` of the SVD
+fn new_syn_field(name: &str, ty: syn::Type) -> syn::Field {
     let span = Span::call_site();
+    let ident = name.to_sanitized_snake_case();
+    let alias = util::doc_alias(name, &ident);
     syn::Field {
-        ident: Some(Ident::new(ident, span)),
+        ident: Some(Ident::new(&ident, span)),
         vis: syn::Visibility::Public(syn::VisPublic {
             pub_token: Token![pub](span),
         }),
-        attrs: vec![],
+        attrs: syn::parse::Parser::parse2(syn::Attribute::parse_outer, alias)
+            .expect("invalid doc alias"),
         colon_token: Some(Token![:](span)),
         ty,
     }
 }
 
-fn name_to_ty_str<'a, 'b>(name: &'a str, ns: Option<&'b str>) -> Cow<'a, str> {
+/// Name of the struct of a cluster: its `headerStructName` if it has one, the sanitized name
+/// otherwise
+fn cluster_ty_name(info: &ClusterInfo) -> String {
+    match &info.header_struct_name {
+        Some(header) => header.clone(),
+        None => util::replace_suffix(&info.name, "")
+            .to_sanitized_upper_case()
+            .into_owned(),
+    }
+}
+
+fn name_to_ty_str<'a>(ty_name: &'a str, ns: Option<&str>) -> Cow<'a, str> {
     if let Some(ns) = ns {
-        Cow::Owned(
-            String::from("self::")
-                + &ns.to_sanitized_snake_case()
-                + "::"
-                + &name.to_sanitized_upper_case(),
-        )
+        Cow::Owned(String::from("self::") + &ns.to_sanitized_snake_case() + "::" + ty_name)
     } else {
-        name.to_sanitized_upper_case()
+        Cow::Borrowed(ty_name)
     }
 }
 
-fn name_to_ty(name: &str, ns: Option<&str>) -> Result<syn::Type, syn::Error> {
-    let ident = name_to_ty_str(name, ns);
+fn name_to_ty(ty_name: &str, ns: Option<&str>) -> Result<syn::Type, syn::Error> {
+    let ident = name_to_ty_str(ty_name, ns);
     Ok(syn::Type::Path(parse_str::<syn::TypePath>(&ident)?))
 }
 
@@ -1,14 +1,16 @@
+use std::collections::{HashMap, HashSet};
+
 use crate::svd::{
-    Access, BitRange, DeriveFrom, EnumeratedValues, Field, Peripheral, Register,
-    RegisterProperties, Usage, WriteConstraint,
+    Access, BitRange, Cluster, EnumeratedValues, Field, ModifiedWriteValues, Peripheral,
+    Protection, ReadAction, Register, RegisterCluster, RegisterProperties, Usage, WriteConstraint,
 };
 use cast::u64;
-use core::u64;
 use log::warn;
-use proc_macro2::{Ident, Punct, Spacing, Span, TokenStream};
-use quote::{quote, ToTokens};
+use proc_macro2::{Ident, Span, TokenStream};
+use quote::quote;
 
-use crate::util::{self, Config, ToSanitizedSnakeCase, ToSanitizedUpperCase, U32Ext};
+use super::Skip;
+use crate::util::{self, Config, Target, ToSanitizedSnakeCase, ToSanitizedUpperCase, U32Ext};
 use anyhow::{anyhow, Result};
 
 pub fn render(
@@ -18,11 +20,17 @@ pub fn render(
     all_peripherals: &[Peripheral],
     defs: &RegisterProperties,
     config: &Config,
+    skipped: &mut Vec<Skip>,
 ) -> Result<TokenStream> {
-    let properties = register.properties.derive_from(defs);
-    let access = util::access_of(&properties, register.fields.as_deref());
+    let properties = util::inherit(&register.properties, defs);
+    let access = util::access_of(
+        register.properties.access,
+        register.fields.as_deref(),
+        defs.access,
+    );
     let name = util::name_of(register, config.ignore_groups);
     let span = Span::call_site();
+    let inline = config.inline.attr();
     let name_pc = Ident::new(&name.to_sanitized_upper_case(), span);
     let name_uc_spec = Ident::new(&format!("{}_SPEC", &name.to_sanitized_upper_case()), span);
     let name_sc = Ident::new(&name.to_sanitized_snake_case(), span);
@@ -47,34 +55,65 @@ pub fn render(
 
     let mut mod_items = TokenStream::new();
     let mut r_impl_items = TokenStream::new();
+    let mut debug_fields = vec![];
     let mut w_impl_items = TokenStream::new();
+    // methods of the register, like the bit-band and the spin helpers of fields
+    let mut reg_impl_items = TokenStream::new();
+    let mut config_fields = vec![];
+    let mut flags = vec![];
+    let mut field_infos = vec![];
     let mut methods = vec![];
 
     let can_read = access.can_read();
     let can_write = access.can_write();
     let can_reset = properties.reset_value.is_some();
+    let write_once = matches!(access, Access::WriteOnce | Access::ReadWriteOnce);
 
     if can_read {
-        let desc = format!("Register `{}` reader", register.name);
+        let mut desc = format!("Register `{}` reader", register.name);
+        if let Some(action) = register.read_action {
+            desc += &format!("\n\n{}", read_action_doc(action, "the register"));
+        }
+        // readers of registers with read side effects should not be thrown away
+        let side_effects = register.read_action.is_some()
+            || register
+                .fields
+                .iter()
+                .flatten()
+                .any(|f| f.read_action.is_some());
+        let must_use = if side_effects {
+            quote! { #[must_use = "reading this register has side effects, the value is lost if unused"] }
+        } else {
+            quote! {}
+        };
         mod_items.extend(quote! {
             #[doc = #desc]
+            #must_use
             pub struct R(crate::R<#name_uc_spec>);
 
             impl core::ops::Deref for R {
                 type Target = crate::R<#name_uc_spec>;
 
-                #[inline(always)]
+                #inline
                 fn deref(&self) -> &Self::Target {
                     &self.0
                 }
             }
 
             impl From<crate::R<#name_uc_spec>> for R {
-                #[inline(always)]
+                #inline
                 fn from(reader: crate::R<#name_uc_spec>) -> Self {
                     R(reader)
                 }
             }
+
+            impl R {
+                #[doc = "Consumes the reader, returning the raw bits read from the register"]
+                #inline
+                pub fn into_bits(self) -> #rty {
+                    self.0.into_bits()
+                }
+            }
         });
         methods.push("read");
     }
@@ -88,45 +127,76 @@ pub fn render(
             impl core::ops::Deref for W {
                 type Target = crate::W<#name_uc_spec>;
 
-                #[inline(always)]
+                #inline
                 fn deref(&self) -> &Self::Target {
                     &self.0
                 }
             }
 
             impl core::ops::DerefMut for W {
-                #[inline(always)]
+                #inline
                 fn deref_mut(&mut self) -> &mut Self::Target {
                     &mut self.0
                 }
             }
 
             impl From<crate::W<#name_uc_spec>> for W {
-                #[inline(always)]
+                #inline
                 fn from(writer: crate::W<#name_uc_spec>) -> Self {
                     W(writer)
                 }
             }
         });
         methods.push("write_with_zero");
+        methods.push("write_from");
         if can_reset {
             methods.push("reset");
             methods.push("write");
+            if write_once {
+                methods.push("write_once");
+            }
+        }
+        if config.impl_default && can_reset {
+            mod_items.extend(quote! {
+                impl Default for W {
+                    #inline
+                    fn default() -> Self {
+                        W(crate::W::reset())
+                    }
+                }
+            });
+            methods.push("write_value");
         }
     }
 
     if can_read && can_write {
         methods.push("modify");
+        methods.push("modify_ext");
+        methods.push("modify_if_changed");
+        methods.push("modify_bits");
+        methods.push("write_preserving");
+        if can_reset {
+            methods.push("reset_masked");
+        }
+        if config.target == Target::Msp430 {
+            methods.push("modify_critical");
+        }
+        if config.critical_section {
+            methods.push("modify_cs");
+        }
     }
 
     if let Some(cur_fields) = register.fields.as_ref() {
         // filter out all reserved fields, as we should not generate code for
         // them
-        let cur_fields: Vec<Field> = cur_fields
+        let (reserved, cur_fields): (Vec<Field>, Vec<Field>) = cur_fields
             .clone()
             .into_iter()
-            .filter(|field| field.name.to_lowercase() != "reserved")
-            .collect();
+            .partition(|field| field.name.to_lowercase() == "reserved");
+        for f in &reserved {
+            let item = format!("{}.{}", register.name, f.name);
+            skipped.push(Skip::new(&item, "reserved field"));
+        }
 
         if !cur_fields.is_empty() {
             fields(
@@ -140,28 +210,139 @@ pub fn render(
                 &properties,
                 &mut mod_items,
                 &mut r_impl_items,
+                &mut debug_fields,
                 &mut w_impl_items,
+                &mut reg_impl_items,
+                &mut config_fields,
+                &mut flags,
+                &mut field_infos,
                 config,
+                skipped,
             )?;
         }
     }
 
-    let open = Punct::new('{', Spacing::Alone);
-    let close = Punct::new('}', Spacing::Alone);
+    // `<enumeratedValues>` of the register itself get an enum of the values of the whole register
+    let register_enum = config
+        .register_enums
+        .iter()
+        .find(|(p, r, _)| *p == peripheral.name && *r == register.name);
+    if let Some((_, _, evs)) = register_enum {
+        let variants = Variant::from_enumerated_values(evs)?;
+        let name_pc_a = Ident::new(&format!("{}_A", name.to_sanitized_upper_case()), span);
+        let collides = register.fields().any(|f| {
+            f.name.to_sanitized_upper_case() == name.to_sanitized_upper_case()
+                && !f.enumerated_values.is_empty()
+        });
+        if collides {
+            return Err(anyhow!(
+                "Register {} and its field of the same name both have enumerated values",
+                register.name
+            ));
+        }
+        let desc = format!("Values of register `{}`", register.name);
+        add_from_variants(
+            &mut mod_items,
+            &variants,
+            &name_pc_a,
+            evs.name.as_deref(),
+            &rty,
+            &desc,
+            properties.reset_value,
+            config,
+        );
+        add_variant_conversions(&mut mod_items, &variants, &name_pc_a, &rty, rsize)?;
+
+        if can_read {
+            r_impl_items.extend(if covers_all(variants.len(), rsize) {
+                quote! {
+                    #[doc = "Get the enumerated value of the whole register"]
+                    #inline
+                    pub fn variant(&self) -> #name_pc_a {
+                        #name_pc_a::from(self.bits())
+                    }
+                }
+            } else {
+                quote! {
+                    #[doc = "Get the enumerated value of the whole register"]
+                    #inline
+                    pub fn variant(&self) -> crate::Variant<#rty, #name_pc_a> {
+                        use core::convert::TryFrom;
+                        match #name_pc_a::try_from(self.bits()) {
+                            Ok(variant) => crate::Variant::Val(variant),
+                            Err(bits) => crate::Variant::Res(bits),
+                        }
+                    }
+                }
+            });
+        }
+        if can_write {
+            w_impl_items.extend(quote! {
+                #[doc = "Writes an enumerated value to the whole register"]
+                #inline
+                pub fn write_variant(&mut self, variant: #name_pc_a) -> &mut Self {
+                    unsafe { self.0.bits(variant.into()) };
+                    self
+                }
+            });
+        }
+    }
 
     if can_read && !r_impl_items.is_empty() {
         mod_items.extend(quote! {
-            impl R #open #r_impl_items #close
+            impl R {
+                #r_impl_items
+            }
         });
     }
 
-    if can_write {
+    // `Debug` and `uDebug` print the same fields
+    let debug_name = util::replace_suffix(&register.name, "");
+    let (debug_names, debug_values): (Vec<_>, Vec<_>) = debug_fields.into_iter().unzip();
+
+    if can_read && config.impl_debug {
+        let debug_body = if debug_names.is_empty() {
+            quote! { core::fmt::Debug::fmt(&self.0, f) }
+        } else {
+            quote! {
+                f.debug_struct(#debug_name)
+                    #(.field(#debug_names, &#debug_values))*
+                    .finish()
+            }
+        };
         mod_items.extend(quote! {
-            impl W #open
+            impl core::fmt::Debug for R {
+                fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+                    #debug_body
+                }
+            }
         });
+    }
 
-        mod_items.extend(w_impl_items);
+    if can_read && config.ufmt {
+        let debug_body = if debug_names.is_empty() {
+            quote! { ufmt::uDebug::fmt(&self.bits(), f) }
+        } else {
+            quote! {
+                f.debug_struct(#debug_name)?
+                    #(.field(#debug_names, &#debug_values)?)*
+                    .finish()
+            }
+        };
+        mod_items.extend(quote! {
+            #[cfg(feature = "ufmt")]
+            impl ufmt::uDebug for R {
+                fn fmt<W>(&self, f: &mut ufmt::Formatter<'_, W>) -> core::result::Result<(), W::Error>
+                where
+                    W: ufmt::uWrite + ?Sized,
+                {
+                    #debug_body
+                }
+            }
+        });
+    }
 
+    if can_write {
         // the writer can be safe if:
         // * there is a single field that covers the entire register
         // * that field can represent all values
@@ -178,26 +359,118 @@ pub fn render(
         };
 
         if can_write_safe {
-            mod_items.extend(quote! {
+            w_impl_items.extend(quote! {
                 #[doc = "Writes raw bits to the register."]
-                #[inline(always)]
+                #inline
                 pub fn bits(&mut self, bits: #rty) -> &mut Self {
                     unsafe { self.0.bits(bits) };
                     self
                 }
+                #[doc = "Sets the bits of `mask`, leaving the other bits unchanged."]
+                #inline
+                pub fn set_bits(&mut self, mask: #rty) -> &mut Self {
+                    unsafe { self.0.set_bits(mask) };
+                    self
+                }
+                #[doc = "Clears the bits of `mask`, leaving the other bits unchanged."]
+                #inline
+                pub fn clear_bits(&mut self, mask: #rty) -> &mut Self {
+                    unsafe { self.0.clear_bits(mask) };
+                    self
+                }
             });
         } else {
-            mod_items.extend(quote! {
+            w_impl_items.extend(quote! {
                 #[doc = "Writes raw bits to the register."]
-                #[inline(always)]
+                #inline
                 pub unsafe fn bits(&mut self, bits: #rty) -> &mut Self {
                     self.0.bits(bits);
                     self
                 }
+                #[doc = "Sets the bits of `mask`, leaving the other bits unchanged."]
+                #inline
+                pub unsafe fn set_bits(&mut self, mask: #rty) -> &mut Self {
+                    self.0.set_bits(mask);
+                    self
+                }
+                #[doc = "Clears the bits of `mask`, leaving the other bits unchanged."]
+                #inline
+                pub unsafe fn clear_bits(&mut self, mask: #rty) -> &mut Self {
+                    self.0.clear_bits(mask);
+                    self
+                }
+            });
+        }
+
+        if !flags.is_empty() {
+            mod_items.extend(flags_struct(&register.name, &rty, &flags, &inline));
+            w_impl_items.extend(quote! {
+                #[doc = "Sets the single-bit fields in `flags`, leaving the other bits unchanged."]
+                #inline
+                pub fn set_flags(&mut self, flags: Flags) -> &mut Self {
+                    unsafe { self.0.set_bits(flags.0) };
+                    self
+                }
+            });
+        }
+
+        w_impl_items.extend(quote! {
+            #[doc = "Replaces the value being written with the bits read from another register."]
+            #[doc = ""]
+            #[doc = "# Safety"]
+            #[doc = ""]
+            #[doc = "The fields of `SRC` must match the ones of this register, see [`crate::W::copy_from`]."]
+            #inline
+            pub unsafe fn copy_from<SRC>(&mut self, from: &crate::R<SRC>) -> &mut Self
+            where
+                SRC: crate::RegisterSpec<Ux = #rty>,
+            {
+                self.0.copy_from(from);
+                self
+            }
+        });
+
+        if can_read {
+            w_impl_items.extend(quote! {
+                #[doc = "Replaces the value being written with a value read from the register."]
+                #inline
+                pub fn preserve(&mut self, from: &R) -> &mut Self {
+                    self.0.preserve(&from.0);
+                    self
+                }
             });
         }
 
-        close.to_tokens(&mut mod_items);
+        mod_items.extend(quote! {
+            impl W {
+                #w_impl_items
+            }
+        });
+    }
+
+    if !reg_impl_items.is_empty() {
+        mod_items.extend(quote! {
+            impl crate::Reg<#name_uc_spec> {
+                #reg_impl_items
+            }
+        });
+    }
+
+    if config.config_struct && can_write && can_reset && !config_fields.is_empty() {
+        mod_items.extend(config_struct(
+            &register.name,
+            &name_uc_spec,
+            &config_fields,
+            &inline,
+        ));
+    }
+
+    if config.reflect {
+        mod_items.extend(quote! {
+            #[doc = "Fields of the register, for runtime introspection"]
+            #[cfg(feature = "reflect")]
+            pub const FIELDS: &[crate::FieldInfo] = &[#(#field_infos),*];
+        });
     }
 
     let mut out = TokenStream::new();
@@ -208,6 +481,18 @@ pub fn render(
     let mut doc = format!("{}\n\nThis register you can {}. See [API](https://docs.rs/svd2rust/#read--modify--write-api).",
                         &description, methods.join(", "));
 
+    if let Some(action) = register.read_action.filter(|_| can_read) {
+        doc += &format!("\n\n{}", read_action_doc(action, "this register"));
+    }
+
+    if write_once {
+        doc += "\n\nThis register can only be written once, usually until the next reset.";
+    }
+
+    if let Some(protection) = properties.protection {
+        doc += &format!("\n\n{}", protection_doc(protection));
+    }
+
     if name_sc != "cfg" {
         doc += format!(
             "\n\nFor information about available fields see [{0}](index.html) module",
@@ -219,8 +504,17 @@ pub fn render(
         "{} register accessor: an alias for `Reg<{}>`",
         name, name_uc_spec,
     );
+    // with `--secure_feature` registers only accessible in secure mode need the `secure` feature
+    let secure = if config.secure_feature && properties.protection == Some(Protection::Secure) {
+        quote! { #[cfg(feature = "secure")] }
+    } else {
+        quote! {}
+    };
+    let alias = util::doc_alias(&name, &name_pc.to_string());
     out.extend(quote! {
         #[doc = #alias_doc]
+        #alias
+        #secure
         pub type #name_pc = crate::Reg<#name_sc::#name_uc_spec>;
     });
     mod_items.extend(quote! {
@@ -256,26 +550,53 @@ pub fn render(
             }
         });
     }
+    if write_once {
+        let doc = format!(
+            "`write_once(|w| ..)` method writes {} a single time",
+            register.name
+        );
+        mod_items.extend(quote! {
+            #[doc = #doc]
+            impl crate::WriteOnce for #name_uc_spec {}
+        });
+    }
     if let Some(rv) = properties.reset_value.map(util::hex) {
-        let doc = format!("`reset()` method sets {} to value {}", register.name, &rv);
+        // `reset` preserves the bits outside of the `resetMask` of readable registers
+        let all_ones = u64::MAX >> (64 - rsize);
+        let size_mask = properties
+            .size
+            .map_or(all_ones, |size| u64::MAX >> (64 - size));
+        let reset_mask = match properties.reset_mask {
+            Some(mask) if can_read && mask & size_mask != size_mask => mask & all_ones,
+            _ => all_ones,
+        };
+        let doc = if reset_mask == all_ones {
+            format!("`reset()` method sets {} to value {}", register.name, &rv)
+        } else {
+            format!(
+                "`reset()` method sets the bits {} of {} to value {}",
+                util::hex(reset_mask),
+                register.name,
+                &rv
+            )
+        };
+        let reset_mask = util::hex(reset_mask);
         mod_items.extend(quote! {
             #[doc = #doc]
             impl crate::Resettable for #name_uc_spec {
-                #[inline(always)]
-                fn reset_value() -> Self::Ux { #rv }
+                const RESET_VALUE: Self::Ux = #rv;
+                const RESET_MASK: Self::Ux = #reset_mask;
             }
         });
     }
 
     out.extend(quote! {
         #[doc = #description]
-        pub mod #name_sc #open
-    });
-
-    out.extend(mod_items);
-
-    out.extend(quote! {
-        #close
+        #alias
+        #secure
+        pub mod #name_sc {
+            #mod_items
+        }
     });
 
     Ok(out)
@@ -293,15 +614,54 @@ pub fn fields(
     properties: &RegisterProperties,
     mod_items: &mut TokenStream,
     r_impl_items: &mut TokenStream,
+    debug_fields: &mut Vec<(String, TokenStream)>,
     w_impl_items: &mut TokenStream,
+    reg_impl_items: &mut TokenStream,
+    config_fields: &mut Vec<ConfigField>,
+    flags: &mut Vec<(Ident, u64, String)>,
+    field_infos: &mut Vec<TokenStream>,
     config: &Config,
+    skipped: &mut Vec<Skip>,
 ) -> Result<()> {
     let span = Span::call_site();
     let can_read = access.can_read();
     let can_write = access.can_write();
+    // the fallback for registers outside of the bit-band regions needs `modify`
+    let bitband = config.bitband
+        && config.target == Target::CortexM
+        && can_read
+        && in_bitband_region(peripheral.base_address);
+    let name_uc_spec = Ident::new(
+        &format!(
+            "{}_SPEC",
+            util::name_of(parent, config.ignore_groups).to_sanitized_upper_case()
+        ),
+        span,
+    );
+
+    // enums of the fields of this register, to reuse them with `--shared_enums`
+    let mut shared_enums: HashMap<EnumKey, Ident> = HashMap::new();
+    let mut shared_readers: HashMap<EnumKey, (Ident, Ident)> = HashMap::new();
+
+    // `--field_setters` and `--option_setters` leave out the setters named like the method of
+    // another field
+    let field_names: HashSet<String> = fields
+        .iter()
+        .flat_map(|f| match f {
+            Field::Single(_) => vec![f.name.to_sanitized_snake_case().into_owned()],
+            Field::Array(_, de) => de
+                .indexes()
+                .map(|i| {
+                    util::replace_suffix(&f.name, &i)
+                        .to_sanitized_snake_case()
+                        .into_owned()
+                })
+                .collect(),
+        })
+        .collect();
 
     // TODO enumeratedValues
-    let inline = quote! { #[inline(always)] };
+    let inline = config.inline.attr();
     for f in fields.iter() {
         // TODO(AJM) - do we need to do anything with this range type?
         let BitRange { offset, width, .. } = f.bit_range;
@@ -330,14 +690,25 @@ pub fn fields(
             true
         };
 
-        let lookup_results = lookup(
-            evs,
-            fields,
-            parent,
-            all_registers,
-            peripheral,
-            all_peripherals,
-        )?;
+        // fields wider than `--max_field_width` only get the raw accessors
+        let lookup_results = if config.max_field_width.map_or(false, |max| width > max) {
+            if !evs.is_empty() {
+                let item = format!("{}.{}.enumeratedValues", parent.name, f.name);
+                let reason = "enumerated values of a field wider than `--max_field_width`";
+                skipped.push(Skip::new(&item, reason));
+            }
+            Vec::new()
+        } else {
+            lookup(
+                evs,
+                fields,
+                parent,
+                all_registers,
+                peripheral,
+                all_peripherals,
+                config,
+            )?
+        };
 
         // Reader and writer use one common `Enum_A` unless a fields have two `enumeratedValues`,
         // then we have one for read-only `Enum_A` and another for write-only `Enum_AW`
@@ -379,6 +750,70 @@ pub fn fields(
             }
         };
 
+        // the names and offsets of the elements of field arrays, or of the field
+        let elements = match &field_dim {
+            Some((_, _, increment, suffixes, _)) => suffixes
+                .iter()
+                .enumerate()
+                .map(|(i, suffix)| {
+                    (
+                        util::replace_suffix(&f.name, suffix),
+                        offset + (i as u64) * u64::from(*increment),
+                    )
+                })
+                .collect(),
+            None => vec![(f.name.clone(), offset)],
+        };
+
+        if config.field_masks {
+            for (name, offset) in &elements {
+                let name = name.to_sanitized_upper_case();
+                let mask_name = Ident::new(&format!("{}_MASK", name), span);
+                let clear_name = Ident::new(&format!("{}_CLEAR", name), span);
+                let mask_doc = format!(
+                    "Mask of the bits of the `{}` field, at its offset in the register",
+                    name
+                );
+                let clear_doc = format!(
+                    "Mask of the bits of the register outside of the `{}` field, to clear it with `&`",
+                    name
+                );
+                let shifted = util::hex(mask << offset);
+                mod_items.extend(quote! {
+                    #[doc = #mask_doc]
+                    pub const #mask_name: #rty = #shifted;
+                    #[doc = #clear_doc]
+                    pub const #clear_name: #rty = !#mask_name;
+                });
+            }
+        }
+
+        if config.reflect {
+            // the values of the reader, or of the writer for write-only fields
+            let usage = if can_read { Usage::Read } else { Usage::Write };
+            let values = lookup_filter(&lookup_results, usage)
+                .map(|(evs, _)| {
+                    evs.values
+                        .iter()
+                        .filter(|v| v.is_default.is_none())
+                        .map(|v| v.name.clone())
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default();
+            for (name, offset) in elements {
+                let offset = util::unsuffixed(offset);
+                let width = util::unsuffixed(u64::from(width));
+                field_infos.push(quote! {
+                    crate::FieldInfo {
+                        name: #name,
+                        offset: #offset,
+                        width: #width,
+                        values: &[#(#values),*],
+                    }
+                });
+            }
+        }
+
         if can_read {
             let readerdoc = if let Some((_, _, _, _, suffixes_str)) = &field_dim {
                 format!(
@@ -389,32 +824,85 @@ pub fn fields(
             } else {
                 format!("Field `{}` reader - {}", f.name, description)
             };
+            let readerdoc = if let Some(action) = f.read_action {
+                format!("{}\n\n{}", readerdoc, read_action_doc(action, "the field"))
+            } else {
+                readerdoc
+            };
 
             let name_pc_r = Ident::new(&(name_pc.clone() + "_R"), span);
+            // spinning on a field with read side effects would repeat them
+            let spin_helpers = config.spin_helpers
+                && width == 1
+                && f.read_action.is_none()
+                && parent.read_action.is_none();
+
+            let debug_value = match lookup_filter(&lookup_results, Usage::Read) {
+                Some((evs, _)) if !Variant::from_enumerated_values(evs)?.is_empty() => {
+                    quote! { variant }
+                }
+                _ => quote! { #bits },
+            };
+
+            // `--signed_field` readers sign-extend the raw bits of the field
+            let signed = config
+                .signed_fields
+                .iter()
+                .any(|(p, r, name)| *p == peripheral.name && *r == parent.name && *name == f.name);
+            if signed && width == 1 {
+                return Err(anyhow!("Single-bit field {} can't be signed", f.name));
+            }
+            if signed && lookup_filter(&lookup_results, Usage::Read).is_some() {
+                return Err(anyhow!(
+                    "Field {} with enumerated values can't be signed",
+                    f.name
+                ));
+            }
+            let rfty = if signed {
+                Ident::new(&format!("i{}", width.to_ty_width()?), span)
+            } else {
+                fty.clone()
+            };
+
+            let shift = width.to_ty_width()? - width;
+            let sign_extend = |value: TokenStream| {
+                if !signed {
+                    value
+                } else if shift == 0 {
+                    quote! { (#value) as #rfty }
+                } else {
+                    let shift = &util::unsuffixed(u64::from(shift));
+                    quote! { (((#value) as #rfty) << #shift) >> #shift }
+                }
+            };
 
             let cast = if width == 1 {
                 quote! { != 0 }
             } else {
                 quote! { as #fty }
             };
-            let value = if offset != 0 {
-                let offset = &util::unsuffixed(offset);
-                quote! {
-                    ((self.bits >> #offset) & #hexmask) #cast
-                }
-            } else if use_mask {
-                quote! {
-                    (self.bits & #hexmask) #cast
-                }
-            } else {
-                quote! {
-                    self.bits
-                }
+            // also used by the `_from_bits` functions, which must decode the same way
+            let extract = |bits: TokenStream, offset: u64| {
+                sign_extend(if offset != 0 {
+                    let offset = &util::unsuffixed(offset);
+                    quote! {
+                        ((#bits >> #offset) & #hexmask) #cast
+                    }
+                } else if use_mask {
+                    quote! {
+                        (#bits & #hexmask) #cast
+                    }
+                } else {
+                    quote! {
+                        #bits
+                    }
+                })
             };
+            let value = extract(quote! { self.bits }, offset);
 
             if let Some((first, dim, increment, suffixes, suffixes_str)) = &field_dim {
                 let offset_calc = calculate_offset(*first, *increment, offset, true);
-                let value = quote! { ((self.bits >> #offset_calc) & #hexmask) #cast };
+                let value = sign_extend(quote! { ((self.bits >> #offset_calc) & #hexmask) #cast });
                 let doc = &util::replace_suffix(&description, suffixes_str);
                 r_impl_items.extend(quote! {
                     #[doc = #doc]
@@ -425,45 +913,65 @@ pub fn fields(
                 });
                 for (i, suffix) in (0..*dim).zip(suffixes.iter()) {
                     let sub_offset = offset + (i as u64) * (*increment as u64);
-                    let value = if sub_offset != 0 {
-                        let sub_offset = &util::unsuffixed(sub_offset);
-                        quote! {
-                            ((self.bits >> #sub_offset) & #hexmask) #cast
-                        }
-                    } else if use_mask {
-                        quote! {
-                            (self.bits & #hexmask) #cast
-                        }
-                    } else {
-                        quote! {
-                            self.bits
-                        }
-                    };
-                    let name_sc_n = Ident::new(
-                        &util::replace_suffix(&f.name, suffix).to_sanitized_snake_case(),
-                        Span::call_site(),
+                    let value = extract(quote! { self.bits }, sub_offset);
+                    let name_n = util::replace_suffix(&f.name, suffix);
+                    let name_sc_n =
+                        Ident::new(&name_n.to_sanitized_snake_case(), Span::call_site());
+                    add_from_bits(
+                        mod_items,
+                        &name_n,
+                        rty,
+                        &rfty,
+                        extract(quote! { bits }, sub_offset),
+                        &inline,
                     );
                     let doc = util::replace_suffix(
                         &description_with_bits(description_raw, sub_offset, width),
                         suffix,
                     );
+                    let alias = util::doc_alias(&name_n, &name_sc_n.to_string());
                     r_impl_items.extend(quote! {
                         #[doc = #doc]
+                        #alias
                         #inline
                         pub fn #name_sc_n(&self) -> #name_pc_r {
                             #name_pc_r::new ( #value )
                         }
                     });
+                    debug_fields.push((
+                        name_sc_n.to_string(),
+                        quote! { self.#name_sc_n().#debug_value() },
+                    ));
+                    if spin_helpers {
+                        add_spin_helpers(reg_impl_items, &name_n, &name_sc_n, config);
+                    }
                 }
             } else {
                 let doc = description_with_bits(description_raw, offset, width);
+                let alias = util::doc_alias(&name, &name_sc.to_string());
                 r_impl_items.extend(quote! {
                     #[doc = #doc]
+                    #alias
                     #inline
                     pub fn #name_sc(&self) -> #name_pc_r {
                         #name_pc_r::new ( #value )
                     }
                 });
+                add_from_bits(
+                    mod_items,
+                    &name,
+                    rty,
+                    &rfty,
+                    extract(quote! { bits }, offset),
+                    &inline,
+                );
+                debug_fields.push((
+                    name_sc.to_string(),
+                    quote! { self.#name_sc().#debug_value() },
+                ));
+                if spin_helpers {
+                    add_spin_helpers(reg_impl_items, &name, &name_sc, config);
+                }
             }
 
             if let Some((evs, base)) = lookup_filter(&lookup_results, Usage::Read) {
@@ -479,59 +987,66 @@ pub fn fields(
                     let pc = pc_orig.to_sanitized_upper_case();
                     let base_pc_r = Ident::new(&(pc + "_R"), span);
                     derive_from_base(mod_items, &base, &name_pc_r, &base_pc_r, &readerdoc);
+                } else if let Some((shared_a, shared_r)) =
+                    shared_enum_key(&Variant::from_enumerated_values(evs)?, width, config)
+                        .and_then(|key| shared_readers.get(&key))
+                {
+                    let description = enum_doc(&description, rv);
+                    mod_items.extend(quote! {
+                        #[doc = #description]
+                        pub type #name_pc_a = #shared_a;
+                        #[doc = #readerdoc]
+                        pub type #name_pc_r = #shared_r;
+                    });
                 } else {
-                    let has_reserved_variant = evs.values.len() != (1 << width);
                     let variants = Variant::from_enumerated_values(evs)?;
+                    let has_reserved_variant = !covers_all(variants.len(), width);
+                    let key = shared_enum_key(&variants, width, config);
                     let mut enum_items = TokenStream::new();
 
                     if variants.is_empty() {
                         add_with_no_variants(mod_items, &name_pc_a, &fty, &description, rv);
                     } else {
-                        add_from_variants(mod_items, &variants, &name_pc_a, &fty, &description, rv);
-
-                        let mut arms = TokenStream::new();
-                        for v in variants.iter().map(|v| {
-                            let i = util::unsuffixed_or_bool(v.value, width);
-                            let pc = &v.pc;
-
-                            if has_reserved_variant {
-                                quote! { #i => Some(#name_pc_a::#pc), }
-                            } else {
-                                quote! { #i => #name_pc_a::#pc, }
-                            }
-                        }) {
-                            arms.extend(v);
-                        }
-
-                        if has_reserved_variant {
-                            arms.extend(quote! {
-                                _ => None,
-                            });
-                        } else if 1 << width.to_ty_width()? != variants.len() {
-                            arms.extend(quote! {
-                                _ => unreachable!(),
+                        if let Some(shared) = key.as_ref().and_then(|key| shared_enums.get(key)) {
+                            let description = enum_doc(&description, rv);
+                            mod_items.extend(quote! {
+                                #[doc = #description]
+                                pub type #name_pc_a = #shared;
                             });
+                        } else {
+                            add_from_variants(
+                                mod_items,
+                                &variants,
+                                &name_pc_a,
+                                evs.name.as_deref(),
+                                &fty,
+                                &description,
+                                rv,
+                                config,
+                            );
+                            add_variant_conversions(mod_items, &variants, &name_pc_a, &fty, width)?;
                         }
 
                         if has_reserved_variant {
                             enum_items.extend(quote! {
                                 ///Get enumerated values variant
                                 #inline
-                                pub fn variant(&self) -> Option<#name_pc_a> {
-                                    match self.bits {
-                                        #arms
+                                pub fn variant(&self) -> crate::Variant<#fty, #name_pc_a> {
+                                    use core::convert::TryFrom;
+                                    match #name_pc_a::try_from(self.bits) {
+                                        Ok(variant) => crate::Variant::Val(variant),
+                                        Err(bits) => crate::Variant::Res(bits),
                                     }
                                 }
                             });
                         } else {
                             enum_items.extend(quote! {
-                            ///Get enumerated values variant
-                            #inline
-                            pub fn variant(&self) -> #name_pc_a {
-                                match self.bits {
-                                    #arms
+                                ///Get enumerated values variant
+                                #inline
+                                pub fn variant(&self) -> #name_pc_a {
+                                    #name_pc_a::from(self.bits)
                                 }
-                            }});
+                            });
                         }
 
                         for v in &variants {
@@ -547,12 +1062,30 @@ pub fn fields(
                                 span,
                             );
 
+                            // single-bit readers already have `is_one` and `is_zero`
+                            if width == 1 {
+                                let generic_value = match is_variant.to_string().as_str() {
+                                    "is_one" => Some(1),
+                                    "is_zero" => Some(0),
+                                    _ => None,
+                                };
+                                if let Some(generic_value) = generic_value {
+                                    if generic_value != v.value {
+                                        warn!(
+                                            "`{}` of field {} checks for {} instead of variant {}",
+                                            is_variant, f.name, generic_value, pc
+                                        );
+                                    }
+                                    continue;
+                                }
+                            }
+
                             let doc = format!("Checks if the value of the field is `{}`", pc);
                             enum_items.extend(quote! {
                                 #[doc = #doc]
                                 #inline
                                 pub fn #is_variant(&self) -> bool {
-                                    **self == #name_pc_a::#pc
+                                    *self == #name_pc_a::#pc
                                 }
                             });
                         }
@@ -560,46 +1093,26 @@ pub fn fields(
 
                     mod_items.extend(quote! {
                         #[doc = #readerdoc]
-                        pub struct #name_pc_r(crate::FieldReader<#fty, #name_pc_a>);
-
-                        impl #name_pc_r {
-                            #[inline(always)]
-                            pub(crate) fn new(bits: #fty) -> Self {
-                                #name_pc_r(crate::FieldReader::new(bits))
-                            }
-                            #enum_items
-                        }
-
-                        impl core::ops::Deref for #name_pc_r {
-                            type Target = crate::FieldReader<#fty, #name_pc_a>;
-
-                            #[inline(always)]
-                            fn deref(&self) -> &Self::Target {
-                                &self.0
-                            }
-                        }
+                        pub type #name_pc_r = crate::FieldReader<#fty, #name_pc_a>;
                     });
+                    if !enum_items.is_empty() {
+                        mod_items.extend(quote! {
+                            impl #name_pc_r {
+                                #enum_items
+                            }
+                        });
+                    }
+                    if let Some(key) = key {
+                        shared_enums
+                            .entry(key.clone())
+                            .or_insert_with(|| name_pc_a.clone());
+                        shared_readers.insert(key, (name_pc_a.clone(), name_pc_r.clone()));
+                    }
                 }
             } else {
                 mod_items.extend(quote! {
                     #[doc = #readerdoc]
-                    pub struct #name_pc_r(crate::FieldReader<#fty, #fty>);
-
-                    impl #name_pc_r {
-                        #[inline(always)]
-                        pub(crate) fn new(bits: #fty) -> Self {
-                            #name_pc_r(crate::FieldReader::new(bits))
-                        }
-                    }
-
-                    impl core::ops::Deref for #name_pc_r {
-                        type Target = crate::FieldReader<#fty, #fty>;
-
-                        #[inline(always)]
-                        fn deref(&self) -> &Self::Target {
-                            &self.0
-                        }
-                    }
+                    pub type #name_pc_r = crate::FieldReader<#rfty, #rfty>;
                 })
             }
         }
@@ -607,15 +1120,18 @@ pub fn fields(
         if can_write {
             let new_pc_aw = Ident::new(&(name_pc.clone() + "_AW"), span);
             let name_pc_w = Ident::new(&(name_pc.clone() + "_W"), span);
-            let name_pc_cgw = Ident::new(&(name_pc.clone() + "_CGW"), span);
 
             let mut proxy_items = TokenStream::new();
             let mut unsafety = unsafety(f.write_constraint.as_ref(), width);
+            let mut field_enum = quote! { () };
+            let mut shortcuts = Vec::new();
+            // values of the enum for the `Config` struct
+            let mut config_variants = vec![];
 
             if let Some((evs, base)) = lookup_filter(&lookup_results, Usage::Write) {
                 let variants = Variant::from_enumerated_values(evs)?;
 
-                if variants.len() == 1 << width {
+                if covers_all(variants.len(), width) {
                     unsafety = None;
                 }
 
@@ -628,33 +1144,50 @@ pub fn fields(
                         derive_from_base(mod_items, &base, name_pc_aw, &base_pc_w, &description)
                     } else if variants.is_empty() {
                         add_with_no_variants(mod_items, name_pc_aw, &fty, &description, rv);
+                    } else if let Some(key) = shared_enum_key(&variants, width, config) {
+                        if let Some(shared) = shared_enums.get(&key) {
+                            let description = enum_doc(&description, rv);
+                            mod_items.extend(quote! {
+                                #[doc = #description]
+                                pub type #name_pc_aw = #shared;
+                            });
+                        } else {
+                            add_from_variants(
+                                mod_items,
+                                &variants,
+                                name_pc_aw,
+                                evs.name.as_deref(),
+                                &fty,
+                                &description,
+                                rv,
+                                config,
+                            );
+                            add_variant_conversions(mod_items, &variants, name_pc_aw, &fty, width)?;
+                            shared_enums.insert(key, name_pc_aw.clone());
+                        }
                     } else {
-                        add_from_variants(mod_items, &variants, name_pc_aw, &fty, &description, rv);
+                        add_from_variants(
+                            mod_items,
+                            &variants,
+                            name_pc_aw,
+                            evs.name.as_deref(),
+                            &fty,
+                            &description,
+                            rv,
+                            config,
+                        );
+                        add_variant_conversions(mod_items, &variants, name_pc_aw, &fty, width)?;
                     }
                 }
 
                 if !variants.is_empty() {
-                    if unsafety.is_some() {
-                        proxy_items.extend(quote! {
-                            ///Writes `variant` to the field
-                            #inline
-                            pub fn variant(self, variant: #name_pc_aw) -> &'a mut W {
-                                unsafe { self.#bits(variant.into()) }
-                            }
-                        });
-                    } else {
-                        proxy_items.extend(quote! {
-                            ///Writes `variant` to the field
-                            #inline
-                            pub fn variant(self, variant: #name_pc_aw) -> &'a mut W {
-                                self.#bits(variant.into())
-                            }
-                        });
-                    }
+                    field_enum = quote! { #name_pc_aw };
 
                     for v in &variants {
                         let pc = &v.pc;
                         let sc = &v.sc;
+                        shortcuts.push(sc.to_string());
+                        config_variants.push((v.value, quote! { #name_pc_aw::#pc }));
 
                         let doc = util::escape_brackets(util::respace(&v.doc).as_ref());
                         proxy_items.extend(quote! {
@@ -668,178 +1201,290 @@ pub fn fields(
                 }
             }
 
-            if width == 1 {
-                proxy_items.extend(quote! {
-                    ///Sets the field bit
-                    #inline
-                    pub #unsafety fn set_bit(self) -> &'a mut W {
-                        self.bit(true)
-                    }
-
-                    ///Clears the field bit
-                    #inline
-                    pub #unsafety fn clear_bit(self) -> &'a mut W {
-                        self.bit(false)
-                    }
-                });
+            let mwv = f
+                .modified_write_values
+                .or(parent.modified_write_values)
+                .unwrap_or_default();
+            if let Some((method, ones, doc)) = modified_write_method(mwv, width) {
+                // an enumerated value with the same name already does the job
+                if !shortcuts.iter().any(|sc| sc == method) {
+                    let method = Ident::new(method, span);
+                    let value = if width == 1 {
+                        quote! { #ones }
+                    } else if ones {
+                        quote! { #hexmask }
+                    } else {
+                        quote! { 0 }
+                    };
+                    proxy_items.extend(quote! {
+                        #[doc = #doc]
+                        #inline
+                        pub fn #method(self) -> &'a mut W {
+                            self.write_raw(#value)
+                        }
+                    });
+                }
             }
-
-            let mut proxy_items_fa = TokenStream::new();
-            let mut proxy_items_cg = TokenStream::new();
-            if field_dim.is_some() {
-                proxy_items_fa.extend(quote! {
-                    ///Writes raw bits to the field
-                    #inline
-                    pub #unsafety fn #bits(self, value: #fty) -> &'a mut W {
-                        self.w.bits = (self.w.bits & !(#hexmask << self.offset)) | ((value as #rty & #hexmask) << self.offset);
-                        self.w
-                    }
-                });
-                if config.const_generic {
-                    proxy_items_cg.extend(quote! {
-                        ///Writes raw bits to the field
+            // `bits` doesn't check the range of values the field can take
+            if let Some(WriteConstraint::Range(range)) = f.write_constraint {
+                let max = range.max.min(mask);
+                if width != 1 && (range.min != 0 || max != mask) && range.min <= max {
+                    let doc = format!(
+                        "Writes raw bits to the field if they are in the range {}..={}",
+                        range.min, max
+                    );
+                    let min = util::unsuffixed(range.min);
+                    let max = util::unsuffixed(max);
+                    proxy_items.extend(quote! {
+                        #[doc = #doc]
                         #inline
-                        pub #unsafety fn #bits(self, value: #fty) -> &'a mut W {
-                            self.w.bits = (self.w.bits & !(#hexmask << O)) | ((value as #rty & #hexmask) << O);
-                            self.w
+                        pub fn checked_bits(self, value: #fty) -> core::result::Result<&'a mut W, crate::OutOfRange> {
+                            if (#min..=#max).contains(&value) {
+                                Ok(self.write_raw(value))
+                            } else {
+                                Err(crate::OutOfRange)
+                            }
                         }
                     });
                 }
-            } else {
-                proxy_items.extend(if offset != 0 {
-                    let offset = &util::unsuffixed(offset);
+            }
+
+            let mut write_doc = modified_write_doc(mwv)
+                .map(|doc| format!("\n\n{}", doc))
+                .unwrap_or_default();
+            if matches!(
+                f.access.unwrap_or(access),
+                Access::WriteOnce | Access::ReadWriteOnce
+            ) {
+                write_doc +=
+                    "\n\nThis field can only be written once, usually until the next reset.";
+            }
+
+            // `Reg` has methods of its own, don't shadow them with field names
+            let bitband = bitband
+                && width == 1
+                && unsafety.is_none()
+                && !REG_METHODS.contains(&name_sc.to_string().as_str());
+            let safety = Ident::new(if unsafety.is_some() { "Unsafe" } else { "Safe" }, span);
+            let width_lit = util::unsuffixed(u64::from(width));
+            // `--flags` combines the single-bit fields which are safe to set
+            let is_flag = config.flags && width == 1 && unsafety.is_none();
+            // `<field>_set` writes raw bits to the field like `<field>().bits(value)`
+            let setter = |name: &Ident, proxy: TokenStream| {
+                let name = name.to_string();
+                let name = name.trim_start_matches("r#");
+                let fn_name = format!("{}_set", name);
+                if !config.field_setters || field_names.contains(&fn_name) {
+                    return quote! {};
+                }
+                let fn_name = Ident::new(&fn_name, span);
+                let doc = format!(
+                    "Writes raw bits to the field, like `{}().{}(value)`",
+                    name, bits
+                );
+                if unsafety.is_some() {
+                    let doc = format!(
+                        "{}\n\n# Safety\n\n`value` must be a valid value of the field",
+                        doc
+                    );
                     quote! {
-                        ///Writes raw bits to the field
+                        #[doc = #doc]
                         #inline
-                        pub #unsafety fn #bits(self, value: #fty) -> &'a mut W {
-                            self.w.bits = (self.w.bits & !(#hexmask << #offset)) | ((value as #rty & #hexmask) << #offset);
-                            self.w
+                        pub unsafe fn #fn_name(&mut self, value: #fty) -> &mut Self {
+                            #proxy::new(self).write_raw(value)
                         }
                     }
                 } else {
-                    let op = if use_mask {
-                        quote!{ self.w.bits = (self.w.bits & !#hexmask) | (value as #rty & #hexmask); }
-                    } else {
-                        quote!{ self.w.bits = value; }
-                    };
-
                     quote! {
-                        ///Writes raw bits to the field
+                        #[doc = #doc]
                         #inline
-                        pub #unsafety fn #bits(self, value: #fty) -> &'a mut W {
-                            #op
-                            self.w
+                        pub fn #fn_name(&mut self, value: #fty) -> &mut Self {
+                            #proxy::new(self).write_raw(value)
                         }
                     }
-                });
-            }
-
-            let mut cgdoc = String::new();
-            let doc = if let Some((_, _, _, _, suffixes_str)) = &field_dim {
-                if config.const_generic {
-                    cgdoc = format!(
-                        "Fields `{}` const generic writer - {}",
-                        util::replace_suffix(&f.name, suffixes_str),
-                        description
-                    );
                 }
-                format!(
-                    "Fields `{}` writer - {}",
-                    util::replace_suffix(&f.name, suffixes_str),
-                    description
-                )
-            } else {
-                format!("Field `{}` writer - {}", f.name, description)
             };
-
-            if field_dim.is_some() {
-                mod_items.extend(quote! {
+            // `<field>_opt` writes a variant to the field if there is one
+            let opt_setter = |name: &Ident, proxy: TokenStream| {
+                let name = name.to_string();
+                let name = name.trim_start_matches("r#");
+                let fn_name = format!("{}_opt", name);
+                // fields without variants have no enum to take
+                if !config.option_setters
+                    || config_variants.is_empty()
+                    || field_names.contains(&fn_name)
+                {
+                    return quote! {};
+                }
+                let fn_name = Ident::new(&fn_name, span);
+                let doc = format!(
+                    "Writes `value` to the field like `{}().variant(value)` if it is `Some`, \
+                     leaves the field as it is otherwise",
+                    name
+                );
+                quote! {
                     #[doc = #doc]
-                    pub struct #name_pc_w<'a> {
-                        w: &'a mut W,
-                        offset: usize,
+                    #inline
+                    pub fn #fn_name(&mut self, value: Option<#field_enum>) -> &mut Self {
+                        if let Some(value) = value {
+                            #proxy::new(self).variant(value);
+                        }
+                        self
                     }
+                }
+            };
+            let config_field = |name: &Ident, offset: u64, description: String| {
+                let rv = properties.reset_value.map(|rv| (rv >> offset) & mask)?;
+                let config_field = match config_variants.iter().find(|(v, _)| *v == rv) {
+                    Some((_, variant)) => ConfigField {
+                        name: name.clone(),
+                        description,
+                        ty: quote! { #name_pc_aw },
+                        default: variant.clone(),
+                        write: quote! { variant },
+                        checked: false,
+                    },
+                    None => ConfigField {
+                        name: name.clone(),
+                        description,
+                        ty: quote! { #fty },
+                        default: if width == 1 {
+                            let rv = rv != 0;
+                            quote! { #rv }
+                        } else {
+                            util::hex(rv)
+                        },
+                        write: quote! { write_raw },
+                        checked: unsafety.is_some(),
+                    },
+                };
+                Some(config_field)
+            };
 
-                    impl<'a> #name_pc_w<'a> {
-                        #proxy_items
-                        #proxy_items_fa
-                    }
+            if let Some((first, dim, increment, suffixes, suffixes_str)) = &field_dim {
+                let doc = format!(
+                    "Fields `{}` writer - {}{}",
+                    util::replace_suffix(&f.name, suffixes_str),
+                    description,
+                    write_doc
+                );
+                // the writer of the element picked at run time by the indexed method
+                let name_pc_dw = Ident::new(&(name_pc.clone() + "_DW"), span);
+                let dyn_doc = format!(
+                    "Fields `{}` writer of the element picked at run time - {}{}",
+                    util::replace_suffix(&f.name, suffixes_str),
+                    description,
+                    write_doc
+                );
+                mod_items.extend(quote! {
+                    #[doc = #doc]
+                    pub type #name_pc_w<'a, const O: usize> =
+                        crate::WProxy<'a, #rty, #name_uc_spec, #fty, #field_enum, crate::#safety, #width_lit, O>;
+                    #[doc = #dyn_doc]
+                    pub type #name_pc_dw<'a> =
+                        crate::DynWProxy<'a, #rty, #name_uc_spec, #fty, #field_enum, crate::#safety, #width_lit>;
                 });
-
-                if config.const_generic {
+                if !proxy_items.is_empty() {
                     mod_items.extend(quote! {
-                        #[doc = #cgdoc]
-                        pub struct #name_pc_cgw<'a, const O: usize> {
-                            w: &'a mut W,
+                        impl<'a, const O: usize> #name_pc_w<'a, O> {
+                            #proxy_items
                         }
-
-                        impl<'a, const O: usize> #name_pc_cgw<'a, O> {
+                        impl<'a> #name_pc_dw<'a> {
                             #proxy_items
-                            #proxy_items_cg
                         }
                     });
                 }
-            } else {
-                mod_items.extend(quote! {
-                    #[doc = #doc]
-                    pub struct #name_pc_w<'a> {
-                        w: &'a mut W,
-                    }
 
-                    impl<'a> #name_pc_w<'a> {
-                        #proxy_items
-                    }
-                });
-            }
-
-            if let Some((first, dim, increment, suffixes, suffixes_str)) = &field_dim {
                 let offset_calc = calculate_offset(*first, *increment, offset, false);
                 let doc = &util::replace_suffix(&description, suffixes_str);
                 w_impl_items.extend(quote! {
                     #[doc = #doc]
                     #inline
-                    pub unsafe fn #name_sc(&mut self, n: usize) -> #name_pc_w {
-                        #name_pc_w { w: self, offset: #offset_calc }
+                    pub unsafe fn #name_sc(&mut self, n: usize) -> #name_pc_dw {
+                        #name_pc_dw::new(self, #offset_calc)
                     }
                 });
+
                 for (i, suffix) in (0..*dim).zip(suffixes.iter()) {
                     let sub_offset = offset + (i as u64) * (*increment as u64);
-                    let name_sc_n = Ident::new(
-                        &util::replace_suffix(&f.name, suffix).to_sanitized_snake_case(),
-                        Span::call_site(),
-                    );
+                    let name_n = util::replace_suffix(&f.name, suffix);
+                    let name_sc_n =
+                        Ident::new(&name_n.to_sanitized_snake_case(), Span::call_site());
                     let doc = util::replace_suffix(
                         &description_with_bits(description_raw, sub_offset, width),
                         suffix,
                     );
-                    let sub_offset = util::unsuffixed(sub_offset as u64);
-                    if !config.const_generic {
-                        w_impl_items.extend(quote! {
-                            #[doc = #doc]
-                            #inline
-                            pub fn #name_sc_n(&mut self) -> #name_pc_w {
-                                #name_pc_w { w: self, offset: #sub_offset }
-                            }
-                        });
-                    } else {
-                        w_impl_items.extend(quote! {
+                    config_fields.extend(config_field(&name_sc_n, sub_offset, doc.clone()));
+                    if is_flag {
+                        let name = util::replace_suffix(&f.name, suffix);
+                        let name = Ident::new(&name.to_sanitized_upper_case(), span);
+                        flags.push((name, sub_offset, doc.clone()));
+                    }
+                    let sub_offset = util::unsuffixed(sub_offset);
+                    let alias = util::doc_alias(&name_n, &name_sc_n.to_string());
+                    w_impl_items.extend(quote! {
+                        #[doc = #doc]
+                        #alias
+                        #inline
+                        pub fn #name_sc_n(&mut self) -> #name_pc_w<#sub_offset> {
+                            #name_pc_w::new(self)
+                        }
+                    });
+                    w_impl_items.extend(setter(&name_sc_n, quote! { #name_pc_w::<#sub_offset> }));
+                    w_impl_items
+                        .extend(opt_setter(&name_sc_n, quote! { #name_pc_w::<#sub_offset> }));
+                    if bitband {
+                        reg_impl_items.extend(quote! {
                             #[doc = #doc]
                             #inline
-                            pub fn #name_sc_n(&mut self) -> #name_pc_cgw<#sub_offset> {
-                                #name_pc_cgw { w: self }
+                            pub fn #name_sc_n(&self) -> crate::BitBand<'_, #name_uc_spec, #sub_offset> {
+                                crate::BitBand::new(self)
                             }
                         });
                     }
                 }
             } else {
+                let doc = format!("Field `{}` writer - {}{}", f.name, description, write_doc);
+                let offset_lit = util::unsuffixed(offset);
+                mod_items.extend(quote! {
+                    #[doc = #doc]
+                    pub type #name_pc_w<'a> =
+                        crate::WProxy<'a, #rty, #name_uc_spec, #fty, #field_enum, crate::#safety, #width_lit, #offset_lit>;
+                });
+                if !proxy_items.is_empty() {
+                    mod_items.extend(quote! {
+                        impl<'a> #name_pc_w<'a> {
+                            #proxy_items
+                        }
+                    });
+                }
+
                 let doc = description_with_bits(description_raw, offset, width);
+                config_fields.extend(config_field(&name_sc, offset, doc.clone()));
+                if is_flag {
+                    let name = Ident::new(&f.name.to_sanitized_upper_case(), span);
+                    flags.push((name, offset, doc.clone()));
+                }
+                let alias = util::doc_alias(&name, &name_sc.to_string());
                 w_impl_items.extend(quote! {
                     #[doc = #doc]
+                    #alias
                     #inline
                     pub fn #name_sc(&mut self) -> #name_pc_w {
-                        #name_pc_w { w: self }
+                        #name_pc_w::new(self)
                     }
                 });
+                w_impl_items.extend(setter(&name_sc, quote! { #name_pc_w }));
+                w_impl_items.extend(opt_setter(&name_sc, quote! { #name_pc_w }));
+                if bitband {
+                    reg_impl_items.extend(quote! {
+                        #[doc = #doc]
+                        #inline
+                        pub fn #name_sc(&self) -> crate::BitBand<'_, #name_uc_spec, #offset_lit> {
+                            crate::BitBand::new(self)
+                        }
+                    });
+                }
             }
         }
     }
@@ -847,6 +1492,262 @@ pub fn fields(
     Ok(())
 }
 
+/// `Flags` struct with a constant per single-bit field, which can be combined with `|`
+fn flags_struct(
+    reg_name: &str,
+    rty: &Ident,
+    flags: &[(Ident, u64, String)],
+    inline: &TokenStream,
+) -> TokenStream {
+    let doc = format!(
+        "Single-bit fields of `{}`, combined with `|` and written by `set_flags`",
+        reg_name
+    );
+    let consts = flags.iter().map(|(name, offset, doc)| {
+        let offset = util::unsuffixed(*offset);
+        quote! {
+            #[doc = #doc]
+            pub const #name: Self = Self(1 << #offset);
+        }
+    });
+    quote! {
+        #[doc = #doc]
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        pub struct Flags(#rty);
+
+        impl Flags {
+            #(#consts)*
+
+            #[doc = "Raw bits of the fields"]
+            #inline
+            pub const fn bits(self) -> #rty {
+                self.0
+            }
+        }
+
+        impl core::ops::BitOr for Flags {
+            type Output = Self;
+            #inline
+            fn bitor(self, rhs: Self) -> Self {
+                Self(self.0 | rhs.0)
+            }
+        }
+    }
+}
+
+/// Writable field of a register in its `Config` struct
+pub struct ConfigField {
+    name: Ident,
+    description: String,
+    /// Enum of the field if it has a variant for the reset value, the raw type otherwise
+    ty: TokenStream,
+    default: TokenStream,
+    /// Method of the field writer taking a value of `ty`
+    write: TokenStream,
+    /// `true` if the raw values of the field must be checked by the caller
+    checked: bool,
+}
+
+/// `Config` struct with the writable fields of a register, and `configure` writing them all
+fn config_struct(
+    reg_name: &str,
+    name_uc_spec: &Ident,
+    fields: &[ConfigField],
+    inline: &TokenStream,
+) -> TokenStream {
+    let doc = format!(
+        "Values of the fields of `{}` written by `configure`, `Default` gives their reset values",
+        reg_name
+    );
+    let names: Vec<_> = fields.iter().map(|f| &f.name).collect();
+    let descriptions = fields.iter().map(|f| &f.description);
+    let tys = fields.iter().map(|f| &f.ty);
+    let defaults = fields.iter().map(|f| &f.default);
+    let writes = fields.iter().map(|f| &f.write);
+    let checked: Vec<_> = fields
+        .iter()
+        .filter(|f| f.checked)
+        .map(|f| format!("`{}`", f.name))
+        .collect();
+    let mut configure_doc = format!(
+        "Writes `config` to `{}`, other bits get their reset value",
+        reg_name
+    );
+    let unsafety = if checked.is_empty() {
+        quote! {}
+    } else {
+        configure_doc += &format!(
+            "\n\n# Safety\n\n`config` must only contain valid raw values for {}",
+            checked.join(", ")
+        );
+        quote! { unsafe }
+    };
+    quote! {
+        #[doc = #doc]
+        #[derive(Clone, Copy, Debug, PartialEq)]
+        pub struct Config {
+            #(
+                #[doc = #descriptions]
+                pub #names: #tys,
+            )*
+        }
+
+        impl Default for Config {
+            #inline
+            fn default() -> Self {
+                Self {
+                    #(#names: #defaults,)*
+                }
+            }
+        }
+
+        impl crate::Reg<#name_uc_spec> {
+            #[doc = #configure_doc]
+            #inline
+            pub #unsafety fn configure(&self, config: Config) {
+                self.write(|w| {
+                    #(w.#names().#writes(config.#names);)*
+                    w
+                })
+            }
+        }
+    }
+}
+
+/// Adds the `_wait_set` and `_wait_clear` methods of `--spin_helpers` for the single-bit field
+/// `name`, whose reader method is `name_sc`
+fn add_spin_helpers(
+    reg_impl_items: &mut TokenStream,
+    name: &str,
+    name_sc: &Ident,
+    config: &Config,
+) {
+    let inline = config.inline.attr();
+    for &(state, check) in &[("set", "bit_is_clear"), ("clear", "bit_is_set")] {
+        let method = format!("{}_wait_{}", name, state);
+        let method = Ident::new(&method.to_sanitized_snake_case(), Span::call_site());
+        let method_timeout = Ident::new(&format!("{}_timeout", method), Span::call_site());
+        let check = Ident::new(check, Span::call_site());
+        let doc = format!(
+            "Spins until the `{0}` field is {1}.\n\nThis is a busy wait, reading the register until \
+             then: it never returns if the field isn't {1}, e.g. because a clock is off. Prefer \
+             [`{2}`](Self::{2}) unless the field is known to change soon.",
+            name, state, method_timeout
+        );
+        let doc_timeout = format!(
+            "Spins until the `{0}` field is {1}, reading the register at most `max_reads` times.\n\n\
+             Returns [`Timeout`](crate::Timeout) if the field still isn't {1} after them.",
+            name, state
+        );
+        reg_impl_items.extend(quote! {
+            #[doc = #doc]
+            #inline
+            pub fn #method(&self) {
+                while self.read().#name_sc().#check() {}
+            }
+
+            #[doc = #doc_timeout]
+            #inline
+            pub fn #method_timeout(&self, max_reads: u32) -> Result<(), crate::Timeout> {
+                for _ in 0..max_reads {
+                    if !self.read().#name_sc().#check() {
+                        return Ok(());
+                    }
+                }
+                Err(crate::Timeout)
+            }
+        });
+    }
+}
+
+/// Describes the side effect of reading `target`
+fn read_action_doc(action: ReadAction, target: &str) -> String {
+    match action {
+        ReadAction::Clear => format!("Reading {} clears its contents.", target),
+        ReadAction::Set => format!("Reading {} sets all its bits.", target),
+        ReadAction::Modify => format!("Reading {} modifies its contents.", target),
+        ReadAction::ModifyExternal => format!(
+            "Reading {} affects other parts of the device, their values may change without being written.",
+            target
+        ),
+    }
+}
+
+fn protection_doc(protection: Protection) -> &'static str {
+    match protection {
+        Protection::Secure => "Access requires secure permission.",
+        Protection::NonSecure => "Accessible with secure or non-secure permission.",
+        Protection::Privileged => "Access requires privileged permission.",
+    }
+}
+
+/// Name of the method which applies the write side effect of `mwv` to the whole field, and
+/// whether it writes ones instead of zeros
+fn modified_write_method(
+    mwv: ModifiedWriteValues,
+    width: u32,
+) -> Option<(&'static str, bool, &'static str)> {
+    use ModifiedWriteValues::*;
+    let (method, ones) = match mwv {
+        OneToClear => ("clear", true),
+        ZeroToClear | Clear => ("clear", false),
+        OneToSet | Set => ("set", true),
+        ZeroToSet => ("set", false),
+        OneToToggle => ("toggle", true),
+        ZeroToToggle => ("toggle", false),
+        Modify => return None,
+    };
+    let doc = match (method, width) {
+        ("clear", 1) => "Clears the field bit",
+        ("set", 1) => "Sets the field bit",
+        ("toggle", 1) => "Toggles the field bit",
+        ("clear", _) => "Clears all bits of the field",
+        ("set", _) => "Sets all bits of the field",
+        _ => "Toggles all bits of the field",
+    };
+    Some((method, ones, doc))
+}
+
+fn modified_write_doc(mwv: ModifiedWriteValues) -> Option<&'static str> {
+    use ModifiedWriteValues::*;
+    Some(match mwv {
+        OneToClear => "Writing `1` to a bit clears it, writing `0` has no effect.",
+        OneToSet => "Writing `1` to a bit sets it, writing `0` has no effect.",
+        OneToToggle => "Writing `1` to a bit toggles it, writing `0` has no effect.",
+        ZeroToClear => "Writing `0` to a bit clears it, writing `1` has no effect.",
+        ZeroToSet => "Writing `0` to a bit sets it, writing `1` has no effect.",
+        ZeroToToggle => "Writing `0` to a bit toggles it, writing `1` has no effect.",
+        Clear => "Any write clears all bits of the field.",
+        Set => "Any write sets all bits of the field.",
+        Modify => return None,
+    })
+}
+
+const REG_METHODS: [&str; 17] = [
+    "as_ptr",
+    "read",
+    "reset",
+    "write",
+    "write_with_zero",
+    "write_from",
+    "write_value",
+    "write_once",
+    "modify",
+    "modify_ext",
+    "modify_if_changed",
+    "modify_bits",
+    "write_preserving",
+    "modify_critical",
+    "modify_cs",
+    "reset_masked",
+    "configure",
+];
+
+/// Checks if `address` is in the SRAM or peripheral bit-band region of Cortex-M3/M4
+fn in_bitband_region(address: u64) -> bool {
+    (0x2000_0000..0x2010_0000).contains(&address) || (0x4000_0000..0x4010_0000).contains(&address)
+}
+
 fn unsafety(write_constraint: Option<&WriteConstraint>, width: u32) -> Option<Ident> {
     match &write_constraint {
         Some(&WriteConstraint::Range(range))
@@ -867,6 +1768,8 @@ fn unsafety(write_constraint: Option<&WriteConstraint>, width: u32) -> Option<Id
 }
 
 struct Variant {
+    /// Name in the SVD
+    name: String,
     doc: String,
     pc: Ident,
     nksc: Ident,
@@ -881,7 +1784,7 @@ impl Variant {
             .iter()
             // filter out all reserved variants, as we should not
             // generate code for them
-            .filter(|field| field.name.to_lowercase() != "reserved" && field.is_default == None)
+            .filter(|field| field.name.to_lowercase() != "reserved" && field.is_default.is_none())
             .map(|ev| {
                 let value = u64(ev.value.ok_or_else(|| {
                     anyhow!("EnumeratedValue {} has no `<value>` field", ev.name)
@@ -890,6 +1793,7 @@ impl Variant {
                 let nksc = ev.name.to_sanitized_not_keyword_snake_case();
                 let sc = util::sanitize_keyword(nksc.clone());
                 Ok(Variant {
+                    name: ev.name.clone(),
                     doc: ev
                         .description
                         .clone()
@@ -904,6 +1808,35 @@ impl Variant {
     }
 }
 
+/// Returns `true` if `n` variants cover every bit pattern of a field `width` bits wide
+fn covers_all(n: usize, width: u32) -> bool {
+    // a 64-bit field has more bit patterns than any enum can have variants
+    1u64.checked_shl(width) == Some(n as u64)
+}
+
+/// Adds a `const fn` extracting the field `name` from a raw value of the register
+fn add_from_bits(
+    mod_items: &mut TokenStream,
+    name: &str,
+    rty: &Ident,
+    fty: &Ident,
+    value: TokenStream,
+    inline: &TokenStream,
+) {
+    let fn_name = Ident::new(
+        &format!("{}_from_bits", name.to_sanitized_not_keyword_snake_case()),
+        Span::call_site(),
+    );
+    let doc = format!("Extracts field `{}` from a raw value of the register", name);
+    mod_items.extend(quote! {
+        #[doc = #doc]
+        #inline
+        pub const fn #fn_name(bits: #rty) -> #fty {
+            #value
+        }
+    });
+}
+
 fn add_with_no_variants(
     mod_items: &mut TokenStream,
     pc: &Ident,
@@ -917,11 +1850,7 @@ fn add_with_no_variants(
         quote! { val.0 as _ }
     };
 
-    let desc = if let Some(rv) = reset_value {
-        format!("{}\n\nValue on reset: {}", desc, rv)
-    } else {
-        desc.to_owned()
-    };
+    let desc = enum_doc(desc, reset_value);
 
     mod_items.extend(quote! {
         #[doc = #desc]
@@ -936,13 +1865,16 @@ fn add_with_no_variants(
     });
 }
 
+#[allow(clippy::too_many_arguments)]
 fn add_from_variants(
     mod_items: &mut TokenStream,
     variants: &[Variant],
     pc: &Ident,
+    svd_name: Option<&str>,
     fty: &Ident,
     desc: &str,
     reset_value: Option<u64>,
+    config: &Config,
 ) {
     let (repr, cast) = if fty == "bool" {
         (quote! {}, quote! { variant as u8 != 0 })
@@ -955,23 +1887,33 @@ fn add_from_variants(
         let desc = util::escape_brackets(&util::respace(&format!("{}: {}", v.value, v.doc)));
         let pcv = &v.pc;
         let pcval = &util::unsuffixed(v.value);
+        let alias = util::doc_alias(&v.name, &pcv.to_string());
         quote! {
             #[doc = #desc]
+            #alias
             #pcv = #pcval,
         }
     }) {
         vars.extend(v);
     }
 
-    let desc = if let Some(rv) = reset_value {
-        format!("{}\n\nValue on reset: {}", desc, rv)
+    let desc = enum_doc(desc, reset_value);
+    let alias = svd_name
+        .map(|name| util::doc_alias(name, &pc.to_string()))
+        .unwrap_or_default();
+    let pcvs = variants.iter().map(|v| &v.pc);
+    // the variants are (de)serialized by their names, which are unique identifiers
+    let serde = if config.serde {
+        quote! { #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))] }
     } else {
-        desc.to_owned()
+        quote! {}
     };
 
     mod_items.extend(quote! {
         #[doc = #desc]
-        #[derive(Clone, Copy, Debug, PartialEq)]
+        #alias
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+        #serde
         #repr
         pub enum #pc {
             #vars
@@ -982,7 +1924,202 @@ fn add_from_variants(
                 #cast
             }
         }
+        impl #pc {
+            #[doc = "Iterates over all the variants, in the order of their values in the SVD"]
+            #[inline]
+            pub fn variants() -> impl Iterator<Item = Self> {
+                let variants: &'static [Self] = &[#(Self::#pcvs),*];
+                variants.iter().copied()
+            }
+        }
     });
+
+    // by the values of the variants, not their order in the SVD like a derive
+    if config.impl_ord {
+        let raw = if fty == "bool" {
+            quote! { u8 }
+        } else {
+            quote! { #fty }
+        };
+        mod_items.extend(quote! {
+            impl PartialOrd for #pc {
+                #[inline(always)]
+                fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+                    Some(self.cmp(other))
+                }
+            }
+            impl Ord for #pc {
+                #[inline(always)]
+                fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+                    (*self as #raw).cmp(&(*other as #raw))
+                }
+            }
+        });
+    }
+
+    if config.ufmt {
+        let arms = variants.iter().map(|v| {
+            let pcv = &v.pc;
+            let name = pcv.to_string();
+            quote! { #pc::#pcv => f.write_str(#name), }
+        });
+        mod_items.extend(quote! {
+            #[cfg(feature = "ufmt")]
+            impl ufmt::uDebug for #pc {
+                fn fmt<W>(&self, f: &mut ufmt::Formatter<'_, W>) -> core::result::Result<(), W::Error>
+                where
+                    W: ufmt::uWrite + ?Sized,
+                {
+                    match self {
+                        #(#arms)*
+                    }
+                }
+            }
+        });
+    }
+
+    if config.str_names {
+        let (pcvs, names): (Vec<_>, Vec<_>) = variants.iter().map(|v| (&v.pc, &v.name)).unzip();
+        mod_items.extend(quote! {
+            #[cfg(feature = "str-names")]
+            impl #pc {
+                #[doc = "Name of the variant in the SVD"]
+                #[inline]
+                pub fn as_str(&self) -> &'static str {
+                    match self {
+                        #(#pc::#pcvs => #names,)*
+                    }
+                }
+            }
+            #[cfg(feature = "str-names")]
+            impl<'a> core::convert::TryFrom<&'a str> for #pc {
+                type Error = crate::UnknownName;
+                #[inline]
+                fn try_from(name: &'a str) -> core::result::Result<Self, Self::Error> {
+                    let name = name.trim();
+                    #(
+                        if name.eq_ignore_ascii_case(#names) {
+                            return Ok(#pc::#pcvs);
+                        }
+                    )*
+                    Err(crate::UnknownName)
+                }
+            }
+        });
+    }
+
+    if config.defmt {
+        let arms = variants.iter().map(|v| {
+            let pcv = &v.pc;
+            let name = pcv.to_string();
+            quote! { #pc::#pcv => defmt::write!(f, #name), }
+        });
+        mod_items.extend(quote! {
+            #[cfg(feature = "defmt")]
+            impl defmt::Format for #pc {
+                fn format(&self, f: defmt::Formatter) {
+                    match self {
+                        #(#arms)*
+                    }
+                }
+            }
+        });
+    }
+}
+
+/// Variants of an enum, used to find the fields with the same enumerated values
+type EnumKey = (u32, Vec<(u64, String, String, String)>);
+
+/// Returns the variants of an enum to share with `--shared_enums`, sorted by value
+fn shared_enum_key(variants: &[Variant], width: u32, config: &Config) -> Option<EnumKey> {
+    if !config.shared_enums || variants.is_empty() {
+        return None;
+    }
+    let mut key: Vec<_> = variants
+        .iter()
+        .map(|v| {
+            let doc = if config.shared_enums_ignore_doc {
+                String::new()
+            } else {
+                v.doc.clone()
+            };
+            // `as_str` returns the names of the SVD
+            let name = if config.str_names {
+                v.name.clone()
+            } else {
+                String::new()
+            };
+            (v.value, v.pc.to_string(), doc, name)
+        })
+        .collect();
+    key.sort();
+    Some((width, key))
+}
+
+fn enum_doc(desc: &str, reset_value: Option<u64>) -> String {
+    if let Some(rv) = reset_value {
+        format!("{}\n\nValue on reset: {}", desc, rv)
+    } else {
+        desc.to_owned()
+    }
+}
+
+/// Implements `From<fty>` for enums covering all values of the field and
+/// `TryFrom<fty>`, returning the unknown bits as error, for the others
+fn add_variant_conversions(
+    mod_items: &mut TokenStream,
+    variants: &[Variant],
+    pc: &Ident,
+    fty: &Ident,
+    width: u32,
+) -> Result<()> {
+    let full = covers_all(variants.len(), width);
+
+    let mut arms = TokenStream::new();
+    for v in variants {
+        let i = util::unsuffixed_or_bool(v.value, width);
+        let pcv = &v.pc;
+        arms.extend(if full {
+            quote! { #i => #pc::#pcv, }
+        } else {
+            quote! { #i => Ok(#pc::#pcv), }
+        });
+    }
+
+    if full {
+        // values above the field width are ignored
+        let bits = if width == width.to_ty_width()? {
+            quote! { bits }
+        } else {
+            let mask = util::hex(u64::MAX >> (64 - width));
+            arms.extend(quote! { _ => unreachable!(), });
+            quote! { bits & #mask }
+        };
+        mod_items.extend(quote! {
+            impl From<#fty> for #pc {
+                #[inline(always)]
+                fn from(bits: #fty) -> Self {
+                    match #bits {
+                        #arms
+                    }
+                }
+            }
+        });
+    } else {
+        arms.extend(quote! { _ => Err(bits), });
+        mod_items.extend(quote! {
+            impl core::convert::TryFrom<#fty> for #pc {
+                type Error = #fty;
+                #[inline(always)]
+                fn try_from(bits: #fty) -> core::result::Result<Self, Self::Error> {
+                    match bits {
+                        #arms
+                    }
+                }
+            }
+        });
+    }
+    Ok(())
 }
 
 fn calculate_offset(
@@ -1037,26 +2174,10 @@ fn derive_from_base(
     base_pc: &Ident,
     desc: &str,
 ) {
-    let span = Span::call_site();
-    if let (Some(peripheral), Some(register)) = (&base.peripheral, &base.register) {
-        let pmod_ = peripheral.to_sanitized_snake_case();
-        let rmod_ = register.to_sanitized_snake_case();
-        let pmod_ = Ident::new(&pmod_, span);
-        let rmod_ = Ident::new(&rmod_, span);
-
-        mod_items.extend(quote! {
-            #[doc = #desc]
-            pub type #pc =
-                crate::#pmod_::#rmod_::#base_pc;
-        });
-    } else if let Some(register) = &base.register {
-        let mod_ = register.to_sanitized_snake_case();
-        let mod_ = Ident::new(&mod_, span);
-
+    if let Some(register) = &base.register {
         mod_items.extend(quote! {
             #[doc = #desc]
-            pub type #pc =
-                super::#mod_::#base_pc;
+            pub type #pc = #register::#base_pc;
         });
     } else {
         mod_items.extend(quote! {
@@ -1068,11 +2189,31 @@ fn derive_from_base(
 
 #[derive(Clone, Debug)]
 pub struct Base<'a> {
-    pub peripheral: Option<&'a str>,
-    pub register: Option<&'a str>,
+    /// Path of the module of the register of the field, `None` for the register of the field
+    /// deriving from it
+    pub register: Option<TokenStream>,
     pub field: &'a str,
 }
 
+/// A register in which `derivedFrom` paths are resolved
+#[derive(Clone)]
+struct Scope<'a> {
+    peripheral: &'a Peripheral,
+    /// The registers of the same peripheral or cluster
+    registers: Vec<&'a Register>,
+    register: &'a Register,
+    fields: &'a [Field],
+    /// Path of the module of the register, from the module of the deriving register's parent
+    path: Vec<Ident>,
+}
+
+/// Longest chain of `derivedFrom`s followed before giving up, which only loops reach
+const MAX_DERIVATIONS: usize = 16;
+
+/// Resolves the `derivedFrom` of `evs` to the enumerated values they refer to, following chains
+/// of `derivedFrom`s down to the one with the values, so all the fields deriving from it use its
+/// enums
+#[allow(clippy::too_many_arguments)]
 fn lookup<'a>(
     evs: &'a [EnumeratedValues],
     fields: &'a [Field],
@@ -1080,49 +2221,42 @@ fn lookup<'a>(
     all_registers: &'a [&'a Register],
     peripheral: &'a Peripheral,
     all_peripherals: &'a [Peripheral],
+    config: &Config,
 ) -> Result<Vec<(&'a EnumeratedValues, Option<Base<'a>>)>> {
-    let evs = evs
-        .iter()
+    let own = Scope {
+        peripheral,
+        registers: all_registers.to_vec(),
+        register,
+        fields,
+        path: vec![
+            Ident::new("super", Span::call_site()),
+            register_mod(register, config),
+        ],
+    };
+    evs.iter()
         .map(|evs| {
-            if let Some(base) = &evs.derived_from {
-                let mut parts = base.split('.');
-
-                match (parts.next(), parts.next(), parts.next(), parts.next()) {
-                    (
-                        Some(base_peripheral),
-                        Some(base_register),
-                        Some(base_field),
-                        Some(base_evs),
-                    ) => lookup_in_peripherals(
-                        base_peripheral,
-                        base_register,
-                        base_field,
-                        base_evs,
-                        all_peripherals,
-                    ),
-                    (Some(base_register), Some(base_field), Some(base_evs), None) => {
-                        lookup_in_peripheral(
-                            None,
-                            base_register,
-                            base_field,
-                            base_evs,
-                            all_registers,
-                            peripheral,
-                        )
-                    }
-                    (Some(base_field), Some(base_evs), None, None) => {
-                        lookup_in_fields(base_evs, base_field, fields, register)
-                    }
-                    (Some(base_evs), None, None, None) => lookup_in_register(base_evs, register),
-                    _ => unreachable!(),
-                }
-            } else {
-                Ok((evs, None))
+            let (mut evs, mut base, mut scope) = (evs, None, own.clone());
+            for _ in 0..MAX_DERIVATIONS {
+                let path = match &evs.derived_from {
+                    Some(path) => path,
+                    None => return Ok((evs, base)),
+                };
+                let (derived, field, derived_scope) =
+                    lookup_in_scope(path, &scope, all_peripherals, config)?;
+                let register = &derived_scope.path;
+                base = Some(Base {
+                    register: (*register != own.path).then(|| quote!(#(#register)::*)),
+                    field: &field.name,
+                });
+                evs = derived;
+                scope = derived_scope;
             }
+            Err(anyhow!(
+                "Loop in the derivedFrom of enumeratedValues {}",
+                evs.derived_from.as_deref().unwrap_or_default()
+            ))
         })
-        .collect::<Result<Vec<_>>>()?;
-
-    Ok(evs)
+        .collect()
 }
 
 fn lookup_filter<'a>(
@@ -1138,145 +2272,212 @@ fn lookup_filter<'a>(
     evs.first().cloned()
 }
 
-fn lookup_in_fields<'f>(
-    base_evs: &str,
-    base_field: &str,
-    fields: &'f [Field],
-    register: &Register,
-) -> Result<(&'f EnumeratedValues, Option<Base<'f>>)> {
-    if let Some(base_field) = fields.iter().find(|f| f.name == base_field) {
-        lookup_in_field(base_evs, None, None, base_field)
-    } else {
-        Err(anyhow!(
-            "Field {} not found in register {}",
-            base_field,
-            register.name
-        ))
+/// Finds the enumerated values of the `derivedFrom` `path`, relative to `scope`. The path is
+/// `[[peripheral.][clusters.]register.][field.]enumeratedValues`
+fn lookup_in_scope<'a>(
+    path: &str,
+    scope: &Scope<'a>,
+    all_peripherals: &'a [Peripheral],
+    config: &Config,
+) -> Result<(&'a EnumeratedValues, &'a Field, Scope<'a>)> {
+    let parts = path.split('.').collect::<Vec<_>>();
+    let (base_evs, parts) = parts.split_last().unwrap();
+    match parts {
+        [] => lookup_in_register(base_evs, scope),
+        [base_field] => lookup_in_fields(base_evs, base_field, scope.clone()),
+        [base_register, base_field] => {
+            let register = scope
+                .registers
+                .iter()
+                .find(|r| r.name == *base_register)
+                .ok_or_else(|| {
+                    anyhow!(
+                        "No register {} in peripheral {}",
+                        base_register,
+                        scope.peripheral.name
+                    )
+                })?;
+            let mut path = scope.path[..scope.path.len() - 1].to_vec();
+            path.push(register_mod(register, config));
+            let scope = Scope {
+                register,
+                fields: register.fields.as_deref().unwrap_or(&[]),
+                path,
+                ..scope.clone()
+            };
+            lookup_in_fields(base_evs, base_field, scope)
+        }
+        [prefix @ .., base_register, base_field] => {
+            // the first name is a peripheral, or otherwise a cluster of the same peripheral
+            let (peripheral, clusters) = match all_peripherals.iter().find(|p| p.name == prefix[0])
+            {
+                Some(peripheral) => (peripheral, &prefix[1..]),
+                None => (scope.peripheral, prefix),
+            };
+            // derived peripherals reexport the module of the original
+            let ercs = match (&peripheral.registers, &peripheral.derived_from) {
+                (Some(ercs), _) => ercs.as_slice(),
+                (None, Some(original)) => all_peripherals
+                    .iter()
+                    .find(|p| p.name == *original)
+                    .and_then(|p| p.registers.as_deref())
+                    .unwrap_or(&[]),
+                (None, None) => &[],
+            };
+            let (registers, register, mut path) = find_register(ercs, clusters, base_register)
+                .ok_or_else(|| {
+                    anyhow!(
+                        "No register {} in peripheral {}",
+                        clusters
+                            .iter()
+                            .chain(Some(base_register))
+                            .copied()
+                            .collect::<Vec<_>>()
+                            .join("."),
+                        peripheral.name
+                    )
+                })?;
+            path.insert(
+                0,
+                Ident::new(
+                    &peripheral.name.to_sanitized_snake_case(),
+                    Span::call_site(),
+                ),
+            );
+            path.insert(0, Ident::new("crate", Span::call_site()));
+            path.push(register_mod(register, config));
+            let scope = Scope {
+                peripheral,
+                registers,
+                register,
+                fields: register.fields.as_deref().unwrap_or(&[]),
+                path,
+            };
+            lookup_in_fields(base_evs, base_field, scope)
+        }
     }
 }
 
-fn lookup_in_peripheral<'p>(
-    base_peripheral: Option<&'p str>,
-    base_register: &'p str,
-    base_field: &str,
-    base_evs: &str,
-    all_registers: &[&'p Register],
-    peripheral: &'p Peripheral,
-) -> Result<(&'p EnumeratedValues, Option<Base<'p>>)> {
-    if let Some(register) = all_registers.iter().find(|r| r.name == base_register) {
-        if let Some(field) = register
-            .fields
-            .as_deref()
-            .unwrap_or(&[])
-            .iter()
-            .find(|f| f.name == base_field)
-        {
-            lookup_in_field(base_evs, Some(base_register), base_peripheral, field)
-        } else {
-            Err(anyhow!(
-                "No field {} in register {}",
-                base_field,
-                register.name
-            ))
+/// Finds the register `name` in the `clusters` of `ercs`, or anywhere in them if there are no
+/// `clusters`, preferring the registers outside of clusters. Returns its sibling registers too,
+/// and the modules of the clusters it is in
+fn find_register<'a>(
+    ercs: &'a [RegisterCluster],
+    clusters: &[&str],
+    name: &str,
+) -> Option<(Vec<&'a Register>, &'a Register, Vec<Ident>)> {
+    let registers = util::only_registers(ercs);
+    let cluster_mod = |c: &Cluster| {
+        Ident::new(
+            &util::replace_suffix(&c.name, "").to_sanitized_snake_case(),
+            Span::call_site(),
+        )
+    };
+    match clusters.split_first() {
+        None => {
+            if let Some(register) = registers.iter().find(|r| r.name == name) {
+                return Some((registers.clone(), register, vec![]));
+            }
+            ercs.iter().find_map(|erc| match erc {
+                RegisterCluster::Cluster(c) => {
+                    let (registers, register, mut path) = find_register(&c.children, &[], name)?;
+                    path.insert(0, cluster_mod(c));
+                    Some((registers, register, path))
+                }
+                RegisterCluster::Register(_) => None,
+            })
         }
-    } else {
-        Err(anyhow!(
-            "No register {} in peripheral {}",
-            base_register,
-            peripheral.name
-        ))
+        Some((first, rest)) => ercs.iter().find_map(|erc| match erc {
+            RegisterCluster::Cluster(c)
+                if c.name == *first || util::replace_suffix(&c.name, "") == *first =>
+            {
+                let (registers, register, mut path) = find_register(&c.children, rest, name)?;
+                path.insert(0, cluster_mod(c));
+                Some((registers, register, path))
+            }
+            _ => None,
+        }),
     }
 }
 
-fn lookup_in_field<'f>(
+/// Module of the code of `register`
+fn register_mod(register: &Register, config: &Config) -> Ident {
+    Ident::new(
+        &util::name_of(register, config.ignore_groups).to_sanitized_snake_case(),
+        Span::call_site(),
+    )
+}
+
+fn lookup_in_fields<'a>(
     base_evs: &str,
-    base_register: Option<&'f str>,
-    base_peripheral: Option<&'f str>,
-    field: &'f Field,
-) -> Result<(&'f EnumeratedValues, Option<Base<'f>>)> {
-    for evs in &field.enumerated_values {
-        if evs.name.as_deref() == Some(base_evs) {
-            return Ok((
-                evs,
-                Some(Base {
-                    field: &field.name,
-                    register: base_register,
-                    peripheral: base_peripheral,
-                }),
-            ));
-        }
+    base_field: &str,
+    scope: Scope<'a>,
+) -> Result<(&'a EnumeratedValues, &'a Field, Scope<'a>)> {
+    let field = scope
+        .fields
+        .iter()
+        .find(|f| f.name == base_field)
+        .ok_or_else(|| {
+            anyhow!(
+                "Field {} not found in register {}",
+                base_field,
+                scope.register.name
+            )
+        })?;
+    match field
+        .enumerated_values
+        .iter()
+        .find(|evs| is_named(evs, base_evs))
+    {
+        Some(evs) => Ok((evs, field, scope)),
+        None => Err(anyhow!(
+            "No EnumeratedValues {} in field {}",
+            base_evs,
+            field.name
+        )),
     }
-
-    Err(anyhow!(
-        "No EnumeratedValues {} in field {}",
-        base_evs,
-        field.name
-    ))
 }
 
-fn lookup_in_register<'r>(
+fn lookup_in_register<'a>(
     base_evs: &str,
-    register: &'r Register,
-) -> Result<(&'r EnumeratedValues, Option<Base<'r>>)> {
+    scope: &Scope<'a>,
+) -> Result<(&'a EnumeratedValues, &'a Field, Scope<'a>)> {
     let mut matches = vec![];
 
-    for f in register.fields.as_deref().unwrap_or(&[]) {
+    for f in scope.register.fields.as_deref().unwrap_or(&[]) {
         if let Some(evs) = f
             .enumerated_values
             .iter()
             .find(|evs| evs.name.as_deref() == Some(base_evs))
         {
-            matches.push((evs, &f.name))
+            matches.push((evs, f))
         }
     }
 
-    match matches.first() {
-        None => Err(anyhow!(
+    match matches.as_slice() {
+        [] => Err(anyhow!(
             "EnumeratedValues {} not found in register {}",
             base_evs,
-            register.name
+            scope.register.name
         )),
-        Some(&(evs, field)) => {
-            if matches.len() == 1 {
-                Ok((
-                    evs,
-                    Some(Base {
-                        field,
-                        register: None,
-                        peripheral: None,
-                    }),
-                ))
-            } else {
-                let fields = matches.iter().map(|(f, _)| &f.name).collect::<Vec<_>>();
-                Err(anyhow!(
-                    "Fields {:?} have an \
-                     enumeratedValues named {}",
-                    fields,
-                    base_evs
-                ))
-            }
+        [(evs, field)] => Ok((evs, field, scope.clone())),
+        _ => {
+            let fields = matches.iter().map(|(_, f)| &f.name).collect::<Vec<_>>();
+            Err(anyhow!(
+                "Fields {:?} have an \
+                 enumeratedValues named {}",
+                fields,
+                base_evs
+            ))
         }
     }
 }
 
-fn lookup_in_peripherals<'p>(
-    base_peripheral: &'p str,
-    base_register: &'p str,
-    base_field: &str,
-    base_evs: &str,
-    all_peripherals: &'p [Peripheral],
-) -> Result<(&'p EnumeratedValues, Option<Base<'p>>)> {
-    if let Some(peripheral) = all_peripherals.iter().find(|p| p.name == base_peripheral) {
-        let all_registers = peripheral.all_registers().collect::<Vec<_>>();
-        lookup_in_peripheral(
-            Some(base_peripheral),
-            base_register,
-            base_field,
-            base_evs,
-            all_registers.as_slice(),
-            peripheral,
-        )
-    } else {
-        Err(anyhow!("No peripheral {}", base_peripheral))
+/// Returns `true` if `evs` are named `name`, or have no name and derive from values named `name`
+fn is_named(evs: &EnumeratedValues, name: &str) -> bool {
+    match (&evs.name, &evs.derived_from) {
+        (Some(n), _) => n == name,
+        (None, Some(path)) => path.rsplit('.').next() == Some(name),
+        (None, None) => false,
     }
 }
@@ -22,9 +22,17 @@
 //! be tailored for either architecture using the `--target` flag. The flag accepts "cortex-m",
 //! "msp430", "riscv", "xtensa-lx" and "none" as values. "none" can be used to generate a crate that's
 //! architecture agnostic and that should work for architectures that `svd2rust` doesn't currently
-//! know about like the Cortex-A architecture.
+//! know about like the Cortex-A architecture. "xtensa" is accepted as an alias of "xtensa-lx".
 //!
-//! If the `--target` flag is omitted `svd2rust` assumes the target is the Cortex-M architecture.
+//! If the `--target` flag is omitted `svd2rust` picks the target from the `<name>` of the `<cpu>`
+//! of the SVD file, e.g. cortex-m for `CM4` or riscv for `RV32IMC`, and otherwise assumes the
+//! target is the Cortex-M architecture. An explicit `--target` always wins over the `<cpu>`.
+//! Used as a library, svd2rust does the same in [generate()] and [config_for] if the
+//! `target_from_cpu` of the [Config] is set.
+//!
+//! The `<cpu>` also gives the `NVIC_PRIO_BITS` constant of the generated crate, and `bool`
+//! constants for the features of the core: `HAS_FPU` and `HAS_MPU`, and `HAS_DSP`, `HAS_ICACHE`,
+//! `HAS_DCACHE`, `HAS_ITCM`, `HAS_DTCM` and `HAS_VTOR` if the SVD file has them.
 //!
 //! If using the `--generic_mod` option, the emitted `generic.rs` needs to be moved to `src`, and
 //! [`form`](https://github.com/djmcgill/form) commit fcb397a or newer is required for splitting
@@ -37,7 +45,7 @@
 //!
 //! - `build.rs`, build script that places `device.x` somewhere the linker can find.
 //! - `device.x`, linker script that weakly aliases all the interrupt handlers to the default
-//! exception handler (`DefaultHandler`).
+//!   exception handler (`DefaultHandler`).
 //! - `lib.rs`, the generated code.
 //!
 //! All these files must be included in the same device crate. The `lib.rs` file contains several
@@ -56,6 +64,10 @@
 //! $ cargo fmt
 //! ```
 //!
+//! Alternatively the `--format` flag runs `rustfmt` on the generated files before svd2rust
+//! exits. It is off by default as formatting the code of large devices takes a while. If
+//! `rustfmt` can't be run, svd2rust warns and leaves the files as they were generated.
+//!
 //! The resulting crate must provide an opt-in "rt" feature and depend on these crates:
 //! `cortex-m` v0.7, `cortex-m-rt` >=v0.6.13 and `vcell` >=v0.1.2. Furthermore
 //! the "device" feature of `cortex-m-rt` must be enabled when the "rt" feature is enabled. The
@@ -85,7 +97,7 @@
 //!
 //! - `build.rs`, build script that places `device.x` somewhere the linker can find.
 //! - `device.x`, linker script that weakly aliases all the interrupt handlers to the default
-//! exception handler (`DefaultHandler`).
+//!   exception handler (`DefaultHandler`).
 //! - `lib.rs`, the generated code.
 //!
 //! All these files must be included in the same device crate. The `lib.rs` file contains several
@@ -131,6 +143,15 @@
 //! unstable = ["msp430-atomic"]
 //! ```
 //!
+//! With the `--nightly` flag registers get `set_bits`, `clear_bits` and `toggle_bits` methods,
+//! which change bits with a single `bis`, `bic` or `xor` instruction. Other read-modify-write
+//! sequences can be protected from interrupt handlers with `modify_critical`, which runs
+//! `modify` inside `msp430::interrupt::free`:
+//!
+//! ```ignore
+//! p.PORT_1_2.p1out.modify_critical(|r, w| w.p0().bit(!r.p0().bit()));
+//! ```
+//!
 //! ## Other targets
 //!
 //! When the target is riscv or none `svd2rust` will emit only the `lib.rs` file. Like in
@@ -181,8 +202,9 @@
 //! ```
 //!
 //! The singleton property can be *unsafely* bypassed using the `ptr` static method which is
-//! available on all the peripheral types. This method is useful for implementing safe higher
-//! level abstractions.
+//! available on all the peripheral types. It returns the `PTR` constant, the base address of the
+//! peripheral as a pointer to its register block. This method is useful for implementing safe
+//! higher level abstractions.
 //!
 //! ```ignore
 //! struct PA0 { _0: () }
@@ -209,9 +231,28 @@
 //! }
 //! ```
 //!
+//! An instance of a single peripheral can also be obtained with its `unsafe` `steal` method, e.g.
+//! to use it from an interrupt handler. This bypasses the ownership tracking of `Peripherals`:
+//! it is up to the caller to ensure the stolen instance doesn't race with other instances.
+//!
+//! ```ignore
+//! let gpioa = unsafe { stm32f30x::GPIOA::steal() };
+//! ```
+//!
+//! For bring-up and debugging, the `unsafe` `peek` and `poke` methods of a peripheral read and
+//! write the 32-bit word at a byte offset from its base address, bypassing the register API.
+//! They take the peripheral by reference, so they can only be used by the code owning it.
+//!
+//! ```ignore
+//! let cr1 = unsafe { i2c1.peek(0x00) };
+//! unsafe { i2c1.poke(0x00, cr1 | 1) };
+//! ```
+//!
 //! Each peripheral proxy `deref`s to a `RegisterBlock` struct that represents a piece of device
 //! memory. Each field in this `struct` represents one register in the register block associated to
-//! the peripheral.
+//! the peripheral. `deref` is safe: the proxy is a zero-sized token and the register block only
+//! exists as memory-mapped registers at `PTR`, which are accessed with volatile operations through
+//! shared references.
 //!
 //! ```ignore
 //! /// Inter-integrated circuit
@@ -244,6 +285,133 @@
 //! }
 //! ```
 //!
+//! Peripherals that are `derivedFrom` another one without overriding its registers share the
+//! register block of the original peripheral: their module is a re-export of the original
+//! module, while the proxy type keeps its own base address and interrupts.
+//!
+//! ```ignore
+//! pub struct USART2 { _marker: PhantomData<*const ()> }
+//! impl USART2 {
+//!     pub const PTR: *const usart1::RegisterBlock = 0x4000_4400 as *const _;
+//! }
+//! pub use usart1 as usart2;
+//! ```
+//!
+//! A derived peripheral that lists `registers` of its own gets a separate register block.
+//!
+//! The `PeripheralId` enum has a variant for each peripheral of `Peripherals`, whose `base`
+//! method returns its base address, and the `ID` constant of each peripheral type is its
+//! variant. Generic drivers over several instances of a peripheral can use it as a key, e.g. to
+//! index the state of each instance:
+//!
+//! ```ignore
+//! fn init(id: PeripheralId, states: &mut [Option<State>]) {
+//!     states[id as usize] = Some(State::new(id.base()));
+//! }
+//!
+//! init(USART2::ID, &mut states);
+//! ```
+//!
+//! Register and cluster arrays whose `dimIncrement` matches the size of one element are
+//! generated as Rust arrays, so they can be indexed or iterated over in a loop:
+//!
+//! ```ignore
+//! for ch in dma1.ch.iter() {
+//!     ch.cr.reset();
+//! }
+//! ```
+//!
+//! Rust arrays also get a method of the same name taking the index as a const generic
+//! parameter, which fails to build instead of panicking if the index is out of bounds:
+//!
+//! ```ignore
+//! dma1.ch::<3>().cr.reset();
+//! dma1.ch::<7>().cr.reset(); // error[E0080]: attempt to compute `6_usize - 7_usize`, which would overflow
+//! ```
+//!
+//! The bound is a constant evaluated when the accessor is instantiated, as the inline `const`
+//! blocks which would check it earlier need a newer Rust than the MSRV of the generated code. So
+//! the error comes from `cargo build`, while `cargo check` and rust-analyzer accept the index.
+//!
+//! If the elements are not contiguous each element gets a field of its own (`ch0`, `ch1`, ...)
+//! at the right offset instead. For cluster arrays the `--const_generic` flag emits an
+//! `ArrayProxy` with indexed access in that case.
+//!
+//! If the SVD names the elements of such an array or `ArrayProxy` in a `dimArrayIndex`, an enum
+//! of those names is generated next to the register block, called after the `headerEnumName`
+//! of the `dimArrayIndex` or `<ARRAY>_INDEX`, with a `<array>_get` method taking it:
+//!
+//! ```ignore
+//! // <dimArrayIndex><headerEnumName>Chan</headerEnumName> with the values RX = 0 and TX = 1
+//! dma1.ch_get(dma1::Chan::TX).cr.reset();
+//! ```
+//!
+//! The `headerEnumName` is sanitized like the `headerStructName` of clusters, and ignored with a
+//! warning if it still isn't a valid Rust identifier. Values out of the array are skipped with a
+//! warning, while values that aren't valid Rust identifiers, or that repeat a name or an index,
+//! are an error.
+//!
+//! The MSRV is the same with or without `--const_generic`: field writers are `WProxy`s with
+//! the width and offset of the field as const generic parameters in both modes, so generated
+//! code always requires the `min_const_generics` of Rust 1.51. There is no mode with marker
+//! structs for the offsets of fields for older compilers, as the field writers, the checked
+//! indices of arrays like `ch::<3>()` and the writers built on them all need const generics.
+//!
+//! Registers that share their address, like the `alternateRegister`s and `alternateGroup`s of
+//! the SVD, can't be fields of the register block. They get accessor methods instead, which all
+//! return the register at the same offset, each with its own layout:
+//!
+//! ```ignore
+//! tim2.ccmr1_output().write(|w| w.oc1m().pwm_mode1());
+//! if tim2.input_ccmr1().read().ic1f().bits() != 0 {
+//!     ..
+//! }
+//! ```
+//!
+//! The name of a register in an `alternateGroup` is prefixed by the group name unless the
+//! `--ignore_groups` flag is passed.
+//!
+//! Clusters can be nested to any depth. Each cluster gets a struct of its own, with its
+//! registers and inner clusters at their offsets relative to the start of the cluster, plus
+//! the padding between them, and a module of the same name that holds the register types and
+//! the structs of the inner clusters. Accesses chain the fields of the nested structs:
+//!
+//! ```ignore
+//! dma.ch[1].desc[2].len.write(|w| unsafe { w.bits(64) });
+//! let src = dma.ch[0].src.addr.read().bits();
+//! ```
+//!
+//! The struct of a cluster is named after its `headerStructName` if it has one, e.g. `Channel`
+//! instead of `CH`, unless it would name another struct of the same module. The `%s` of arrays
+//! and the characters which can't be in identifiers are dropped, and a `headerStructName` which
+//! still isn't a valid identifier, like `type`, is ignored with a warning. `derivedFrom` still
+//! refers to the `name` of the cluster. The `headerStructName` of peripherals is not used, as
+//! each peripheral needs a type of its own. Registers have no `headerStructName` in the SVD
+//! schema, and svd-parser drops the `headerEnumName` of `<enumeratedValues>`, so registers and
+//! enums keep their usual names.
+//!
+//! Names from the SVD that give the same Rust identifier as an earlier item of the same scope,
+//! like a peripheral `GPIO-A` after `GPIOA` or a field `En` after `EN`, get a `_1`, `_2`, ...
+//! suffix, and svd2rust warns about the renaming. Arrays get it before their `%s`, e.g. `CH_1%s`,
+//! and the `derivedFrom`s referring to renamed items are renamed as well. The elements of field
+//! arrays come first, so a field `CH0` next to a `CH%s` becomes `CH0_1`, with a `ch0_1` accessor
+//! next to the `ch0` of the array.
+//!
+//! Peripherals, registers, fields, enumerated values and their enums whose Rust name differs
+//! from the SVD name by more than its case, e.g. the field `type_` of `TYPE` or the enum
+//! `EN_A` of the `enumeratedValues` `ENABLE`, get a `#[doc(alias)]` with the SVD name, so that
+//! searching the documentation for the name in the reference manual finds them.
+//!
+//! The module of each peripheral also has a `RESET_STATE` table with the offset and reset value
+//! of each register, or element of register arrays, that has a reset value in the SVD, sorted
+//! by offset. The values are `u32`s, or `u64`s if the block has 64-bit registers:
+//!
+//! ```ignore
+//! for &(offset, reset) in stm32f30x::i2c1::RESET_STATE.iter() {
+//!     assert_eq!(unsafe { i2c1.peek(offset) }, reset);
+//! }
+//! ```
+//!
 //! # `read` / `modify` / `write` API
 //!
 //! Each register in the register block, e.g. the `cr1` field in the `I2C` struct, exposes a
@@ -255,6 +423,18 @@
 //! - read-write registers expose all the methods: `read`, `modify`, and
 //!   `write`.
 //!
+//! These are the `Readable` registers, the `Writable` ones and the registers which are both.
+//! `read-writeOnce` registers are read-write and `writeOnce` ones are write-only. Registers
+//! without an `<access>` of their own whose fields all have an explicit read-only access are
+//! read-only, and likewise for write-only fields, before falling back to the `<access>` of their
+//! cluster, peripheral or device. In a read-write register, read-only fields have no writer and
+//! write-only fields no reader.
+//!
+//! `writeOnce` and `read-writeOnce` registers, like lock bits, implement `WriteOnce` too, which
+//! adds a `write_once` method. It writes like `write`, but documents at the call site that the
+//! later writes are ignored by the hardware, usually until the next reset. The docs of these
+//! registers and of their write-once fields say so too.
+//!
 //! Below shows signatures of each of these methods:
 //!
 //! (using `I2C`'s `CR2` register as an example)
@@ -280,9 +460,8 @@
 //!         ..
 //!     }
 //! }
-//! impl crate::ResetValue for CR2 {
-//!     type Type = u32;
-//!     fn reset_value() -> Self::Type { 0 }
+//! impl crate::Resettable for CR2_SPEC {
+//!     const RESET_VALUE: Self::Ux = 0;
 //! }
 //! ```
 //!
@@ -316,17 +495,47 @@
 //! }
 //! ```
 //!
+//! The raw value is returned by `bits`, both for the whole register and for a field. `into_bits`
+//! does the same, consuming the reader:
+//!
+//! ```ignore
+//! let cr2: u32 = i2c1.cr2.read().into_bits();
+//! let sadd1: u8 = i2c1.cr2.read().sadd1().into_bits();
+//! ```
+//!
+//! Fields can also be extracted from a raw value of the register in constant expressions, with
+//! the `const fn`s of the register module:
+//!
+//! ```ignore
+//! const SADD1: u8 = i2c1::cr2::sadd1_from_bits(0x0000_003c);
+//! ```
+//!
 //! ## `reset`
 //!
-//! The `ResetValue` trait provides `reset_value` which returns the value of the `CR2`
-//! register after a reset. This value can be used to modify the
-//! writable bitfields of the `CR2` register or reset it to its initial state.
-//! Usage looks like this:
+//! The `Resettable` trait provides `RESET_VALUE`, the value of the `CR2` register after a reset,
+//! and `reset_value` which returns it. This value can be used to modify the writable bitfields of
+//! the `CR2` register or reset it to its initial state. Usage looks like this:
+//!
+//! ```ignore
+//! i2c1.cr2.reset();
+//! ```
+//!
+//! As `RESET_VALUE` is a constant, it can also be used in constant expressions:
 //!
 //! ```ignore
-//! if i2c1.c2r.write().reset()
+//! use stm32f30x::generic::Resettable;
+//! const CR2_INIT: u32 = i2c1::cr2::CR2_SPEC::RESET_VALUE | 1;
 //! ```
 //!
+//! If the `resetMask` of a readable register doesn't cover all of its bits, the reset value only
+//! defines the bits of the mask, given by `RESET_MASK`. `reset` then reads the register and only
+//! restores those bits, writing the others back as they were. For other registers `RESET_MASK`
+//! is all ones, and `reset` writes `RESET_VALUE` without reading the register.
+//!
+//! Registers without their own `<size>`, `<access>`, `<resetValue>` or `<resetMask>` take the
+//! ones of their cluster, then of their peripheral, then of the device. A reset value or mask
+//! taken from a level with wider registers is truncated to the size of the register.
+//!
 //! ## `write`
 //!
 //! On the other hand, the `write` method writes some value to the register using a **single**,
@@ -343,6 +552,54 @@
 //! }
 //! ```
 //!
+//! Field writers like `SADD1_W` are aliases of the generic `WProxy` type, which carries the width
+//! and the bit offset of the field as const generic parameters. They are also available as the
+//! `WIDTH` and `OFFSET` associated constants, and the `generic::Mask` trait gives the mask of
+//! the field, e.g. `<SADD1_W as Mask<u32>>::MASK`.
+//!
+//! The elements of field arrays, e.g. `CH%s`, have a writer each, like `ch0()`, and the `unsafe`
+//! `ch(n)` picks the element at run time. It returns a `DynWProxy`, the `WProxy` of an offset only
+//! known at run time, with the same methods. `n` isn't checked, as with the `ch(n)` of readers.
+//!
+//! `WProxy` is `#[must_use]`: nothing is written to the field until a method of its writer is
+//! called, so `w.sadd0();` on its own is a warning. Chaining, as in `w.sadd0().set_bit()`, doesn't
+//! warn, and `let _ = w.sadd0();` silences the warning as usual.
+//!
+//! Writing raw bits to a field is `unsafe` if the field doesn't accept every value. If the SVD
+//! gives the range of values the field accepts in its `writeConstraint`, the writer also has a
+//! safe `checked_bits` method which returns `Err(OutOfRange)` for values outside of that range.
+//!
+//! Precomputed masks can be applied to the whole register with the `set_bits` and `clear_bits`
+//! methods of `W`, which OR and AND NOT the mask into the value being written. Like `bits`
+//! they are `unsafe` unless the register accepts every value.
+//!
+//! With the `--field_masks` flag, the module of each register has a `<FIELD>_MASK` constant for
+//! each field, with the bits of the field at its offset in the register, and its complement
+//! `<FIELD>_CLEAR`, to combine them by hand:
+//!
+//! ```ignore
+//! let cr2 = i2c1.cr2.read().bits();
+//! let sadd1 = (cr2 & i2c1::cr2::SADD1_MASK) >> 1;
+//! let without_sadd = cr2 & i2c1::cr2::SADD0_CLEAR & i2c1::cr2::SADD1_CLEAR;
+//! ```
+//!
+//! The value set up so far is returned by `current_bits`. For readable registers `preserve`
+//! starts over from a value read earlier, e.g. to write back some fields as they were:
+//!
+//! ```ignore
+//! let saved = i2c1.cr2.read();
+//! // ..
+//! i2c1.cr2.write(|w| w.preserve(&saved).start().set_bit());
+//! ```
+//!
+//! `copy_from` does the same with the value read from another register of the same size, e.g.
+//! to copy the configuration of a DMA channel to a sibling. It is `unsafe`, as the bits are
+//! copied as they are: the fields of both registers must have the same offsets and widths.
+//!
+//! ```ignore
+//! dma1.ch2.cr.write(|w| unsafe { w.copy_from(&dma1.ch1.cr.read()) }.en().set_bit());
+//! ```
+//!
 //! The `write` method takes a closure with signature `(&mut W) -> &mut W`. If the "identity
 //! closure", `|w| w`, is passed then the `write` method will set the `CR2` register to its reset
 //! value. Otherwise, the closure specifies how the reset value will be modified *before* it's
@@ -364,6 +621,16 @@
 //! // field; instead, `6 & 3` (i.e. `2`) will be written to the bitfield.
 //! ```
 //!
+//! For bits the SVD file doesn't describe as fields, e.g. while reverse-engineering a register,
+//! the reader and the writer have a raw `bits_range` method for any range of bits. It bypasses
+//! the fields, so the one of the writer is `unsafe` like `bits`. Empty or decreasing ranges, and
+//! ranges outside of the register, panic:
+//!
+//! ```ignore
+//! let revision = i2c1.cr2.read().bits_range(28..32);
+//! i2c1.cr2.modify(|_, w| unsafe { w.bits_range(28..32, 0b0101) });
+//! ```
+//!
 //! ## `modify`
 //!
 //! Finally, the `modify` method performs a **single** read-modify-write
@@ -383,6 +650,48 @@
 //! i2c1.cr2.modify(|r, w| w.stop().bit(!r.stop().bit()));
 //! ```
 //!
+//! As the writer of `modify` starts from the value read, single-bit fields can also be inverted
+//! with `toggle_bit`, which does the same as the last line. In `write` it inverts the reset value
+//! of the bit instead:
+//!
+//! ```ignore
+//! i2c1.cr2.modify(|_, w| w.stop().toggle_bit());
+//! ```
+//!
+//! The closure of `modify_ext` doesn't return the writer, which is handier when the fields to
+//! change depend on the value read:
+//!
+//! ```ignore
+//! i2c1.cr2.modify_ext(|r, w| {
+//!     if r.start().bit_is_clear() {
+//!         w.stop().set_bit();
+//!     }
+//! });
+//! ```
+//!
+//! `modify_if_changed` only writes the register back if the value computed by the closure
+//! differs from the one read, and returns whether it did. Like `modify` it is not atomic, and it
+//! compares all the bits of the register:
+//!
+//! ```ignore
+//! let written = i2c1.cr2.modify_if_changed(|_, w| w.start().set_bit());
+//! ```
+//!
+//! `modify_bits` is `modify` returning the value it wrote, e.g. to log it:
+//!
+//! ```ignore
+//! let cr2 = i2c1.cr2.modify_bits(|_, w| w.start().set_bit());
+//! ```
+//!
+//! `reset_masked` restores the bits of a mask to their reset value, leaving the others alone.
+//! It is a read-modify-write too, and `unsafe` because a mask that splits a field can make an
+//! invalid value of it:
+//!
+//! ```ignore
+//! // back to the reset values of the fields in bits 8..16
+//! unsafe { i2c1.cr2.reset_masked(0xff00) };
+//! ```
+//!
 //! # enumeratedValues
 //!
 //! If your SVD uses the `<enumeratedValues>` feature, then the API will be *extended* to provide
@@ -407,6 +716,35 @@
 //! }
 //! ```
 //!
+//! If the enumerated values don't cover every bit pattern of the field, `variant` returns a
+//! `Variant`, which holds either the enum or the raw reserved bits:
+//!
+//! ```ignore
+//! match gpioa.cfg.read().mode().variant() {
+//!     Variant::Val(gpioa::cfg::MODE_A::Fast) => { .. },
+//!     Variant::Val(gpioa::cfg::MODE_A::Slow) => { .. },
+//!     Variant::Res(bits) => { .. },
+//! }
+//! ```
+//!
+//! The same conversions are available from raw bits: enums covering every bit pattern implement
+//! `From` the field type, the others implement `TryFrom` it, returning the unknown bits as error.
+//! This is handy to get a value for the `variant` method of a writer:
+//!
+//! ```ignore
+//! let mode = gpioa::cfg::MODE_A::try_from(bits).unwrap_or(gpioa::cfg::MODE_A::Slow);
+//! gpioa.cfg.write(|w| w.mode().variant(mode));
+//! ```
+//!
+//! The enums also implement `Eq` and `Hash`, and their `variants` function iterates over all
+//! the variants, leaving out the reserved and `isDefault` values of the SVD:
+//!
+//! ```ignore
+//! for mode in gpioa::cfg::MODE_A::variants() {
+//!     ..
+//! }
+//! ```
+//!
 //! It also provides convenience methods to check for a specific variant without
 //! having to import the enum:
 //!
@@ -420,6 +758,10 @@
 //! }
 //! ```
 //!
+//! Single-bit readers all have `is_one` and `is_zero`, which check the bit, so the variants
+//! `One` and `Zero` of single-bit fields don't get methods of their own: `is_one` is true if the
+//! bit is 1 even if the field names 0 `One`. svd2rust warns about the fields where they differ.
+//!
 //! The original `bits` method is available as well:
 //!
 //! ```ignore
@@ -451,6 +793,56 @@
 //! gpioa.dir.write(|w| w.pin0().bit(true));
 //! ```
 //!
+//! `variant` is always safe, even when `bits` isn't. The enumerated values don't have to cover
+//! every bit pattern for `bits` to be safe either: a multi-bit field is only `unsafe` to write if
+//! its `writeConstraint` doesn't accept every value (or it has none), and enumerated values that
+//! cover every bit pattern make it safe anyway. A field with a few named modes and a
+//! `writeConstraint` range over the whole field gets both:
+//!
+//! ```ignore
+//! // enum MODE_A { Fast = 0, Slow = 1 }, writeConstraint 0..=3
+//! gpioa.cfg.write(|w| w.mode().variant(gpioa::cfg::MODE_A::Slow));
+//! gpioa.cfg.write(|w| w.mode().bits(3));
+//! ```
+//!
+//! A field can have different `enumeratedValues` for reading and writing, with a `usage` of
+//! `read` and `write`. The reader then uses the `_A` enum of the read values and the writer an
+//! `_AW` enum of the write values. With a single `enumeratedValues`, or a `usage` of
+//! `read-write`, both use the same `_A` enum:
+//!
+//! ```ignore
+//! // enum PIN0_A { Input, Output }, enum PIN0_AW { SetInput, SetOutput, SetAlternate }
+//! if gpioa.dir.read().pin0().variant() == gpioa::dir::PIN0_A::Input {
+//!     gpioa.dir.write(|w| w.pin0().variant(gpioa::dir::PIN0_AW::SetAlternate));
+//! }
+//! ```
+//!
+//! Fields with `<enumeratedValues derivedFrom="..">` share the enum of the values they derive
+//! from, instead of getting one of their own: their `_A` and `_R` types are aliases of the ones of
+//! the original field. The path names the values, `MODE_EV`, or a field of the same register,
+//! `A.MODE_EV`, of a register next to it, `CFG.A.MODE_EV`, or of any register given by its
+//! peripheral and clusters, `GPIOA.GRP.CFG.A.MODE_EV` (the peripheral can be left out for its
+//! own clusters). Chains of `derivedFrom` are followed, so all the fields end up with the enum of
+//! the values that are defined:
+//!
+//! ```ignore
+//! // gpiob::ctl::X_A = crate::gpioa::grp::cfg::A_A
+//! gpiob.ctl.write(|w| w.x().variant(gpioa::grp::cfg::A_A::On));
+//! ```
+//!
+//! Registers with `<enumeratedValues>` of their own, outside of the SVD schema, get a `_A` enum of
+//! the values of the whole register. Their reader has a `variant` method, like the ones of fields,
+//! and their writer a `write_variant` method writing all the bits of the register:
+//!
+//! ```ignore
+//! // enum KR_A { UNLOCK = 0x5555, RELOAD = 0xAAAA, START = 0xCCCC }
+//! iwdg.kr.write(|w| w.write_variant(iwdg::kr::KR_A::START));
+//! ```
+//!
+//! The SVD parser drops them, so they are read from the XML of the SVD file again, and added to
+//! the `register_enums` of the [Config] by [config_for]. Code rendering the
+//! [Device](svd::Device) of [load_from] itself has to do the same.
+//!
 //! # Interrupt API
 //!
 //! SVD files also describe the device interrupts. svd2rust generated crates expose an enumeration
@@ -469,6 +861,25 @@
 //! nvic.enable(Interrupt::TIM3);
 //! ```
 //!
+//! For RISC-V targets the `Interrupt` enum lists the external interrupts of the device and
+//! implements `bare_metal::Nr`, which is what the PLIC API of the `riscv` crate takes. With the
+//! "rt" feature the handlers are put in the `__EXTERNAL_INTERRUPTS` table that `riscv-rt`
+//! dispatches external interrupts from.
+//!
+//! ```ignore
+//! let plic = e310x::PLIC::ptr();
+//! let nr = e310x::Interrupt::UART0.nr();
+//! ```
+//!
+//! The `interrupt_name` and `interrupt_number` functions next to the enum map the numbers of
+//! the interrupts to their names in the SVD file and back, e.g. for logging or custom vector
+//! tables. Reserved numbers and unknown names give `None`:
+//!
+//! ```ignore
+//! assert_eq!(stm32f30x::interrupt_name(28), Some("TIM2"));
+//! assert_eq!(stm32f30x::interrupt_number("TIM2"), Some(28));
+//! ```
+//!
 //! ## the "rt" feature
 //!
 //! If the "rt" Cargo feature of the svd2rust generated crate is enabled, the crate will populate the
@@ -479,6 +890,62 @@
 //!
 //! [`interrupt`]: https://docs.rs/cortex-m-rt-macros/0.1/cortex_m_rt_macros/attr.interrupt.html
 //!
+//! ## the "mock" feature
+//!
+//! If the "mock" Cargo feature of the generated crate is enabled, registers are stored in plain
+//! `Cell`s instead of volatile cells, so code using them can be tested on the host. The register
+//! blocks have the same API, but must be created in host memory instead of taken from the
+//! peripherals:
+//!
+//! ```ignore
+//! let gpioa: stm32f30x::gpioa::RegisterBlock = unsafe { core::mem::zeroed() };
+//! gpioa.odr.write(|w| w.odr0().set_bit());
+//! assert!(gpioa.odr.read().odr0().bit_is_set());
+//! ```
+//!
+//! The feature must be declared in the `Cargo.toml` of the device crate (`mock = []`).
+//!
+//! ## the `--vector_table` flag
+//!
+//! The vector table of the "rt" feature is for `cortex-m-rt`, which provides the rest of the
+//! table, the `DefaultHandler` and the linker script. With the `--vector_table` flag, the
+//! "vector-table" feature of a Cortex-M device crate provides the interrupt vectors without it,
+//! for a minimal application with a linker script of its own: `__INTERRUPTS` in the
+//! `.vector_table.interrupts` section, and a `DefaultHandler` that spins. The linker script must
+//! `INCLUDE device.x`, whose `PROVIDE`s bind each interrupt to `DefaultHandler` unless the
+//! application defines a handler of the same name:
+//!
+//! ```ignore
+//! #[no_mangle]
+//! extern "C" fn TIM2() {
+//!     // ..
+//! }
+//! ```
+//!
+//! With the "rt" feature too, the crate only has the vectors of "rt", and `cortex-m-rt` its
+//! `DefaultHandler`, so the features don't conflict. The feature must be declared in the
+//! `Cargo.toml` of the device crate (`vector-table = []`). Other targets ignore the flag.
+//!
+//! ## the `--std_feature` flag
+//!
+//! With the `--std_feature` flag the generated crate is only `#![no_std]` when its "std" feature
+//! is disabled, so tests on the host can use the standard library. Making the feature enable
+//! "mock" gives those tests registers in host memory, and as the volatile cells are then unused
+//! `vcell` can be an optional dependency of the default features:
+//!
+//! ``` toml
+//! [dependencies.vcell]
+//! optional = true
+//! version = "0.1.2"
+//!
+//! [features]
+//! default = ["vcell"]
+//! mock = []
+//! std = ["mock"]
+//! ```
+//!
+//! Host tests then build with `--no-default-features --features std`.
+//!
 //! ## the `--nightly` flag
 //!
 //! The `--nightly` flag can be passed to `svd2rust` to enable features in the generated api that are only available to a nightly
@@ -499,13 +966,651 @@
 //! P1.p1out.clear(|w| unsafe { w.bits(!(1 << 2)) });
 //! P1.p1out.toggle(|w| unsafe { w.bits(1 << 4) });
 //! ```
+//!
+//! ## the `--strict` flag
+//!
+//! The `--strict` flag makes the validation of the SVD stricter, e.g. of the names, and turns
+//! inconsistencies that are tolerated otherwise into errors:
+//!
+//! - fields that end past the size of their register,
+//! - enumerated values of a field with the same value,
+//! - enumerated values that don't fit in the bits of their field,
+//! - address blocks of different peripherals that overlap, unless one is the
+//!   `alternatePeripheral` of the other.
+//!
+//! The error names the peripheral, cluster, register and field it was found in.
+//!
+//! Without `--strict` the enumerated values that don't fit in their field are left out of the
+//! generated enums with a warning, rather than being silently truncated by `variant`. The XML
+//! parser rejects them anyway, so this only makes a difference for YAML and JSON input.
+//!
+//! ## the `--bitband` flag
+//!
+//! On Cortex-M3/M4 devices the `--bitband` flag adds accessors for the safe single-bit fields of
+//! readable and writable registers of peripherals located in the SRAM or peripheral bit-band
+//! regions. They can be set or cleared atomically with a single store to the bit-band alias:
+//!
+//! ```ignore
+//! dp.GPIOA.odr.odr5().set_bit_atomic();
+//! dp.GPIOA.odr.odr5().clear_bit_atomic();
+//! ```
+//!
+//! If a register turns out to be outside of the bit-band regions (e.g. a derived peripheral at
+//! another address) the bit is changed by a read-modify-write inside a critical section instead.
+//!
+//! ## the `--critical_section` flag
+//!
+//! A `modify` of a register that interrupt handlers, or other cores, change too can lose their
+//! changes. The `--critical_section` flag generates `modify_cs`, which takes the token of a
+//! critical section of the [`critical-section`] crate to do the read-modify-write in it. The
+//! token proves that the caller is in the critical section, `modify_cs` doesn't enter one itself:
+//!
+//! ```ignore
+//! critical_section::with(|cs| {
+//!     dp.GPIOA.odr.modify_cs(&cs, |_, w| w.odr5().set_bit());
+//! });
+//! ```
+//!
+//! `modify_cs` is only compiled with the `critical-section` feature of the device crate, so
+//! `Cargo.toml` needs to declare the optional dependency. `modify` is unchanged.
+//!
+//! With the feature, `Peripherals::take` is guarded by `critical_section::with` too, instead of
+//! the `interrupt::free` of the crate of the target, so it is also available with `--target none`
+//! once the application provides an implementation of `critical-section`. `Peripherals::steal`
+//! stays unguarded.
+//!
+//! ```toml
+//! [dependencies]
+//! critical-section = { version = "1.0", optional = true }
+//! ```
+//!
+//! [`critical-section`]: https://crates.io/crates/critical-section
+//!
+//! ## the `--split_registers` flag
+//!
+//! Registers are `Send` but not `Sync`: every access goes through `&self`, so a peripheral only
+//! has exclusive access to its registers as long as it is not shared. The `--split_registers`
+//! flag adds a `split` method to the peripherals, which consumes the peripheral and moves its
+//! registers out as owned tokens, so that e.g. a driver can own a single register:
+//!
+//! ```ignore
+//! let mut parts = dp.TIMER0.split();
+//! parts.cr.reg().write(|w| w.en().set_bit());
+//! let cmp1 = parts.cmp[1].reg().read().bits();
+//! ```
+//!
+//! The tokens are `crate::Owned` registers which only lend the register through `&mut self`,
+//! `reg`. The registers of a cluster are in the `<CLUSTER>Parts` of its block, next to the block
+//! in the same module, which its own `parts` method returns:
+//!
+//! ```ignore
+//! // a cluster array `CH[%s]` with a register `CR`
+//! parts.ch[0].cr.reg().modify(|_, w| w.en().set_bit());
+//! ```
+//!
+//! Registers that share their address with others, and arrays of clusters which are
+//! `ArrayProxy`s, are not part of `Parts` and are still accessed through the register block.
+//!
+//! ## the `--reflect` flag
+//!
+//! The `--reflect` flag adds a `FIELDS` table to the module of each register, with the SVD
+//! name, offset and width of its fields and the names of their enumerated values, for tools
+//! like register viewers that need to describe registers at runtime. The values are the ones
+//! read, or the ones written for write-only fields and fields with only `<enumeratedValues>` for
+//! writing:
+//!
+//! ```ignore
+//! let bits = dp.TIMER0.cr.read().bits();
+//! for f in timer0::cr::FIELDS {
+//!     let value = (bits >> f.offset) & (u32::MAX >> (32 - f.width));
+//!     rprintln!("{} = {} {:?}", f.name, value, f.values);
+//! }
+//! ```
+//!
+//! The tables, and the `FieldInfo` struct they are made of, are only compiled with the
+//! `reflect` feature of the device crate, so they don't take space in other builds.
+//!
+//! ## the `--hal_scaffold` flag
+//!
+//! The `--hal_scaffold` flag writes `hal_scaffold.rs` next to the generated crate, with an empty
+//! driver for each peripheral whose kind it recognizes from its group or name: GPIO ports,
+//! serial ports, SPI and I2C controllers, timers, watchdogs and ADCs. Each driver owns its
+//! peripheral and has stubs of the methods behind the `embedded-hal` traits usually implemented
+//! for that kind of peripheral, all `todo!()`:
+//!
+//! ```ignore
+//! /// Driver of `USART1` (scaffolding)
+//! pub struct Usart1 {
+//!     regs: crate::pac::USART1,
+//! }
+//!
+//! impl Usart1 {
+//!     pub fn new(regs: crate::pac::USART1) -> Self { .. }
+//!     pub fn free(self) -> crate::pac::USART1 { .. }
+//!     pub fn write(&mut self, word: u8) { todo!() }
+//!     pub fn read(&mut self) -> Option<u8> { todo!() }
+//! }
+//! ```
+//!
+//! The file is not part of the device crate: it is meant to be copied into a HAL crate which
+//! has the device crate available as `pac`, and then filled in, so it is always formatted with
+//! `rustfmt`, even without `--format`. The kinds are only guessed, so the output is off by
+//! default.
+//!
+//! ## the `--field_setters` flag
+//!
+//! The `--field_setters` flag adds a `<field>_set` method to the `W` writer of each register,
+//! which writes raw bits to the field like `<field>().bits(value)`, or `bit` for single-bit
+//! fields. It takes the writer by `&mut` and returns it, so code setting fields from a table or
+//! a macro doesn't have to go through the consuming field writer:
+//!
+//! ```ignore
+//! i2c1.cr2.write(|w| w.start_set(true).autoend_set(false));
+//! ```
+//!
+//! Like `bits`, the setters of fields that don't accept every value are `unsafe`. A setter
+//! whose name is taken by the writer of another field is left out.
+//!
+//! ## the `--option_setters` flag
+//!
+//! The `--option_setters` flag (or `--option-setters`) adds a `<field>_opt` method to the `W`
+//! writer of each register for the fields with enumerated values, which takes an `Option` of the
+//! enum and writes the variant if it is `Some`, to set up a writer from optional settings without
+//! branching around each field:
+//!
+//! ```ignore
+//! usart1.cr1.modify(|_, w| w.m_opt(config.word_length).pce_opt(config.parity));
+//! ```
+//!
+//! `None` leaves the field as it is in the writer, which is what the register holds in `modify`,
+//! but is the reset value in `write`, since `write` starts from the reset value of the register.
+//! As with `--field_setters`, a method whose name is taken by the writer of another field is
+//! left out.
+//!
+//! ## the `--spin_helpers` flag
+//!
+//! The `--spin_helpers` flag (or `--spin-helpers`) adds `<field>_wait_set` and
+//! `<field>_wait_clear` methods to the registers of readable single-bit fields, which read the
+//! register until the field is set or cleared, to wait for status flags:
+//!
+//! ```ignore
+//! rcc.cr.hserdy_wait_set();
+//! if rcc.cr.pllrdy_wait_set_timeout(10_000).is_err() {
+//!     // the PLL did not lock
+//! }
+//! ```
+//!
+//! These are busy waits which never return if the hardware never changes the field, so the
+//! `_timeout` variants, giving up with `Err(generic::Timeout)` after `max_reads` reads, are
+//! usually the better choice. Fields of registers or fields with a `readAction` are left out,
+//! since reading them over and over has side effects.
+//!
+//! ## the `--str_names` flag
+//!
+//! The `--str_names` flag lets tools like command line interfaces convert enumerated values to
+//! and from the names of the SVD, rather than the Rust identifiers of the variants. `as_str`
+//! returns the name and the `TryFrom<&str>` implementation looks it up, ignoring the case and
+//! the surrounding whitespace, or fails with `UnknownName`:
+//!
+//! ```ignore
+//! use core::convert::TryFrom;
+//! assert_eq!(timer0::cr::DIV_A::DIV4.as_str(), "Div4");
+//! let div = timer0::cr::DIV_A::try_from(" div4 ")?;
+//! ```
+//!
+//! Both are only compiled with the `str-names` feature of the device crate.
+//!
+//! ## the `--impl_debug` flag
+//!
+//! The `--impl_debug` flag implements `core::fmt::Debug` for the `R` reader of every register.
+//! It prints each readable field with its enumerated variant, if there is one, or its raw value:
+//!
+//! ```ignore
+//! rprintln!("{:?}", dp.TIMER0.cr.read()); // CR { en: ENABLED, mode: Res(3), psc: 0 }
+//! ```
+//!
+//! Without this flag only the raw bits of the register can be printed with `{:?}`, as the
+//! generic `R` prints them in hex.
+//!
+//! ## the `--impl_default` flag
+//!
+//! Writers are normally only created by `write` and friends, inside of the closure. The
+//! `--impl_default` flag implements `Default` for the `W` writer of every register with a reset
+//! value, holding that value, so a value can be set up beforehand, stored, and written later
+//! with `write_value`:
+//!
+//! ```ignore
+//! let mut w = timer0::cr::W::default();
+//! w.en().set_bit().mode().cont();
+//! // ..
+//! dp.TIMER0.cr.write_value(w);
+//! ```
+//!
+//! ## the `--impl_ord` flag
+//!
+//! Enumerated values are often ordered quantities, like the dividers of a prescaler. The
+//! `--impl_ord` flag implements `PartialOrd` and `Ord` for the enums of enumerated values,
+//! comparing the values the SVD gives to the variants rather than their order in the file:
+//!
+//! ```ignore
+//! assert!(timer0::cr::DIV_A::DIV2 < timer0::cr::DIV_A::DIV4);
+//! ```
+//!
+//! ## the `--ufmt` flag
+//!
+//! The `--ufmt` flag implements `ufmt::uDebug` for the `R` reader of every register and for the
+//! enumerated values, printing the same fields as `--impl_debug`. The impls are only compiled with
+//! the `ufmt` feature of the device crate, so `Cargo.toml` needs to declare it along with the
+//! optional dependency:
+//!
+//! ```toml
+//! [dependencies]
+//! ufmt = { version = "0.2", optional = true }
+//! ```
+//!
+//! ## the `--addresses` flag
+//!
+//! The `--addresses` flag generates an `addresses` module with the base address of every
+//! peripheral, sorted by address, e.g. for linker scripts or debuggers, and a module per
+//! peripheral with the addresses of its registers, including the offsets of their clusters.
+//! Registers in register or cluster arrays get a `const fn` taking an index per array instead:
+//!
+//! ```ignore
+//! pub mod addresses {
+//!     pub const GPIOA: usize = 0x4800_0000;
+//!     pub mod gpioa {
+//!         pub const MODER: usize = 0x4800_0000;
+//!         ..
+//!     }
+//!     pub const DMA1: usize = 0x4002_0000;
+//!     pub mod dma1 {
+//!         pub const fn ch_cr(ch: usize) -> usize {
+//!             0x4002_0008 + ch * 0x14
+//!         }
+//!         ..
+//!     }
+//! }
+//! ```
+//!
+//! These are the addresses that `as_ptr()` of the registers returns. They are not associated
+//! consts of the register types, because derived peripherals share these types.
+//!
+//! ## the `--ptr_consts` flag
+//!
+//! Each peripheral has a `PTR` associated const and a `ptr()` function with the pointer to its
+//! register block. The `--ptr_consts` flag (or `--ptr-consts`) also generates a free `const`
+//! for each of them, next to the peripheral, for code written against plain consts, like
+//! `const` and `static` initializers and macros:
+//!
+//! ```ignore
+//! pub const GPIOA_PTR: *const gpioa::RegisterBlock = GPIOA::PTR;
+//! ```
+//!
+//! It is defined as `PTR`, so it always matches `ptr()`. Like any raw pointer, dereferencing it
+//! is `unsafe`: the references it gives can race with the owned peripheral, as with `steal`.
+//!
+//! ## the `--address_blocks` flag
+//!
+//! The `<addressBlock>`s of a peripheral describe its address space, which is what the regions of
+//! an MPU protect. The `--address_blocks` flag (or `--address-blocks`) generates, next to each
+//! peripheral with address blocks, the size of its address space, from its base address to the
+//! end of its last block, and the list of its blocks with their absolute base address, size,
+//! usage and protection:
+//!
+//! ```ignore
+//! pub const GPIOA_SIZE: usize = 0x0400;
+//! pub const GPIOA_ADDRESS_BLOCKS: [crate::AddressBlock; 1] = [crate::AddressBlock {
+//!     base: 0x4001_0800,
+//!     size: 0x0400,
+//!     usage: crate::AddressBlockUsage::Registers,
+//!     protection: None,
+//! }];
+//! ```
+//!
+//! `region()` returns the `(base, size)` of a block, to configure an MPU:
+//!
+//! ```ignore
+//! for (base, size) in pac::GPIOA_ADDRESS_BLOCKS.iter().map(|b| b.region()) {
+//!     // ..
+//! }
+//! ```
+//!
+//! Derived peripherals without address blocks of their own have the ones of the original. Sizes
+//! are in bytes, assuming an `addressUnitBits` of 8.
+//!
+//! ## the `--module_per_peripheral` flag
+//!
+//! The output for big devices is a single file of several megabytes, that editors and compilers
+//! struggle with. The `--module_per_peripheral` flag writes the module of each peripheral to a
+//! file of its own, e.g. `gpioa.rs`, and `generic.rs` as with `--generic_mod`, declared in
+//! `lib.rs` as `pub mod gpioa;`. All these files belong in `src`, and the generated crate is
+//! the same as without the flag.
+//!
+//! ## the `--check_layout` flag
+//!
+//! Registers are placed in the `#[repr(C)]` register blocks at their offset by `_reservedN`
+//! padding fields, but a register that isn't aligned to its size, e.g. a 32-bit register at offset
+//! 2, gets moved by the compiler. The `--check_layout` flag adds a check that fails to compile
+//! when the size of a register block doesn't match the one given by the SVD, naming the block and
+//! both sizes:
+//!
+//! ```text
+//! error[E0308]: mismatched types
+//!     const _: crate::BlockSize<CH, 0x06> = crate::BlockSize::<CH, { core::mem::size_of::<CH>() }>::SIZE;
+//!                                           ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^ expected `6`, found `8`
+//! ```
+//!
+//! The size of a block in the SVD is the end of its last register, rounded up to the alignment of
+//! its largest register as the compiler does, e.g. 8 bytes for a cluster with a 32-bit register at
+//! 0x0 and a 16-bit one at 0x4. The elements of a cluster array must also fit in its
+//! `dimIncrement`, so the check above fails if that cluster had a `dimIncrement` of 6.
+//!
+//! The padding fields, including the one up to the alignment at the end of a block, are also
+//! marked `#[doc(hidden)]`, so that they don't clutter the documentation of private items.
+//!
+//! ## the `--config_struct` flag
+//!
+//! Registers with many fields need long chains of field writers to be configured. The
+//! `--config_struct` flag generates a `Config` struct in the module of each writable register with
+//! a reset value, with a public member per field, and a `configure` method writing all of them:
+//!
+//! ```ignore
+//! use stm32f30x::tim6::cr1::{Config, ARPE_A, OPM_A};
+//!
+//! tim6.cr1.configure(Config {
+//!     arpe: ARPE_A::ENABLED,
+//!     opm: OPM_A::ENABLED,
+//!     ..Default::default()
+//! });
+//! ```
+//!
+//! Members have the enumerated values of their field if one of them is its reset value, and the
+//! raw type of the field otherwise. `Config::default()` holds the reset values of the fields.
+//! `configure` is `unsafe` if the raw values of some field aren't all valid, like `bits`.
+//!
+//! ## the `--flags` flag
+//!
+//! Registers of status or interrupt flags have many single-bit fields, often set together. The
+//! `--flags` flag generates a `Flags` struct in the module of each writable register with a
+//! constant per single-bit field, combined with `|`, and a `set_flags` method of the writer
+//! setting them all:
+//!
+//! ```ignore
+//! use stm32f30x::tim6::dier::Flags;
+//!
+//! tim6.dier.modify(|_, w| w.set_flags(Flags::UIE | Flags::UDE));
+//! ```
+//!
+//! Only the single-bit fields that can be written safely are included, wider fields have no use
+//! for this. `Flags::bits` returns the combined raw value.
+//!
+//! ## the `--secure_feature` flag
+//!
+//! The `protection` of registers in the SVD, i.e. whether they need secure, non-secure or
+//! privileged permission on Armv8-M devices, is noted in their documentation. With the
+//! `--secure_feature` flag the registers that need secure permission are only accessible with the
+//! `secure` feature of the device crate, for firmware running in the secure state:
+//!
+//! ```toml
+//! [features]
+//! secure = []
+//! ```
+//!
+//! Without the feature their fields in the register blocks are replaced by padding, which keeps
+//! the offsets of the other registers.
+//!
+//! ## the `--shared_enums` flag
+//!
+//! Fields of the same register often have the same enumerated values, e.g. the clock switch and
+//! the clock switch status. With the `--shared_enums` flag the enum of the first such field is
+//! used for the others, which get type aliases instead of enums of their own:
+//!
+//! ```ignore
+//! if rcc.cfgr.read().sws() == rcc::cfgr::SW_A::PLL {
+//!     ..
+//! }
+//! ```
+//!
+//! Fields have the same enumerated values if their width and the names, values and descriptions
+//! of all variants match. The `--shared_enums_ignore_doc` flag also shares enums whose variants
+//! only differ in their descriptions.
+//!
+//! ## the `--defmt` flag
+//!
+//! The `--defmt` flag implements `defmt::Format` for the enumerated values of fields, printing
+//! the name of the variant. The impls are only compiled with the `defmt` feature of the device
+//! crate, so `Cargo.toml` needs to declare it along with the optional dependency:
+//!
+//! ```toml
+//! [dependencies]
+//! defmt = { version = "0.3", optional = true }
+//! ```
+//!
+//! ## the `--serde` flag
+//!
+//! The `--serde` flag derives `serde::Serialize` and `serde::Deserialize` for the enumerated
+//! values of fields, e.g. to save the state of a device for host simulations. The variants are
+//! (de)serialized by the names of the generated variants, not by the names of the SVD file: an
+//! SVD value `2MHz` is `_2MHZ`. These names are sanitized and unique within their enum, so any
+//! serialized value round-trips, while the names of the SVD file may not be valid identifiers.
+//! Bit patterns of a field without a variant have nothing to serialize but their raw bits.
+//!
+//! The derives are only compiled with the `serde` feature of the device crate, which an optional
+//! dependency without `std` provides. svd2rust writes it to `serde.toml` next to `lib.rs`, to
+//! be added to the `Cargo.toml` of the device crate:
+//!
+//! ```toml
+//! [dependencies.serde]
+//! version = "1"
+//! default-features = false
+//! features = ["derive"]
+//! optional = true
+//! ```
+//!
+//! ## the `--feature_peripheral` flag
+//!
+//! Big devices have hundreds of peripherals, most of which are unused by any given application.
+//! The `--feature_peripheral` flag puts each peripheral, its module and its field of
+//! `Peripherals` behind a Cargo feature named after the peripheral in lowercase, so only the
+//! enabled ones get compiled. The features are written to `features.toml`, to be copied into the
+//! `Cargo.toml` of the device crate:
+//!
+//! ```toml
+//! [features]
+//! gpioa = []
+//! gpiob = ["gpioa"]
+//! ```
+//!
+//! Peripherals derived from another one use its register block, so their feature enables the
+//! feature of the original peripheral. The `Interrupt` enum is not gated.
+//!
+//! ## the `--rename_peripheral` option
+//!
+//! `--rename_peripheral FROM=TO`, which can be repeated, renames the peripheral `FROM` of the SVD
+//! file to `TO` before generating the code, so its struct, module, field of `Peripherals` and
+//! the peripherals `derivedFrom` it all use the new name, as do the `derivedFrom` paths starting
+//! with it, like `FROM.REGISTER.FIELD.VALUES`. `--rename-peripheral` is accepted as well. It is an error if `FROM` is not a peripheral of the device, or if `TO` gives the same
+//! Rust name as another peripheral. Interrupts keep their names.
+//!
+//! ```text
+//! svd2rust -i device.svd --rename_peripheral GPIO_A=GPIOA --rename_peripheral USART_1=USART1
+//! ```
+//!
+//! ## the `--signed_field` option
+//!
+//! SVD files have no way to tell that a field holds a signed value, like a calibration offset.
+//! `--signed_field PERIPHERAL.REGISTER.FIELD`, which can be repeated, makes the reader of the
+//! field sign-extend its bits, so its `bits` method and its `_from_bits` function return an
+//! `i8`, `i16`, `i32` or `i64` instead of the unsigned type. `--signed-field` is accepted as well.
+//!
+//! ```text
+//! svd2rust -i device.svd --signed_field ADC1.CALFACT.OFFSET --signed_field TEMP.T.VAL
+//! ```
+//!
+//! The names are the ones of the SVD file, with `%s` for arrays, and the ones given to
+//! `--rename_peripheral` for renamed peripherals. The register may be in a cluster. Writers keep
+//! taking unsigned bits. It is an error if there is no such field, or if it has a single bit or
+//! `<enumeratedValues>` for reading.
+//!
+//! ## the `--inline` option
+//!
+//! The methods of registers, readers and writers, like `read`, `write`, `modify`, the field
+//! accessors and the methods of field writers, are `#[inline(always)]` so that each access
+//! compiles down to a few instructions. On devices with little flash this code can add up: the
+//! `--inline` option picks the attribute they get, `always` (the default), `auto` for a plain
+//! `#[inline]` that leaves the choice to the compiler, or `never` for no attribute at all.
+//! The functions and methods of other options get it as well: the `<field>_from_bits`
+//! functions, `configure` and `Config::default` of `--config_struct`, the methods of the `Flags`
+//! of `--flags` and the `<array>_get` accessors of index enums.
+//!
+//! ## the `--max_field_width` option
+//!
+//! Fields spanning most of a register, like data or counter registers, seldom gain anything from
+//! their `<enumeratedValues>`. With `--max_field_width WIDTH`, or `--max-field-width`, the
+//! enumerated values of fields wider than `WIDTH` bits are ignored: those fields only get the
+//! raw `bits` accessors, without an enum, `variant` or `is_*` methods. There is no limit by
+//! default.
+//!
+//! ```text
+//! svd2rust -i device.svd --max_field_width 16
+//! ```
+//!
+//! ## the `--report` option
+//!
+//! To debug how the items of an SVD file map to the generated code, `--report FILE` writes a
+//! JSON report of the generation to `FILE`, without changing the code: the peripherals with their
+//! interrupts, registers and fields, the counts of them, the items which were skipped with the
+//! reason why, e.g. the Cortex-M core peripherals or peripherals without registers, and the
+//! warnings, including the ones of items renamed or merged. The warnings are in the report even
+//! when `--log` (or `--log-level`) leaves them out of the log.
+//!
+//! ```text
+//! svd2rust -i device.svd --report report.json
+//! ```
+//!
+//! ```text
+//! {
+//!   "device": "STM32F30x",
+//!   "counts": { "peripherals": 52, "registers": 1042, "fields": 5873, "skipped": 12, ... },
+//!   "peripherals": [{ "name": "GPIOA", "base_address": 1207959552, "registers": [...], ... }],
+//!   "skipped": [{ "item": "NVIC", "reason": "core peripheral of the `cortex-m` crate" }, ...],
+//!   "warnings": [...]
+//! }
+//! ```
+//!
+//! The skipped items are recorded while generating the code, and are also in the `skipped` of the
+//! [Generation] of [generate()], for [report::render] to write the same report.
+//!
+//! # Merging SVD files
+//!
+//! Some SoCs describe their core peripherals and their vendor peripherals in different SVD files.
+//! Given `-i` more than once, svd2rust merges the files, which must all be in the same format,
+//! into a single device crate with the peripherals of all of them and one interrupt table.
+//! [load_merged] does the same for tools using svd2rust as a library.
+//!
+//! ```text
+//! svd2rust -i core.svd -i vendor.svd
+//! ```
+//!
+//! The files are merged in the order they are given, and the first one takes precedence:
+//!
+//! - the name, version, description, width and other properties of the device come from the
+//!   first file, and the `<cpu>` from the first file that has one. Files with different `<cpu>`
+//!   names can't be merged;
+//! - the default register properties (`<size>`, `<access>`, `<resetValue>`...) of each file
+//!   apply to its own peripherals only;
+//! - the peripherals of the later files follow the ones of the first. One named like a
+//!   peripheral of an earlier file is left out if it is identical, which lets the files share
+//!   peripherals, and is an error otherwise. `derivedFrom` can refer to a peripheral of another
+//!   file;
+//! - interrupts of different files must have both the same name and number, or neither;
+//! - address blocks of peripherals of different files must not overlap, unless the peripherals
+//!   are the `alternatePeripheral` of one another.
+//!
+//! The renames of `--rename_peripheral` and the checks of `--strict` apply to the merged device.
+//!
+//! # Patching SVD files
+//!
+//! Vendor SVD files often have missing fields, wrong sizes or wrong accesses. Rather than editing
+//! them, `--patch FILE` applies the YAML patches of `FILE` to the device once it is loaded, and
+//! merged if several files are given, before the renames of `--rename_peripheral`, the checks of
+//! `--strict` and the code generation.
+//!
+//! ```text
+//! svd2rust -i vendor.svd --patch patches.yaml
+//! ```
+//!
+//! The file is a list of patches, applied in order. Each one edits a register, named by its
+//! `register` path of SVD names, `PERIPHERAL.REGISTER` or `PERIPHERAL.CLUSTER.REGISTER` with
+//! as many clusters as it is nested in, with `%s` for arrays:
+//!
+//! ```yaml
+//! - register: TIMER0.CR
+//!   # sets the given register properties, keeps the others
+//!   properties:
+//!     size: 16
+//!     access: read-write
+//!     resetValue: 0x0100
+//!   # removes fields
+//!   deleteFields: [RESERVED1, OLD]
+//!   # replaces properties of fields, `name` renames them
+//!   modifyFields:
+//!     EN:
+//!       access: read-only
+//!       bitWidth: 2
+//!   # appends fields
+//!   addFields:
+//!     - name: PSC
+//!       description: Prescaler
+//!       bitRange: "[15:8]"
+//!       access: read-write
+//! - register: TIMER0.CH%s.CCR
+//!   properties:
+//!     resetValue: 0
+//! ```
+//!
+//! `properties` takes `size`, `access`, `protection`, `resetValue` and `resetMask`. The fields
+//! of `addFields` and the changes of `modifyFields` are written like the fields of SVD files in
+//! YAML, with `name`, `description`, the bits as `bitOffset` and `bitWidth`, `bitRange` or `lsb`
+//! and `msb`, `access`, `modifiedWriteValues`, `writeConstraint`, `readAction`,
+//! `enumeratedValues`, `derivedFrom` and the `dim` keys of arrays. The keys of a change replace
+//! the ones of the field, and changing some of its bits keeps the others.
+//!
+//! The edits of a patch are made in the order above. It is an error if a patch refers to a
+//! peripheral, cluster, register or field that doesn't exist, adds a field or renames one to
+//! a name the register already has, or has an unknown key or an invalid value. The errors give
+//! the number of the patch, counting from 1.
+//!
+//! # Vendor extensions
+//!
+//! The SVD parser skips the `<vendorExtensions>` of SVD files. Tools using svd2rust as a library
+//! can still turn them into docs with [generate_with], whose callback gets each of them as a
+//! [vendor::VendorExtension]: the names of the peripheral, clusters, register and field the
+//! element is in, and its raw XML. The text it returns, if any, is appended to the docs of that
+//! item, and reformatted like the descriptions of the SVD file, with `\n` for line breaks.
+//! [load_with] does the same for the loaded [Device](svd::Device), and [vendor::extensions] only
+//! lists the elements.
+//!
+//! ```ignore
+//! let generation = svd2rust::generate_with(&svd, &config, |extension| {
+//!     // <vendorExtensions><resetDomain>VDD</resetDomain></vendorExtensions>
+//!     let domain = extension.xml.split("<resetDomain>").nth(1)?.split('<').next()?;
+//!     Some(format!("Reset domain: {}", domain))
+//! })?;
+//! ```
 #![recursion_limit = "128"]
 
 use quote::quote;
 use svd_parser::svd;
 
 pub mod generate;
+mod merge;
+mod names;
+mod patch;
+pub mod report;
+mod strict;
 pub mod util;
+pub mod vendor;
 
 pub use crate::util::{Config, Target};
 
@@ -513,6 +1618,8 @@ pub use crate::util::{Config, Target};
 pub struct Generation {
     pub lib_rs: String,
     pub device_specific: Option<DeviceSpecific>,
+    /// Items of the SVD file no code was generated for
+    pub skipped: Vec<generate::Skip>,
 }
 
 #[non_exhaustive]
@@ -533,12 +1640,25 @@ pub enum SvdError {
 
 /// Generates rust code for the specified svd content.
 pub fn generate(input: &str, config: &Config) -> Result<Generation> {
+    generate_with(input, config, |_| None)
+}
+
+/// Generates rust code for the specified svd content like [generate()], calling `transform` on
+/// each `<vendorExtensions>` of the SVD file. The text it returns is appended to the docs of the
+/// peripheral, cluster, register or field containing the extension, and ignored for the ones of
+/// the device.
+pub fn generate_with<F>(input: &str, config: &Config, transform: F) -> Result<Generation>
+where
+    F: FnMut(&vendor::VendorExtension) -> Option<String>,
+{
     use std::fmt::Write;
 
-    let device = load_from(input, config)?;
+    let device = load_with(input, config, transform)?;
+    let config = config_for(config, &device, &[input])?;
     let mut device_x = String::new();
-    let items =
-        generate::device::render(&device, config, &mut device_x).or(Err(SvdError::Render))?;
+    let mut skipped = Vec::new();
+    let items = generate::device::render(&device, &config, &mut device_x, &mut skipped)
+        .or(Err(SvdError::Render))?;
 
     let mut lib_rs = String::new();
     writeln!(
@@ -562,15 +1682,82 @@ pub fn generate(input: &str, config: &Config) -> Result<Generation> {
     Ok(Generation {
         lib_rs,
         device_specific,
+        skipped,
     })
 }
 
+/// Returns `config` completed with what it leaves to the SVD files `inputs` of `device`: the
+/// target, from the `<cpu>` of the device with `target_from_cpu`, and the register-level
+/// `<enumeratedValues>`, which the [Device](svd::Device) has no place for, added to its
+/// `register_enums` with their peripherals renamed like the ones of the device.
+pub fn config_for(config: &Config, device: &svd::Device, inputs: &[&str]) -> Result<Config> {
+    let mut config = config.clone();
+    if config.target_from_cpu {
+        if let Some(target) = device.cpu.as_ref().and_then(Target::from_cpu) {
+            log::info!("Using the target {:?} of the <cpu> of the SVD file", target);
+            config.target = target;
+        }
+    }
+    // YAML and JSON files are serialized devices
+    if config.source_type != util::SourceType::Xml {
+        return Ok(config);
+    }
+    for input in inputs {
+        for (peripheral, register, values) in vendor::register_enums(input)? {
+            let peripheral = config
+                .rename_peripherals
+                .iter()
+                .find(|(from, _)| *from == peripheral)
+                .map_or(peripheral, |(_, to)| to.clone());
+            config.register_enums.push((peripheral, register, values));
+        }
+    }
+    Ok(config)
+}
+
 /// Load a [Device] from a string slice with given [config](crate::util::Config).
 pub fn load_from(input: &str, config: &crate::util::Config) -> Result<svd::Device> {
+    load_with(input, config, |_| None)
+}
+
+/// Load a [Device](svd::Device) like [load_from], appending the text `transform` returns for each
+/// `<vendorExtensions>` of the SVD file to the description of the item containing it.
+pub fn load_with<F>(input: &str, config: &crate::util::Config, transform: F) -> Result<svd::Device>
+where
+    F: FnMut(&vendor::VendorExtension) -> Option<String>,
+{
+    let device = parse(input, config, transform)?;
+    fix_up(device, config)
+}
+
+/// Load a [Device](svd::Device) like [load_from], merging the devices of several SVD files
+/// `inputs`, in the same format, into one.
+///
+/// The first file gives the properties of the device and the peripherals of the others are
+/// appended, see [the docs](crate#merging-svd-files) for how conflicts are handled.
+pub fn load_merged(inputs: &[&str], config: &crate::util::Config) -> Result<svd::Device> {
+    if let [input] = inputs {
+        return load_from(input, config);
+    }
+    let devices = inputs
+        .iter()
+        .enumerate()
+        .map(|(i, input)| {
+            parse(input, config, |_| None).with_context(|| format!("In SVD file {}", i + 1))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    fix_up(merge::merge(devices)?, config)
+}
+
+/// Parses the SVD file `input` and adds the docs of its vendor extensions
+fn parse<F>(input: &str, config: &crate::util::Config, mut transform: F) -> Result<svd::Device>
+where
+    F: FnMut(&vendor::VendorExtension) -> Option<String>,
+{
     use self::util::SourceType;
     use svd_parser::ValidateLevel;
 
-    Ok(match config.source_type {
+    let mut device = match config.source_type {
         SourceType::Xml => {
             let mut parser_config = svd_parser::Config::default();
             parser_config.validate_level = if config.strict {
@@ -586,7 +1773,42 @@ pub fn load_from(input: &str, config: &crate::util::Config) -> Result<svd::Devic
             .with_context(|| "Error parsing SVD YAML file".to_string())?,
         SourceType::Json => serde_json::from_str(input)
             .with_context(|| "Error parsing SVD JSON file".to_string())?,
-    })
+    };
+    // YAML and JSON files have no vendor extensions
+    if config.source_type == SourceType::Xml {
+        for extension in vendor::extensions(input)? {
+            // the device has no docs of its own in the generated crate
+            let doc = transform(&extension).filter(|_| !extension.path.is_empty());
+            if let Some(doc) = doc {
+                if !vendor::append_doc(&mut device, &extension.path, &doc) {
+                    log::warn!(
+                        "Can't document the vendor extension of `{}`",
+                        extension.path.join(".")
+                    );
+                }
+            }
+        }
+    }
+    Ok(device)
+}
+
+/// Applies the renames and fixups of `config` to a parsed device
+fn fix_up(mut device: svd::Device, config: &crate::util::Config) -> Result<svd::Device> {
+    if let Some(path) = &config.patch {
+        let patches = std::fs::read_to_string(path)
+            .with_context(|| format!("Error reading patch file {}", path.display()))?;
+        patch::apply(&mut device, &patches)
+            .with_context(|| format!("Error applying patch file {}", path.display()))?;
+    }
+    names::rename_peripherals(&mut device, &config.rename_peripherals)?;
+    util::check_signed_fields(&device, &config.signed_fields)?;
+    names::disambiguate(&mut device, config.ignore_groups);
+    if config.strict {
+        strict::check(&device).with_context(|| "Error checking SVD file".to_string())?;
+    } else {
+        strict::drop_oversized_values(&mut device);
+    }
+    Ok(device)
 }
 
 /// Assigns a handler to an interrupt
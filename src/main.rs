@@ -1,18 +1,19 @@
 #![recursion_limit = "128"]
 
-use log::{error, info};
+use log::{error, info, warn};
 use std::path::{Path, PathBuf};
 
 use std::fs::File;
 use std::io::Write;
 use std::process;
+use std::sync::Mutex;
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use clap::{App, Arg};
 
 use svd2rust::{
-    generate, load_from,
-    util::{build_rs, Config, SourceType, Target},
+    config_for, generate, load_merged,
+    util::{build_rs, serde_manifest, Config, Inline, SourceType, Target},
 };
 
 fn run() -> Result<()> {
@@ -23,9 +24,11 @@ fn run() -> Result<()> {
         .about("Generate a Rust API from SVD files")
         .arg(
             Arg::with_name("input")
-                .help("Input SVD file")
+                .help("Input SVD file, repeated to merge several of them")
                 .short("i")
                 .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
                 .value_name("FILE"),
         )
         .arg(
@@ -47,9 +50,25 @@ fn run() -> Result<()> {
         .arg(
             Arg::with_name("target")
                 .long("target")
-                .help("Target architecture")
+                .help("Target architecture, by default guessed from the <cpu> of the SVD file or cortex-m")
                 .takes_value(true)
-                .value_name("ARCH"),
+                .value_name("ARCH")
+                .validator(|s| Target::parse(&s).map(drop).map_err(|e| e.to_string())),
+        )
+        .arg(
+            Arg::with_name("inline")
+                .long("inline")
+                .help("Inline attribute of the register methods: always (default), auto or never")
+                .takes_value(true)
+                .value_name("MODE"),
+        )
+        .arg(
+            Arg::with_name("max_field_width")
+                .long("max_field_width")
+                .alias("max-field-width")
+                .help("Only generate raw `bits` accessors for fields wider than WIDTH bits")
+                .takes_value(true)
+                .value_name("WIDTH"),
         )
         .arg(
             Arg::with_name("nightly_features")
@@ -58,7 +77,7 @@ fn run() -> Result<()> {
         )
         .arg(
             Arg::with_name("const_generic").long("const_generic").help(
-                "Use const generics to generate writers for same fields with different offsets",
+                "Use const generics to generate `ArrayProxy` for cluster arrays with gaps between elements",
             ),
         )
         .arg(
@@ -81,20 +100,210 @@ fn run() -> Result<()> {
                 .short("m")
                 .help("Create mod.rs instead of lib.rs, without inner attributes"),
         )
+        .arg(
+            Arg::with_name("format")
+                .long("format")
+                .help("Format the generated files with rustfmt"),
+        )
         .arg(
             Arg::with_name("strict")
                 .long("strict")
                 .short("s")
-                .help("Make advanced checks due to parsing SVD"),
+                .help("Make advanced checks due to parsing SVD, and reject inconsistent SVDs"),
+        )
+        .arg(Arg::with_name("bitband").long("bitband").help(
+            "Generate atomic set/clear methods for single-bit fields using Cortex-M bit-banding",
+        ))
+        .arg(
+            Arg::with_name("impl_debug")
+                .long("impl_debug")
+                .help("Implement `Debug` for register readers, printing the value of every field"),
+        )
+        .arg(
+            Arg::with_name("impl_default")
+                .long("impl_default")
+                .help("Implement `Default` for register writers, holding the reset value"),
+        )
+        .arg(
+            Arg::with_name("impl_ord")
+                .long("impl_ord")
+                .help("Implement `PartialOrd` and `Ord` for enumerated values, by their values"),
+        )
+        .arg(
+            Arg::with_name("ufmt")
+                .long("ufmt")
+                .help("Implement `ufmt::uDebug` for register readers behind the `ufmt` feature"),
+        )
+        .arg(
+            Arg::with_name("module_per_peripheral")
+                .long("module_per_peripheral")
+                .help("Write each peripheral module to a file of its own, next to `generic.rs`"),
+        )
+        .arg(
+            Arg::with_name("feature_peripheral")
+                .long("feature_peripheral")
+                .help("Put each peripheral behind a Cargo feature, listed in `features.toml`"),
+        )
+        .arg(
+            Arg::with_name("rename_peripheral")
+                .long("rename_peripheral")
+                .alias("rename-peripheral")
+                .help("Rename the peripheral FROM of the SVD file to TO")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("FROM=TO"),
+        )
+        .arg(
+            Arg::with_name("signed_field")
+                .long("signed_field")
+                .alias("signed-field")
+                .help("Sign-extend the reader of the field PERIPHERAL.REGISTER.FIELD")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .value_name("PERIPHERAL.REGISTER.FIELD"),
+        )
+        .arg(
+            Arg::with_name("check_layout")
+                .long("check_layout")
+                .help("Check at compile time that register blocks have the size given by the SVD"),
+        )
+        .arg(
+            Arg::with_name("secure_feature")
+                .long("secure_feature")
+                .help("Put registers with secure `protection` behind the `secure` feature"),
+        )
+        .arg(
+            Arg::with_name("std_feature")
+                .long("std_feature")
+                .help("Only make the crate `no_std` if its `std` feature is disabled"),
+        )
+        .arg(
+            Arg::with_name("config_struct")
+                .long("config_struct")
+                .help("Generate a `Config` struct of the fields of each register, for `configure`"),
+        )
+        .arg(
+            Arg::with_name("flags")
+                .long("flags")
+                .help("Generate `Flags` of the single-bit fields of each register, for `set_flags`"),
+        )
+        .arg(
+            Arg::with_name("addresses")
+                .long("addresses")
+                .help("Generate an `addresses` module with the base addresses of all peripherals"),
+        )
+        .arg(
+            Arg::with_name("shared_enums")
+                .long("shared_enums")
+                .help("Use one enum for the fields of a register with the same enumerated values"),
+        )
+        .arg(
+            Arg::with_name("shared_enums_ignore_doc")
+                .long("shared_enums_ignore_doc")
+                .help("Share enums with `--shared_enums` even if the docs of their values differ"),
+        )
+        .arg(
+            Arg::with_name("defmt")
+                .long("defmt")
+                .help("Implement `defmt::Format` for enumerated values, behind the `defmt` feature"),
+        )
+        .arg(
+            Arg::with_name("serde")
+                .long("serde")
+                .help("Derive `serde` traits for enumerated values, behind the `serde` feature enabled by the written `serde.toml`"),
+        )
+        .arg(
+            Arg::with_name("critical_section")
+                .long("critical_section")
+                .help(
+                    "Generate `modify_cs` and guard `Peripherals::take` with `critical-section`, \
+                     behind the `critical-section` feature",
+                ),
+        )
+        .arg(
+            Arg::with_name("split_registers")
+                .long("split_registers")
+                .help("Generate `split` to move the registers out of a peripheral as owned tokens"),
+        )
+        .arg(
+            Arg::with_name("reflect")
+                .long("reflect")
+                .help("Generate a `FIELDS` table of each register, behind the `reflect` feature"),
+        )
+        .arg(
+            Arg::with_name("field_setters")
+                .long("field_setters")
+                .help("Generate `<field>_set` methods of `W` writing raw bits to a field"),
+        )
+        .arg(
+            Arg::with_name("option_setters")
+                .long("option_setters")
+                .alias("option-setters")
+                .help("Generate `<field>_opt` methods of `W` writing a variant of a field if it is `Some`"),
+        )
+        .arg(
+            Arg::with_name("field_masks")
+                .long("field_masks")
+                .help("Generate `<FIELD>_MASK` and `<FIELD>_CLEAR` constants of the bits of each field"),
+        )
+        .arg(
+            Arg::with_name("spin_helpers")
+                .long("spin_helpers")
+                .alias("spin-helpers")
+                .help("Generate `<field>_wait_set` and `_wait_clear` methods spinning on single-bit fields"),
+        )
+        .arg(
+            Arg::with_name("ptr_consts")
+                .long("ptr_consts")
+                .alias("ptr-consts")
+                .help("Generate a `<PERIPHERAL>_PTR` const with the pointer to each register block"),
+        )
+        .arg(
+            Arg::with_name("address_blocks")
+                .long("address_blocks")
+                .alias("address-blocks")
+                .help("Generate `<PERIPHERAL>_SIZE` and `<PERIPHERAL>_ADDRESS_BLOCKS` consts of the address blocks of each peripheral"),
+        )
+        .arg(
+            Arg::with_name("hal_scaffold")
+                .long("hal_scaffold")
+                .help("Write empty drivers of the recognized peripherals to `hal_scaffold.rs`"),
+        )
+        .arg(
+            Arg::with_name("vector_table")
+                .long("vector_table")
+                .help("Provide the interrupt vectors without `cortex-m-rt`, with the \"vector-table\" feature"),
+        )
+        .arg(
+            Arg::with_name("str_names")
+                .long("str_names")
+                .help("Convert enumerated values to and from their names, behind the `str-names` feature"),
         )
         .arg(
             Arg::with_name("source_type")
                 .long("source_type")
                 .help("Specify file/stream format"),
         )
+        .arg(
+            Arg::with_name("patch")
+                .long("patch")
+                .help("Apply the YAML patches of FILE to the SVD file before generating the code")
+                .takes_value(true)
+                .value_name("FILE"),
+        )
+        .arg(
+            Arg::with_name("report")
+                .long("report")
+                .help("Write a JSON report of the generated items, skipped items and warnings")
+                .takes_value(true)
+                .value_name("FILE"),
+        )
         .arg(
             Arg::with_name("log_level")
                 .long("log")
+                .alias("log-level")
                 .short("l")
                 .help(&format!(
                     "Choose which messages to log (overrides {})",
@@ -109,21 +318,20 @@ fn run() -> Result<()> {
         ))
         .get_matches();
 
-    let input = &mut String::new();
-    match matches.value_of("input") {
-        Some(file) => {
-            File::open(file)
-                .context("Cannot open the SVD file")?
-                .read_to_string(input)
-                .context("Cannot read the SVD file")?;
-        }
-        None => {
-            let stdin = std::io::stdin();
-            stdin
-                .lock()
-                .read_to_string(input)
-                .context("Cannot read from stdin")?;
-        }
+    let files: Vec<_> = matches.values_of("input").into_iter().flatten().collect();
+    let mut inputs = vec![String::new(); files.len().max(1)];
+    if files.is_empty() {
+        let stdin = std::io::stdin();
+        stdin
+            .lock()
+            .read_to_string(&mut inputs[0])
+            .context("Cannot read from stdin")?;
+    }
+    for (file, input) in files.iter().zip(&mut inputs) {
+        File::open(file)
+            .with_context(|| format!("Cannot open the SVD file {}", file))?
+            .read_to_string(input)
+            .with_context(|| format!("Cannot read the SVD file {}", file))?;
     }
 
     let path = PathBuf::from(matches.value_of("output").unwrap_or("."));
@@ -132,7 +340,15 @@ fn run() -> Result<()> {
 
     let cfg = with_toml_env(&matches, &[config_filename, "svd2rust.toml"]);
 
-    setup_logging(&cfg);
+    let report = cfg.grab().arg("report").conf("report").done();
+    let patch = cfg
+        .grab()
+        .arg("patch")
+        .conf("patch")
+        .done()
+        .map(PathBuf::from);
+
+    let logger = setup_logging(&cfg, report.is_some());
 
     let target = cfg
         .grab()
@@ -140,7 +356,26 @@ fn run() -> Result<()> {
         .conf("target")
         .done()
         .map(|s| Target::parse(&s))
-        .unwrap_or_else(|| Ok(Target::default()))?;
+        .transpose()?;
+
+    let inline = cfg
+        .grab()
+        .arg("inline")
+        .conf("inline")
+        .done()
+        .map(|s| Inline::parse(&s))
+        .unwrap_or_else(|| Ok(Inline::default()))?;
+
+    let max_field_width = cfg
+        .grab()
+        .arg("max_field_width")
+        .conf("max_field_width")
+        .done()
+        .map(|s| {
+            s.parse::<u32>()
+                .with_context(|| format!("Invalid --max_field_width `{}`", s))
+        })
+        .transpose()?;
 
     let nightly =
         cfg.bool_flag("nightly_features", Filter::Arg) || cfg.bool_flag("nightly", Filter::Conf);
@@ -148,6 +383,7 @@ fn run() -> Result<()> {
         cfg.bool_flag("generic_mod", Filter::Arg) || cfg.bool_flag("generic_mod", Filter::Conf);
     let make_mod =
         cfg.bool_flag("make_mod", Filter::Arg) || cfg.bool_flag("make_mod", Filter::Conf);
+    let format = cfg.bool_flag("format", Filter::Arg) || cfg.bool_flag("format", Filter::Conf);
     let const_generic =
         cfg.bool_flag("const_generic", Filter::Arg) || cfg.bool_flag("const_generic", Filter::Conf);
     let ignore_groups =
@@ -155,6 +391,85 @@ fn run() -> Result<()> {
     let keep_list =
         cfg.bool_flag("keep_list", Filter::Arg) || cfg.bool_flag("keep_list", Filter::Conf);
     let strict = cfg.bool_flag("strict", Filter::Arg) || cfg.bool_flag("strict", Filter::Conf);
+    let bitband = cfg.bool_flag("bitband", Filter::Arg) || cfg.bool_flag("bitband", Filter::Conf);
+    let impl_debug =
+        cfg.bool_flag("impl_debug", Filter::Arg) || cfg.bool_flag("impl_debug", Filter::Conf);
+    let impl_default =
+        cfg.bool_flag("impl_default", Filter::Arg) || cfg.bool_flag("impl_default", Filter::Conf);
+    let impl_ord =
+        cfg.bool_flag("impl_ord", Filter::Arg) || cfg.bool_flag("impl_ord", Filter::Conf);
+    let defmt = cfg.bool_flag("defmt", Filter::Arg) || cfg.bool_flag("defmt", Filter::Conf);
+    let ufmt = cfg.bool_flag("ufmt", Filter::Arg) || cfg.bool_flag("ufmt", Filter::Conf);
+    let serde = cfg.bool_flag("serde", Filter::Arg) || cfg.bool_flag("serde", Filter::Conf);
+    let critical_section = cfg.bool_flag("critical_section", Filter::Arg)
+        || cfg.bool_flag("critical_section", Filter::Conf);
+    let feature_peripheral = cfg.bool_flag("feature_peripheral", Filter::Arg)
+        || cfg.bool_flag("feature_peripheral", Filter::Conf);
+    let module_per_peripheral = cfg.bool_flag("module_per_peripheral", Filter::Arg)
+        || cfg.bool_flag("module_per_peripheral", Filter::Conf);
+    let addresses =
+        cfg.bool_flag("addresses", Filter::Arg) || cfg.bool_flag("addresses", Filter::Conf);
+    let check_layout =
+        cfg.bool_flag("check_layout", Filter::Arg) || cfg.bool_flag("check_layout", Filter::Conf);
+    let secure_feature = cfg.bool_flag("secure_feature", Filter::Arg)
+        || cfg.bool_flag("secure_feature", Filter::Conf);
+    let std_feature =
+        cfg.bool_flag("std_feature", Filter::Arg) || cfg.bool_flag("std_feature", Filter::Conf);
+    let config_struct =
+        cfg.bool_flag("config_struct", Filter::Arg) || cfg.bool_flag("config_struct", Filter::Conf);
+    let flags = cfg.bool_flag("flags", Filter::Arg) || cfg.bool_flag("flags", Filter::Conf);
+    let split_registers = cfg.bool_flag("split_registers", Filter::Arg)
+        || cfg.bool_flag("split_registers", Filter::Conf);
+    let reflect = cfg.bool_flag("reflect", Filter::Arg) || cfg.bool_flag("reflect", Filter::Conf);
+    let str_names =
+        cfg.bool_flag("str_names", Filter::Arg) || cfg.bool_flag("str_names", Filter::Conf);
+    let field_setters =
+        cfg.bool_flag("field_setters", Filter::Arg) || cfg.bool_flag("field_setters", Filter::Conf);
+    let option_setters = cfg.bool_flag("option_setters", Filter::Arg)
+        || cfg.bool_flag("option_setters", Filter::Conf);
+    let field_masks =
+        cfg.bool_flag("field_masks", Filter::Arg) || cfg.bool_flag("field_masks", Filter::Conf);
+    let spin_helpers =
+        cfg.bool_flag("spin_helpers", Filter::Arg) || cfg.bool_flag("spin_helpers", Filter::Conf);
+    let ptr_consts =
+        cfg.bool_flag("ptr_consts", Filter::Arg) || cfg.bool_flag("ptr_consts", Filter::Conf);
+    let address_blocks = cfg.bool_flag("address_blocks", Filter::Arg)
+        || cfg.bool_flag("address_blocks", Filter::Conf);
+    let hal_scaffold =
+        cfg.bool_flag("hal_scaffold", Filter::Arg) || cfg.bool_flag("hal_scaffold", Filter::Conf);
+    let vector_table =
+        cfg.bool_flag("vector_table", Filter::Arg) || cfg.bool_flag("vector_table", Filter::Conf);
+    let shared_enums =
+        cfg.bool_flag("shared_enums", Filter::Arg) || cfg.bool_flag("shared_enums", Filter::Conf);
+    let shared_enums_ignore_doc = cfg.bool_flag("shared_enums_ignore_doc", Filter::Arg)
+        || cfg.bool_flag("shared_enums_ignore_doc", Filter::Conf);
+
+    let mut rename_peripherals = Vec::new();
+    for rename in matches.values_of("rename_peripheral").into_iter().flatten() {
+        let mut names = rename.splitn(2, '=');
+        match (names.next(), names.next()) {
+            (Some(from), Some(to)) if !from.is_empty() && !to.is_empty() => {
+                rename_peripherals.push((from.to_string(), to.to_string()))
+            }
+            _ => bail!("Invalid --rename_peripheral `{}`, expected FROM=TO", rename),
+        }
+    }
+
+    let mut signed_fields = Vec::new();
+    for path in matches.values_of("signed_field").into_iter().flatten() {
+        let names: Vec<_> = path.split('.').collect();
+        match names[..] {
+            [peripheral, register, field]
+                if !peripheral.is_empty() && !register.is_empty() && !field.is_empty() =>
+            {
+                signed_fields.push((peripheral.into(), register.into(), field.into()))
+            }
+            _ => bail!(
+                "Invalid --signed_field `{}`, expected PERIPHERAL.REGISTER.FIELD",
+                path
+            ),
+        }
+    }
 
     let mut source_type = cfg
         .grab()
@@ -164,29 +479,76 @@ fn run() -> Result<()> {
         .and_then(|s| SourceType::from_extension(&s))
         .unwrap_or_default();
 
-    if let Some(file) = matches.value_of("input") {
-        source_type = SourceType::from_path(Path::new(file))
+    if let Some(file) = files.first() {
+        source_type = SourceType::from_path(Path::new(file));
+        if files[1..]
+            .iter()
+            .any(|file| SourceType::from_path(Path::new(file)) != source_type)
+        {
+            bail!("The SVD files to merge must all be in the same format");
+        }
     }
 
     let config = Config {
-        target,
+        target: target.unwrap_or_default(),
+        // without an explicit target, the `<cpu>` of the SVD picks it
+        target_from_cpu: target.is_none(),
+        inline,
+        max_field_width,
         nightly,
         generic_mod,
         make_mod,
+        format,
         const_generic,
         ignore_groups,
         keep_list,
         strict,
+        bitband,
+        impl_debug,
+        impl_default,
+        impl_ord,
+        defmt,
+        ufmt,
+        serde,
+        critical_section,
+        shared_enums,
+        shared_enums_ignore_doc,
+        addresses,
+        check_layout,
+        secure_feature,
+        std_feature,
+        config_struct,
+        flags,
+        split_registers,
+        reflect,
+        str_names,
+        field_setters,
+        option_setters,
+        field_masks,
+        spin_helpers,
+        ptr_consts,
+        address_blocks,
+        hal_scaffold,
+        vector_table,
+        feature_peripheral,
+        module_per_peripheral,
+        rename_peripherals,
+        signed_fields,
+        register_enums: Vec::new(),
+        patch,
         output_dir: path.clone(),
         source_type,
     };
 
     info!("Parsing device from SVD file");
-    let device = load_from(input, &config)?;
+    let inputs: Vec<_> = inputs.iter().map(String::as_str).collect();
+    let device = load_merged(&inputs, &config)?;
+    let config = config_for(&config, &device, &inputs)?;
 
     let mut device_x = String::new();
+    let mut skipped = Vec::new();
     info!("Rendering device");
-    let items = generate::device::render(&device, &config, &mut device_x)
+    let items = generate::device::render(&device, &config, &mut device_x, &mut skipped)
         .with_context(|| "Error rendering device")?;
 
     let filename = if make_mod { "mod.rs" } else { "lib.rs" };
@@ -196,19 +558,100 @@ fn run() -> Result<()> {
     file.write_all(data.as_ref())
         .expect("Could not write code to lib.rs");
 
-    if target == Target::CortexM
-        || target == Target::Msp430
-        || target == Target::XtensaLX
-        || target == Target::RISCV
+    if config.format {
+        info!("Formatting {}", filename);
+        format_file(&path.join(filename));
+    }
+
+    if config.hal_scaffold {
+        info!("Rendering HAL scaffolding");
+        let scaffold = generate::hal_scaffold::render(&device, &config);
+        let file = path.join("hal_scaffold.rs");
+        std::fs::write(&file, scaffold.to_string())
+            .with_context(|| format!("Could not write {}", file.display()))?;
+        // the scaffolding is edited by hand, so it is formatted even without `--format`
+        format_file(&file);
+    }
+
+    if config.serde {
+        let file = path.join("serde.toml");
+        std::fs::write(&file, serde_manifest())
+            .with_context(|| format!("Could not write {}", file.display()))?;
+    }
+
+    if config.target == Target::CortexM
+        || config.target == Target::Msp430
+        || config.target == Target::XtensaLX
+        || config.target == Target::RISCV
     {
         writeln!(File::create(path.join("device.x"))?, "{}", device_x)?;
         writeln!(File::create(path.join("build.rs"))?, "{}", build_rs())?;
     }
 
+    if let (Some(file), Some(logger)) = (report, logger) {
+        info!("Writing report to {}", file);
+        let warnings = logger.warnings.lock().unwrap();
+        let report = svd2rust::report::render(&device, &skipped, &warnings);
+        std::fs::write(&file, format!("{:#}\n", report))
+            .with_context(|| format!("Could not write {}", file))?;
+    }
+
     Ok(())
 }
 
-fn setup_logging<'a>(getter: &'a impl clap_conf::Getter<'a, String>) {
+/// Formats `file`, and the files of the modules it declares, with rustfmt. They are left
+/// unformatted if rustfmt can't be run or fails
+fn format_file(file: &Path) {
+    match process::Command::new("rustfmt")
+        .args(&["--edition", "2018"])
+        .arg(file)
+        .output()
+    {
+        Ok(output) if output.status.success() => {}
+        Ok(output) => warn!(
+            "rustfmt failed, leaving the output unformatted: {}",
+            String::from_utf8_lossy(&output.stderr)
+        ),
+        Err(e) => warn!(
+            "Could not run rustfmt, leaving the output unformatted: {}",
+            e
+        ),
+    }
+}
+
+/// Logger passing the records on to `env_logger`, which keeps the warnings for the `--report`
+struct ReportLogger {
+    inner: env_logger::Logger,
+    warnings: Mutex<Vec<String>>,
+}
+
+impl log::Log for ReportLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::Level::Warn || self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &log::Record) {
+        if record.level() <= log::Level::Warn {
+            self.warnings
+                .lock()
+                .unwrap()
+                .push(record.args().to_string());
+        }
+        if self.inner.matches(record) {
+            self.inner.log(record);
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+/// Sets up the logger, which keeps the warnings if `report`
+fn setup_logging<'a>(
+    getter: &'a impl clap_conf::Getter<'a, String>,
+    report: bool,
+) -> Option<&'static ReportLogger> {
     // * Log at info by default.
     // * Allow users the option of setting complex logging filters using
     //   env_logger's `RUST_LOG` environment variable.
@@ -231,7 +674,20 @@ fn setup_logging<'a>(getter: &'a impl clap_conf::Getter<'a, String>) {
         builder.filter_level(level);
     }
 
-    builder.init();
+    if !report {
+        builder.init();
+        return None;
+    }
+    // the warnings are kept even when they aren't logged
+    if log::max_level() < log::LevelFilter::Warn {
+        log::set_max_level(log::LevelFilter::Warn);
+    }
+    let logger = Box::leak(Box::new(ReportLogger {
+        inner: builder.build(),
+        warnings: Mutex::new(Vec::new()),
+    }));
+    log::set_logger(logger).expect("Logger already set");
+    Some(logger)
 }
 
 fn main() {
@@ -0,0 +1,129 @@
+//! Merging of the devices of several SVD files into one, e.g. the core peripherals and the vendor
+//! peripherals of a SoC described by different files
+
+use std::collections::HashMap;
+
+use crate::strict::{address_ranges, alternates};
+use crate::svd::Device;
+use crate::util::inherit;
+use anyhow::{bail, Result};
+use log::warn;
+
+/// Merges `devices` into the first one, in order.
+///
+/// The first device gives the properties of the merged one, except for the `<cpu>` which comes
+/// from the first device having one. The peripherals of the others are appended, taking the
+/// default register properties of their own device along. A peripheral named like one of an
+/// earlier device is dropped if it is the same and is an error otherwise, as are different
+/// `<cpu>`s, interrupts of different devices sharing a name or a number but not both, and
+/// overlapping address blocks of peripherals of different devices which are not
+/// `alternatePeripheral`s of each other.
+///
+/// Devices are numbered from 1 in the error messages.
+pub fn merge(devices: Vec<Device>) -> Result<Device> {
+    let mut devices = devices.into_iter();
+    let mut device = match devices.next() {
+        Some(device) => device,
+        None => bail!("No SVD file to merge"),
+    };
+    for p in &mut device.peripherals {
+        p.default_register_properties = inherit(
+            &p.default_register_properties,
+            &device.default_register_properties,
+        );
+    }
+    // the device each peripheral comes from
+    let mut sources = vec![1; device.peripherals.len()];
+
+    for (n, other) in (2..).zip(devices) {
+        if let Some(cpu) = other.cpu {
+            match &device.cpu {
+                Some(first) if first.name != cpu.name => bail!(
+                    "The <cpu> {} of SVD file {} differs from the <cpu> {}",
+                    cpu.name,
+                    n,
+                    first.name
+                ),
+                Some(_) => {}
+                None => device.cpu = Some(cpu),
+            }
+        }
+        for mut p in other.peripherals {
+            p.default_register_properties = inherit(
+                &p.default_register_properties,
+                &other.default_register_properties,
+            );
+            match device.peripherals.iter().position(|x| x.name == p.name) {
+                Some(i) if device.peripherals[i] == p => warn!(
+                    "Peripheral `{}` of SVD file {} is the same as the one of SVD file {}, merged",
+                    p.name, n, sources[i]
+                ),
+                Some(i) => bail!(
+                    "Peripheral `{}` of SVD file {} differs from the one of SVD file {}",
+                    p.name,
+                    n,
+                    sources[i]
+                ),
+                None => {
+                    device.peripherals.push(p);
+                    sources.push(n);
+                }
+            }
+        }
+    }
+
+    let mut by_value = HashMap::new();
+    let mut by_name = HashMap::new();
+    for (p, &n) in device.peripherals.iter().zip(&sources) {
+        for i in &p.interrupt {
+            if let Some((other, m)) = by_value.insert(i.value, (i, n)) {
+                if other.name != i.name && m != n {
+                    bail!(
+                        "Interrupt `{}` of SVD file {} has the number {} of `{}` of SVD file {}",
+                        i.name,
+                        n,
+                        i.value,
+                        other.name,
+                        m
+                    );
+                }
+            }
+            if let Some((other, m)) = by_name.insert(&i.name, (i, n)) {
+                if other.value != i.value && m != n {
+                    bail!(
+                        "Interrupt `{}` has the number {} in SVD file {} and {} in SVD file {}",
+                        i.name,
+                        i.value,
+                        n,
+                        other.value,
+                        m
+                    );
+                }
+            }
+        }
+    }
+
+    let ranges = address_ranges(&device.peripherals);
+    for (i, &(start, end, p)) in ranges.iter().enumerate() {
+        for &(other_start, _, other) in &ranges[i + 1..] {
+            if other_start >= end {
+                break;
+            }
+            let (n, m) = (sources[p], sources[other]);
+            let (p, other) = (&device.peripherals[p], &device.peripherals[other]);
+            if n != m && !alternates(p, other) {
+                bail!(
+                    "Address block of peripheral `{}` of SVD file {} at {:#x} overlaps the one of \
+                     `{}` of SVD file {} at {:#x}",
+                    other.name,
+                    m,
+                    other_start,
+                    p.name,
+                    n,
+                    start
+                );
+            }
+        }
+    }
+    Ok(device)
+}
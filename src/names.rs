@@ -0,0 +1,440 @@
+//! Renaming of SVD items whose Rust identifiers would collide with the ones of a sibling
+
+use std::collections::{HashMap, HashSet};
+
+use crate::svd::{Cluster, Device, EnumeratedValues, Field, Register, RegisterCluster};
+use crate::util::{self, ToSanitizedSnakeCase, ToSanitizedUpperCase};
+use anyhow::{bail, Result};
+use log::warn;
+
+/// Identifiers used in one scope of the generated code, e.g. the fields of a register
+#[derive(Default)]
+struct Scope(HashSet<String>);
+
+impl Scope {
+    /// Reserves the identifiers of `name`, returning the suffix to append to the name of the item
+    /// if they are taken already. `name` is the name without the `%s` of arrays
+    fn reserve(&mut self, name: &str) -> Option<String> {
+        self.reserve_with(|suffix| vec![format!("{}{}", name, suffix)])
+    }
+
+    /// Reserves the identifiers of the array `name`, like `reserve`, along with the ones of its
+    /// elements, named with each of `indexes` in place of its `%s`
+    fn reserve_array(&mut self, name: &str, indexes: &[String]) -> Option<String> {
+        self.reserve_with(|suffix| {
+            let candidate = with_suffix(name, suffix);
+            std::iter::once(util::replace_suffix(&candidate, ""))
+                .chain(indexes.iter().map(|i| util::replace_suffix(&candidate, i)))
+                .collect()
+        })
+    }
+
+    /// Reserves the identifiers of the names `candidates` gives for the first of the suffixes `""`,
+    /// `"_1"`, `"_2"`, ... with which they are all free, returning it like `reserve`
+    fn reserve_with(&mut self, candidates: impl Fn(&str) -> Vec<String>) -> Option<String> {
+        let mut suffix = String::new();
+        for n in 1.. {
+            let keys: Vec<_> = candidates(&suffix)
+                .iter()
+                .flat_map(|candidate| {
+                    vec![
+                        candidate.to_sanitized_snake_case().into_owned(),
+                        candidate.to_sanitized_upper_case().into_owned(),
+                    ]
+                })
+                .collect();
+            if keys.iter().all(|k| !self.0.contains(k)) {
+                self.0.extend(keys);
+                break;
+            }
+            suffix = format!("_{}", n);
+        }
+        if suffix.is_empty() {
+            None
+        } else {
+            Some(suffix)
+        }
+    }
+}
+
+/// Renames the peripherals named like the first name of one of `renames` to the second one,
+/// along with the `derivedFrom`s referring to them. The new names must not have the same Rust
+/// identifiers as another peripheral
+pub fn rename_peripherals(device: &mut Device, renames: &[(String, String)]) -> Result<()> {
+    let renamed = |name: &str| {
+        renames
+            .iter()
+            .find(|(from, _)| from == name)
+            .map(|(_, to)| to.clone())
+    };
+    for (from, _) in renames {
+        if !device.peripherals.iter().any(|p| p.name == *from) {
+            bail!("Can't rename peripheral `{}`, as there is none", from);
+        }
+    }
+
+    // the names which stay take precedence, even if they collide with each other
+    let mut scope = Scope::default();
+    for p in &device.peripherals {
+        if renamed(&p.name).is_none() {
+            scope.reserve(&p.name);
+        }
+    }
+    let mut paths = HashMap::new();
+    for p in &device.peripherals {
+        if let Some(to) = renamed(&p.name) {
+            if scope.reserve(&to).is_some() {
+                bail!(
+                    "Can't rename peripheral `{}` to `{}`, as its Rust name is taken by another one",
+                    p.name,
+                    to
+                );
+            }
+            paths.insert(vec![p.name.clone()], to);
+        }
+    }
+    rename_derived_from(device, &paths);
+    for p in &mut device.peripherals {
+        if let Some(to) = renamed(&p.name) {
+            p.name = to;
+        }
+    }
+    Ok(())
+}
+
+/// Renames the items of `renames`, given by the original names of their peripheral, clusters,
+/// register and field, in the `derivedFrom`s referring to them. Paths are resolved like when
+/// generating the code, so this must be done before renaming the items themselves
+fn rename_derived_from(device: &mut Device, renames: &HashMap<Vec<String>, String>) {
+    if renames.is_empty() {
+        return;
+    }
+    let paths = Paths {
+        peripherals: device
+            .peripherals
+            .iter()
+            .map(|p| {
+                let original = p.derived_from.clone().filter(|_| p.registers.is_none());
+                (p.name.clone(), original)
+            })
+            .collect(),
+        renames,
+    };
+    for p in &mut device.peripherals {
+        if let Some(original) = &mut p.derived_from {
+            paths.rename(original, &[], 0, 0);
+        }
+        let mut context = vec![p.name.clone()];
+        if let Some(registers) = &mut p.registers {
+            paths.rename_ercs(registers, &mut context);
+        }
+    }
+}
+
+/// The `derivedFrom` paths of a device, `[peripheral.][clusters.][register.][field.]name`
+struct Paths<'a> {
+    /// Names of the peripherals, and of the ones they take their registers from
+    peripherals: HashMap<String, Option<String>>,
+    renames: &'a HashMap<Vec<String>, String>,
+}
+
+impl<'a> Paths<'a> {
+    fn rename_ercs(&self, ercs: &mut [RegisterCluster], context: &mut Vec<String>) {
+        for erc in ercs {
+            match erc {
+                RegisterCluster::Register(r) => {
+                    if let Some(original) = &mut r.derived_from {
+                        self.rename(original, context, 1, 0);
+                    }
+                    context.push(r.name.clone());
+                    for f in r.fields_mut() {
+                        if let Some(original) = &mut f.derived_from {
+                            self.rename(original, context, 2, 0);
+                        }
+                        for evs in &mut f.enumerated_values {
+                            if let Some(original) = &mut evs.derived_from {
+                                self.rename(original, context, 2, 1);
+                            }
+                        }
+                    }
+                    context.pop();
+                }
+                RegisterCluster::Cluster(c) => {
+                    if let Some(original) = &mut c.derived_from {
+                        self.rename(original, context, 1, 0);
+                    }
+                    context.push(c.name.clone());
+                    self.rename_ercs(&mut c.children, context);
+                    context.pop();
+                }
+            }
+        }
+    }
+
+    /// Renames the items of the `derivedFrom` `path` of an item of `context`, the names of its
+    /// peripheral, clusters and register. The last `tail` segments of the path don't name an
+    /// item of `context`, like the name of enumeratedValues, and paths to up to `relative` items
+    /// are relative to `context` instead of to the peripheral, like `REGISTER.FIELD`
+    fn rename(&self, path: &mut String, context: &[String], relative: usize, tail: usize) {
+        let mut segments: Vec<String> = path.split('.').map(String::from).collect();
+        let items = segments.len().saturating_sub(tail);
+        let mut absolute = if items == 0 {
+            return;
+        } else if items <= relative {
+            context[..context.len() + 1 - items].to_vec()
+        } else if self.peripherals.contains_key(&segments[0]) {
+            Vec::new()
+        } else {
+            context.iter().take(1).cloned().collect()
+        };
+        for segment in segments.iter_mut().take(items) {
+            absolute.push(segment.clone());
+            if let Some(new) = self.renames.get(&absolute) {
+                *segment = new.clone();
+            }
+            if absolute.len() == 1 {
+                absolute[0] = self.original(&absolute[0]);
+            }
+        }
+        *path = segments.join(".");
+    }
+
+    /// Name of the peripheral whose registers `peripheral` has
+    fn original(&self, peripheral: &str) -> String {
+        let mut name = peripheral;
+        // bounded, in case of a cycle
+        for _ in 0..self.peripherals.len() {
+            match self.peripherals.get(name) {
+                Some(Some(original)) => name = original,
+                _ => break,
+            }
+        }
+        name.into()
+    }
+}
+
+/// Appends `_1`, `_2`, ... to the names of peripherals, clusters, registers, fields and
+/// enumerated values which have the same Rust identifiers as an item before them in the same
+/// scope, e.g. `GPIO-A` after `GPIOA`, so that the generated code compiles. The `derivedFrom`s
+/// referring to renamed items are renamed too. The suffix of arrays goes before their `%s`
+pub fn disambiguate(device: &mut Device, ignore_groups: bool) {
+    // the renames are found on a copy first, as the `derivedFrom`s are resolved with the
+    // original names
+    let mut renames = Renames::default();
+    disambiguate_device(&mut device.clone(), ignore_groups, &mut renames);
+    rename_derived_from(device, &renames.paths);
+    renames.warn = true;
+    disambiguate_device(device, ignore_groups, &mut renames);
+}
+
+/// Items renamed by `disambiguate`
+#[derive(Default)]
+struct Renames {
+    /// New names, by the original names of the item and its parents. Items with the same name
+    /// as one before them are left out, as the `derivedFrom`s with their name refer to that one
+    paths: HashMap<Vec<String>, String>,
+    warn: bool,
+}
+
+impl Renames {
+    /// Adds `suffix` to `name`, the name of an item of `scope` whose parents are `path`
+    fn rename(
+        &mut self,
+        kind: &str,
+        path: &[String],
+        name: &mut String,
+        suffix: &str,
+        scope: &str,
+        first: bool,
+    ) {
+        let new = with_suffix(name, suffix);
+        if self.warn {
+            warn!(
+                "Renaming {} `{}` of `{}` to `{}`, as its Rust name is taken by another one",
+                kind, name, scope, new
+            );
+        }
+        if first {
+            self.paths.insert(child(path, name), new.clone());
+        }
+        *name = new;
+    }
+}
+
+/// Appends `suffix` to `name`, before the `%s` of arrays
+fn with_suffix(name: &str, suffix: &str) -> String {
+    match name.find("[%s]").or_else(|| name.find("%s")) {
+        Some(i) => format!("{}{}{}", &name[..i], suffix, &name[i..]),
+        None => format!("{}{}", name, suffix),
+    }
+}
+
+/// Path of the item `name` of the item `path`
+fn child(path: &[String], name: &str) -> Vec<String> {
+    let mut path = path.to_vec();
+    path.push(name.into());
+    path
+}
+
+fn disambiguate_device(device: &mut Device, ignore_groups: bool, renames: &mut Renames) {
+    let mut peripherals = Scope::default();
+    let mut seen = HashSet::new();
+    for p in &mut device.peripherals {
+        let original = child(&[], &p.name);
+        let first = seen.insert(p.name.clone());
+        if let Some(suffix) = peripherals.reserve(&p.name) {
+            renames.rename("peripheral", &[], &mut p.name, &suffix, &device.name, first);
+        }
+        let scope = p.name.clone();
+        if let Some(registers) = &mut p.registers {
+            disambiguate_ercs(registers, ignore_groups, &original, &scope, renames);
+        }
+    }
+}
+
+fn disambiguate_ercs(
+    ercs: &mut [RegisterCluster],
+    ignore_groups: bool,
+    path: &[String],
+    scope: &str,
+    renames: &mut Renames,
+) {
+    // registers and clusters are both fields of the register block
+    let mut names = Scope::default();
+    let mut seen = HashSet::new();
+    for erc in ercs.iter_mut() {
+        match erc {
+            RegisterCluster::Register(register) => {
+                let original = child(path, &register.name);
+                let first = seen.insert(register.name.clone());
+                let name = util::name_of(register, ignore_groups).into_owned();
+                if let Some(suffix) = names.reserve(&name) {
+                    renames.rename("register", path, &mut register.name, &suffix, scope, first);
+                }
+                let scope = format!("{}.{}", scope, register.name);
+                disambiguate_fields(register, &original, &scope, renames);
+            }
+            RegisterCluster::Cluster(cluster) => {
+                let original = child(path, &cluster.name);
+                let first = seen.insert(cluster.name.clone());
+                if let Some(suffix) = names.reserve(&util::replace_suffix(&cluster.name, "")) {
+                    renames.rename("cluster", path, &mut cluster.name, &suffix, scope, first);
+                }
+                let scope = format!("{}.{}", scope, cluster.name);
+                let children = &mut cluster.children;
+                disambiguate_ercs(children, ignore_groups, &original, &scope, renames);
+            }
+        }
+    }
+    header_struct_names(ercs, scope, renames.warn);
+}
+
+/// Sanitizes the `headerStructName`s of clusters, and drops the ones which aren't valid Rust
+/// identifiers or would define the same struct as another cluster of the block. As derived
+/// clusters inherit the one of their original, the name of the cluster is set as its
+/// `headerStructName` instead of removing it
+fn header_struct_names(ercs: &mut [RegisterCluster], scope: &str, warn: bool) {
+    for erc in ercs.iter_mut() {
+        if let RegisterCluster::Cluster(c) = erc {
+            if let Some(header) = &c.header_struct_name {
+                let name = util::to_type_name(header);
+                if name.is_none() && warn {
+                    warn!(
+                        "Ignoring headerStructName `{}` of cluster `{}` of `{}`, as it isn't a valid Rust identifier",
+                        header, c.name, scope
+                    );
+                }
+                c.header_struct_name = Some(name.unwrap_or_else(|| default_struct_name(c)));
+            }
+        }
+    }
+    let headers: HashMap<String, String> = ercs
+        .iter()
+        .filter_map(|erc| match erc {
+            RegisterCluster::Cluster(c) => Some((c.name.clone(), c.header_struct_name.clone()?)),
+            RegisterCluster::Register(_) => None,
+        })
+        .collect();
+    // the default names are unique after `disambiguate_ercs`
+    let mut structs: HashSet<String> = ercs
+        .iter()
+        .filter_map(|erc| match erc {
+            RegisterCluster::Cluster(c) => Some(default_struct_name(c)),
+            RegisterCluster::Register(_) => None,
+        })
+        .collect();
+    structs.insert("RegisterBlock".into());
+
+    for erc in ercs {
+        if let RegisterCluster::Cluster(c) = erc {
+            let header = c.header_struct_name.as_ref().or_else(|| {
+                let original = c.derived_from.as_ref()?;
+                headers.get(original)
+            });
+            if let Some(header) = header.cloned() {
+                let default = default_struct_name(c);
+                if header != default && !structs.insert(header.clone()) {
+                    if warn {
+                        warn!(
+                            "Ignoring headerStructName `{}` of cluster `{}` of `{}`, as it names another struct",
+                            header, c.name, scope
+                        );
+                    }
+                    c.header_struct_name = Some(default);
+                }
+            }
+        }
+    }
+}
+
+fn default_struct_name(c: &Cluster) -> String {
+    util::replace_suffix(&c.name, "")
+        .to_sanitized_upper_case()
+        .into_owned()
+}
+
+fn disambiguate_fields(
+    register: &mut Register,
+    path: &[String],
+    scope: &str,
+    renames: &mut Renames,
+) {
+    let mut names = Scope::default();
+    let mut seen = HashSet::new();
+    // the accessors of the elements of field arrays, like `ch0` of `CH%s`, take precedence over
+    // the ones of single fields, like `CH0`
+    for f in register.fields_mut() {
+        if let Field::Array(_, de) = f {
+            let indexes = de
+                .dim_index
+                .clone()
+                .unwrap_or_else(|| (0..de.dim).map(|i| i.to_string()).collect());
+            let first = seen.insert(f.name.clone());
+            if let Some(suffix) = names.reserve_array(&f.name, &indexes) {
+                renames.rename("field", path, &mut f.name, &suffix, scope, first);
+            }
+        }
+    }
+    for f in register.fields_mut() {
+        if let Field::Single(_) = f {
+            let first = seen.insert(f.name.clone());
+            if let Some(suffix) = names.reserve(&f.name) {
+                renames.rename("field", path, &mut f.name, &suffix, scope, first);
+            }
+        }
+        let scope = format!("{}.{}", scope, f.name);
+        disambiguate_values(f, &scope, renames);
+    }
+}
+
+/// Enumerated values aren't referred to by `derivedFrom`s, so their renames aren't recorded
+fn disambiguate_values(f: &mut Field, scope: &str, renames: &mut Renames) {
+    for EnumeratedValues { values, .. } in &mut f.enumerated_values {
+        let mut names = Scope::default();
+        for ev in values {
+            if let Some(suffix) = names.reserve(&ev.name) {
+                renames.rename("enumerated value", &[], &mut ev.name, &suffix, scope, false);
+            }
+        }
+    }
+}
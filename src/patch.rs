@@ -0,0 +1,237 @@
+//! Patches of the `--patch` option, editing the registers and fields of the device before the
+//! code is generated, to fix SVD files without editing them
+
+use crate::svd::{
+    BitRange, DeriveFrom, Device, Field, Register, RegisterCluster, RegisterProperties,
+    ValidateLevel,
+};
+use anyhow::{anyhow, bail, Context, Result};
+use serde_yaml::{Mapping, Value};
+
+/// Keys of a patch
+const PATCH_KEYS: &[&str] = &[
+    "register",
+    "properties",
+    "deleteFields",
+    "modifyFields",
+    "addFields",
+];
+
+/// Keys of the `properties` of a patch
+const PROPERTY_KEYS: &[&str] = &["size", "access", "protection", "resetValue", "resetMask"];
+
+/// Keys of the fields of `modifyFields` and `addFields`
+const FIELD_KEYS: &[&str] = &[
+    "name",
+    "description",
+    "bitOffset",
+    "bitWidth",
+    "bitRange",
+    "lsb",
+    "msb",
+    "access",
+    "modifiedWriteValues",
+    "writeConstraint",
+    "readAction",
+    "enumeratedValues",
+    "derivedFrom",
+    "dim",
+    "dimIncrement",
+    "dimIndex",
+    "dimName",
+];
+
+/// Keys describing the bits of a field, which replace each other
+const BIT_RANGE_KEYS: &[&str] = &["bitOffset", "bitWidth", "bitRange", "lsb", "msb"];
+
+/// Applies the YAML list of patches `patches` to `device`, in order.
+///
+/// Patches are numbered from 1 in the error messages.
+pub fn apply(device: &mut Device, patches: &str) -> Result<()> {
+    let patches: Value = serde_yaml::from_str(patches).context("Error parsing patch file")?;
+    let patches = match patches {
+        Value::Sequence(patches) => patches,
+        Value::Null => vec![],
+        _ => bail!("The patch file must be a list of patches"),
+    };
+    for (n, patch) in (1..).zip(&patches) {
+        apply_one(device, patch).with_context(|| format!("In patch {}", n))?;
+    }
+    Ok(())
+}
+
+fn apply_one(device: &mut Device, patch: &Value) -> Result<()> {
+    let patch = mapping(patch, PATCH_KEYS, "patch")?;
+    let path = match patch.get(&key("register")) {
+        Some(Value::String(path)) => path,
+        Some(_) => bail!("`register` must be a string"),
+        None => bail!("The patch has no `register`"),
+    };
+    let register = find_register(device, path)?;
+
+    if let Some(properties) = patch.get(&key("properties")) {
+        mapping(properties, PROPERTY_KEYS, "properties")?;
+        let properties: RegisterProperties =
+            serde_yaml::from_value(properties.clone()).context("Invalid `properties`")?;
+        register.properties = properties.derive_from(&register.properties);
+    }
+
+    if let Some(names) = patch.get(&key("deleteFields")) {
+        let names: Vec<String> =
+            serde_yaml::from_value(names.clone()).context("`deleteFields` must be a list")?;
+        let fields = register.fields.get_or_insert_with(Vec::new);
+        for name in names {
+            let i = position(fields, &name, path)?;
+            fields.remove(i);
+        }
+    }
+
+    if let Some(modified) = patch.get(&key("modifyFields")) {
+        let modified = match modified {
+            Value::Mapping(modified) => modified,
+            _ => bail!("`modifyFields` must map field names to their changes"),
+        };
+        let fields = register.fields.get_or_insert_with(Vec::new);
+        for (name, changes) in modified {
+            let name = name
+                .as_str()
+                .ok_or_else(|| anyhow!("The names of `modifyFields` must be strings"))?;
+            let changes = mapping(changes, FIELD_KEYS, "field")?;
+            let i = position(fields, name, path)?;
+            let field = modify(&fields[i], changes)
+                .with_context(|| format!("Invalid changes of field `{}`", name))?;
+            if field.name != name && fields.iter().any(|f| f.name == field.name) {
+                bail!(
+                    "Can't rename field `{}` to `{}`, as it exists",
+                    name,
+                    field.name
+                );
+            }
+            fields[i] = field;
+        }
+    }
+
+    if let Some(added) = patch.get(&key("addFields")) {
+        let added = match added {
+            Value::Sequence(added) => added,
+            _ => bail!("`addFields` must be a list of fields"),
+        };
+        let fields = register.fields.get_or_insert_with(Vec::new);
+        for field in added {
+            mapping(field, FIELD_KEYS, "field")?;
+            let mut field: Field =
+                serde_yaml::from_value(field.clone()).context("Invalid field of `addFields`")?;
+            field
+                .validate(ValidateLevel::Weak)
+                .with_context(|| format!("Invalid field `{}`", field.name))?;
+            if fields.iter().any(|f| f.name == field.name) {
+                bail!(
+                    "Can't add field `{}` to register `{}`, as it exists",
+                    field.name,
+                    path
+                );
+            }
+            fields.push(field);
+        }
+    }
+    Ok(())
+}
+
+/// Returns the register at `path`, `PERIPHERAL.REGISTER` or `PERIPHERAL.CLUSTER...REGISTER`
+fn find_register<'a>(device: &'a mut Device, path: &str) -> Result<&'a mut Register> {
+    let mut names = path.split('.');
+    let peripheral = names.next().unwrap_or_default();
+    let mut names = names.collect::<Vec<_>>();
+    let register = match names.pop() {
+        Some(register) => register,
+        None => bail!(
+            "Invalid register `{}`, expected PERIPHERAL.REGISTER or PERIPHERAL.CLUSTER.REGISTER",
+            path
+        ),
+    };
+    let p = device
+        .peripherals
+        .iter_mut()
+        .find(|p| p.name == peripheral)
+        .ok_or_else(|| anyhow!("There is no peripheral `{}`", peripheral))?;
+    let mut ercs = p
+        .registers
+        .as_mut()
+        .map_or(&mut [][..], |ercs| &mut ercs[..]);
+    for cluster in names {
+        ercs = ercs
+            .iter_mut()
+            .find_map(|erc| match erc {
+                RegisterCluster::Cluster(c) if c.name == cluster => Some(&mut c.children[..]),
+                _ => None,
+            })
+            .ok_or_else(|| anyhow!("There is no cluster `{}` in `{}`", cluster, path))?;
+    }
+    ercs.iter_mut()
+        .find_map(|erc| match erc {
+            RegisterCluster::Register(r) if r.name == register => Some(r),
+            _ => None,
+        })
+        .ok_or_else(|| anyhow!("There is no register `{}`", path))
+}
+
+/// Returns the position of the field `name` in `fields` of the register at `path`
+fn position(fields: &[Field], name: &str, path: &str) -> Result<usize> {
+    fields
+        .iter()
+        .position(|f| f.name == name)
+        .ok_or_else(|| anyhow!("There is no field `{}` in register `{}`", name, path))
+}
+
+/// Returns `field` with the keys of `changes` replacing its own
+fn modify(field: &Field, changes: &Mapping) -> Result<Field> {
+    let mut field = match serde_yaml::to_value(field)? {
+        Value::Mapping(field) => field,
+        _ => unreachable!("fields are serialized to mappings"),
+    };
+    if BIT_RANGE_KEYS.iter().any(|k| changes.contains_key(&key(k))) {
+        // the changes may describe the bits in another way, keep what they don't give
+        let (offset, width) = (key("bitOffset"), key("bitWidth"));
+        let bits = serde_yaml::from_value::<BitRange>(Value::Mapping(field.clone()))?;
+        for k in BIT_RANGE_KEYS {
+            field.remove(&key(k));
+        }
+        if !["bitRange", "lsb", "msb"]
+            .iter()
+            .any(|k| changes.contains_key(&key(k)))
+        {
+            field.insert(offset, bits.offset.into());
+            field.insert(width, bits.width.into());
+        }
+    }
+    for (k, v) in changes {
+        field.insert(k.clone(), v.clone());
+    }
+    let mut field: Field = serde_yaml::from_value(Value::Mapping(field))?;
+    field.validate(ValidateLevel::Weak)?;
+    Ok(field)
+}
+
+/// Returns `value` as a mapping, failing on the keys which aren't in `keys`
+fn mapping<'a>(value: &'a Value, keys: &[&str], what: &str) -> Result<&'a Mapping> {
+    let mapping = match value {
+        Value::Mapping(mapping) => mapping,
+        _ => bail!("A {} must be a mapping", what),
+    };
+    for (k, _) in mapping {
+        match k.as_str() {
+            Some(k) if keys.contains(&k) => {}
+            _ => bail!(
+                "Unknown key `{}` of {}, expected one of {}",
+                serde_yaml::to_string(k)?.trim_start_matches("---").trim(),
+                what,
+                keys.join(", ")
+            ),
+        }
+    }
+    Ok(mapping)
+}
+
+fn key(k: &str) -> Value {
+    Value::String(k.into())
+}
@@ -0,0 +1,113 @@
+//! JSON report of the `--report` flag, of what was generated from the SVD file, to debug how its
+//! items map to the Rust code
+
+use std::collections::HashSet;
+
+use crate::generate::Skip;
+use crate::svd::{Device, RegisterCluster};
+use crate::util;
+use serde_json::{json, Value};
+
+/// Returns the report of the code generated for `device`: its peripherals, with their registers
+/// and fields, the counts of those, the items the generation `skipped` and why, and the
+/// `warnings` logged while loading and generating it, like the renamings of items and the merges
+/// of peripherals.
+///
+/// Registers are named by their path from the peripheral, through their clusters, and their
+/// offsets are the ones of the first element of arrays, from the peripheral. Skipped items are
+/// left out of the peripherals.
+pub fn render(device: &Device, skipped: &[Skip], warnings: &[String]) -> Value {
+    let skips: HashSet<_> = skipped.iter().map(|s| s.item.as_str()).collect();
+    let mut peripherals = vec![];
+    let (mut registers, mut fields) = (0, 0);
+    for p in &device.peripherals {
+        if skips.contains(p.name.as_str()) {
+            continue;
+        }
+        let mut regs = vec![];
+        let ercs = p.registers.as_deref().unwrap_or(&[]);
+        walk(ercs, &p.name, "", 0, &skips, &mut regs);
+        registers += regs.len();
+        fields += regs
+            .iter()
+            .map(|r| r["fields"].as_array().map_or(0, Vec::len))
+            .sum::<usize>();
+        let interrupts = p
+            .interrupt
+            .iter()
+            .map(|i| json!({ "name": i.name, "value": i.value }))
+            .collect::<Vec<_>>();
+        peripherals.push(json!({
+            "name": p.name,
+            "base_address": p.base_address,
+            "derived_from": p.derived_from,
+            "interrupts": interrupts,
+            "registers": regs,
+        }));
+    }
+
+    let skipped = skipped
+        .iter()
+        .map(|s| json!({ "item": s.item, "reason": s.reason }))
+        .collect::<Vec<_>>();
+    json!({
+        "device": device.name,
+        "counts": {
+            "peripherals": peripherals.len(),
+            "registers": registers,
+            "fields": fields,
+            "skipped": skipped.len(),
+            "warnings": warnings.len(),
+        },
+        "peripherals": peripherals,
+        "skipped": skipped,
+        "warnings": warnings,
+    })
+}
+
+/// Adds the registers of `ercs`, in the clusters `prefix` of the peripheral `peripheral` at
+/// `offset`, to `out`, leaving out the ones in `skips`
+fn walk(
+    ercs: &[RegisterCluster],
+    peripheral: &str,
+    prefix: &str,
+    offset: u64,
+    skips: &HashSet<&str>,
+    out: &mut Vec<Value>,
+) {
+    for erc in ercs {
+        let name = format!("{}{}", prefix, util::erc_name(erc));
+        if skips.contains(format!("{}.{}", peripheral, name).as_str()) {
+            continue;
+        }
+        match erc {
+            RegisterCluster::Cluster(c) => {
+                let prefix = format!("{}.", name);
+                let offset = offset + c.address_offset as u64;
+                walk(&c.children, peripheral, &prefix, offset, skips, out);
+            }
+            RegisterCluster::Register(r) => {
+                let fields = r
+                    .fields()
+                    .filter(|f| {
+                        let path = format!("{}.{}.{}", peripheral, name, f.name);
+                        !skips.contains(path.as_str())
+                    })
+                    .map(|f| {
+                        json!({
+                            "name": f.name,
+                            "offset": f.bit_range.offset,
+                            "width": f.bit_range.width,
+                            "enumerated_values": f.enumerated_values.len(),
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                out.push(json!({
+                    "name": name,
+                    "offset": offset + r.address_offset as u64,
+                    "fields": fields,
+                }));
+            }
+        }
+    }
+}
@@ -0,0 +1,196 @@
+//! Checks of the `--strict` flag for inconsistencies of the SVD that are tolerated otherwise, and
+//! the fixups of the ones that would generate broken code without it
+
+use std::collections::HashMap;
+
+use crate::svd::{
+    Cluster, Device, Field, Peripheral, Register, RegisterCluster, RegisterProperties,
+};
+use crate::util::inherit;
+use anyhow::{anyhow, bail, Context, Result};
+use log::warn;
+
+/// Fails on the first inconsistency found in `device`
+pub fn check(device: &Device) -> Result<()> {
+    for p in &device.peripherals {
+        if let Some(registers) = &p.registers {
+            let defs = inherit(
+                &p.default_register_properties,
+                &device.default_register_properties,
+            );
+            check_registers(registers, &defs)
+                .with_context(|| format!("In peripheral `{}`", p.name))?;
+        }
+    }
+    check_address_blocks(&device.peripherals)
+}
+
+fn check_registers(ercs: &[RegisterCluster], defs: &RegisterProperties) -> Result<()> {
+    for erc in ercs {
+        match erc {
+            RegisterCluster::Register(register) => check_register(register, defs)
+                .with_context(|| format!("In register `{}`", register.name))?,
+            RegisterCluster::Cluster(cluster) => check_cluster(cluster, defs)
+                .with_context(|| format!("In cluster `{}`", cluster.name))?,
+        }
+    }
+    Ok(())
+}
+
+fn check_cluster(cluster: &Cluster, defs: &RegisterProperties) -> Result<()> {
+    let defs = inherit(&cluster.default_register_properties, defs);
+    check_registers(&cluster.children, &defs)
+}
+
+fn check_register(register: &Register, defs: &RegisterProperties) -> Result<()> {
+    let size = register
+        .properties
+        .size
+        .or(defs.size)
+        .ok_or_else(|| anyhow!("Register has no `size` field"))?;
+    for f in register.fields() {
+        // the last element of field arrays ends last
+        let offset = match f {
+            Field::Single(_) => f.bit_range.offset,
+            Field::Array(_, dim) => f.bit_range.offset + (dim.dim - 1) * dim.dim_increment,
+        };
+        let end = offset + f.bit_range.width;
+        if end > size {
+            bail!(
+                "Field `{}` ends at bit {}, past the end of the {}-bit register",
+                f.name,
+                end,
+                size
+            );
+        }
+        for evs in &f.enumerated_values {
+            if let Some(ev) = evs
+                .values
+                .iter()
+                .find(|ev| ev.value.map_or(false, |v| !fits(v, f.bit_range.width)))
+            {
+                bail!(
+                    "Enumerated value `{}` of field `{}` doesn't fit in its {} bits",
+                    ev.name,
+                    f.name,
+                    f.bit_range.width
+                );
+            }
+            let mut values = HashMap::new();
+            for ev in evs.values.iter().filter(|ev| ev.is_default.is_none()) {
+                if let Some(value) = ev.value {
+                    if let Some(other) = values.insert(value, &ev.name) {
+                        bail!(
+                            "Enumerated values `{}` and `{}` of field `{}` have the same value {}",
+                            other,
+                            ev.name,
+                            f.name,
+                            value
+                        );
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns `true` if `value` fits in a field `width` bits wide
+fn fits(value: u64, width: u32) -> bool {
+    value.checked_shr(width).map_or(true, |rest| rest == 0)
+}
+
+/// Drops the enumerated values which don't fit in their field, with a warning, as their
+/// variants would be truncated when written
+pub fn drop_oversized_values(device: &mut Device) {
+    for p in &mut device.peripherals {
+        let scope = p.name.clone();
+        if let Some(registers) = &mut p.registers {
+            drop_oversized_values_in(registers, &scope);
+        }
+    }
+}
+
+fn drop_oversized_values_in(ercs: &mut [RegisterCluster], scope: &str) {
+    for erc in ercs {
+        match erc {
+            RegisterCluster::Register(register) => {
+                let scope = format!("{}.{}", scope, register.name);
+                for f in register.fields_mut() {
+                    // closures capture the whole field, so split its borrows first
+                    let f = &mut **f;
+                    let (name, width) = (&f.name, f.bit_range.width);
+                    for evs in &mut f.enumerated_values {
+                        evs.values.retain(|ev| match ev.value {
+                            Some(v) if !fits(v, width) => {
+                                warn!(
+                                    "Ignoring enumerated value `{}` of `{}.{}`, as {} doesn't fit in {} bits",
+                                    ev.name, scope, name, v, width
+                                );
+                                false
+                            }
+                            _ => true,
+                        });
+                    }
+                }
+            }
+            RegisterCluster::Cluster(cluster) => {
+                let scope = format!("{}.{}", scope, cluster.name);
+                drop_oversized_values_in(&mut cluster.children, &scope);
+            }
+        }
+    }
+}
+
+/// Checks that the address blocks of different peripherals don't overlap, except for
+/// `alternatePeripheral`s
+fn check_address_blocks(peripherals: &[Peripheral]) -> Result<()> {
+    let ranges = address_ranges(peripherals);
+    for (i, &(start, end, p)) in ranges.iter().enumerate() {
+        for &(other_start, _, other) in &ranges[i + 1..] {
+            if other_start >= end {
+                break;
+            }
+            let (p, other) = (&peripherals[p], &peripherals[other]);
+            if p.name != other.name && !alternates(p, other) {
+                bail!(
+                    "Address block of peripheral `{}` at {:#x} overlaps the one of `{}` at {:#x}",
+                    other.name,
+                    other_start,
+                    p.name,
+                    start
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Returns the start, end and index of the peripheral of the address blocks of `peripherals`,
+/// sorted by start
+pub(crate) fn address_ranges(peripherals: &[Peripheral]) -> Vec<(u64, u64, usize)> {
+    let mut ranges = vec![];
+    for (i, p) in peripherals.iter().enumerate() {
+        // derived peripherals without address blocks of their own use the ones of the original
+        let blocks = p.address_block.as_ref().or_else(|| {
+            let original = p.derived_from.as_ref()?;
+            peripherals
+                .iter()
+                .find(|x| &x.name == original)?
+                .address_block
+                .as_ref()
+        });
+        for block in blocks.into_iter().flatten().filter(|b| b.size != 0) {
+            let start = p.base_address + block.offset as u64;
+            ranges.push((start, start + block.size as u64, i));
+        }
+    }
+    ranges.sort_by_key(|(start, _, _)| *start);
+    ranges
+}
+
+/// Returns `true` if one of the peripherals is an `alternatePeripheral` of the other
+pub(crate) fn alternates(p: &Peripheral, other: &Peripheral) -> bool {
+    p.alternate_peripheral.as_ref() == Some(&other.name)
+        || other.alternate_peripheral.as_ref() == Some(&p.name)
+}
@@ -1,7 +1,8 @@
 use std::borrow::Cow;
 
 use crate::svd::{
-    Access, Cluster, Field, Register, RegisterCluster, RegisterInfo, RegisterProperties,
+    Access, Cluster, Cpu, DeriveFrom, Device, EnumeratedValues, Field, Register, RegisterCluster,
+    RegisterInfo, RegisterProperties,
 };
 use inflections::Inflect;
 use proc_macro2::{Ident, Literal, Span, TokenStream};
@@ -19,13 +20,50 @@ const BLACKLIST_CHARS: &[char] = &['(', ')', '[', ']', '/', ' ', '-'];
 #[derive(Clone, PartialEq, Debug)]
 pub struct Config {
     pub target: Target,
+    pub target_from_cpu: bool,
+    pub inline: Inline,
+    pub max_field_width: Option<u32>,
     pub nightly: bool,
     pub generic_mod: bool,
     pub make_mod: bool,
+    pub format: bool,
     pub const_generic: bool,
     pub ignore_groups: bool,
     pub keep_list: bool,
     pub strict: bool,
+    pub bitband: bool,
+    pub impl_debug: bool,
+    pub impl_default: bool,
+    pub impl_ord: bool,
+    pub defmt: bool,
+    pub ufmt: bool,
+    pub serde: bool,
+    pub critical_section: bool,
+    pub shared_enums: bool,
+    pub shared_enums_ignore_doc: bool,
+    pub addresses: bool,
+    pub check_layout: bool,
+    pub secure_feature: bool,
+    pub std_feature: bool,
+    pub config_struct: bool,
+    pub flags: bool,
+    pub split_registers: bool,
+    pub reflect: bool,
+    pub str_names: bool,
+    pub field_setters: bool,
+    pub option_setters: bool,
+    pub field_masks: bool,
+    pub spin_helpers: bool,
+    pub ptr_consts: bool,
+    pub address_blocks: bool,
+    pub hal_scaffold: bool,
+    pub vector_table: bool,
+    pub feature_peripheral: bool,
+    pub module_per_peripheral: bool,
+    pub rename_peripherals: Vec<(String, String)>,
+    pub signed_fields: Vec<(String, String, String)>,
+    pub register_enums: Vec<(String, String, EnumeratedValues)>,
+    pub patch: Option<PathBuf>,
     pub output_dir: PathBuf,
     pub source_type: SourceType,
 }
@@ -34,13 +72,50 @@ impl Default for Config {
     fn default() -> Self {
         Self {
             target: Target::default(),
+            target_from_cpu: false,
+            inline: Inline::default(),
+            max_field_width: None,
             nightly: false,
             generic_mod: false,
             make_mod: false,
+            format: false,
             const_generic: false,
             ignore_groups: false,
             keep_list: false,
             strict: false,
+            bitband: false,
+            impl_debug: false,
+            impl_default: false,
+            impl_ord: false,
+            defmt: false,
+            ufmt: false,
+            serde: false,
+            critical_section: false,
+            shared_enums: false,
+            shared_enums_ignore_doc: false,
+            addresses: false,
+            check_layout: false,
+            secure_feature: false,
+            std_feature: false,
+            config_struct: false,
+            flags: false,
+            split_registers: false,
+            reflect: false,
+            str_names: false,
+            field_setters: false,
+            option_setters: false,
+            field_masks: false,
+            spin_helpers: false,
+            ptr_consts: false,
+            address_blocks: false,
+            hal_scaffold: false,
+            vector_table: false,
+            feature_peripheral: false,
+            module_per_peripheral: false,
+            rename_peripherals: Vec::new(),
+            signed_fields: Vec::new(),
+            register_enums: Vec::new(),
+            patch: None,
             output_dir: PathBuf::from("."),
             source_type: SourceType::default(),
         }
@@ -65,10 +140,31 @@ impl Target {
             "cortex-m" => Target::CortexM,
             "msp430" => Target::Msp430,
             "riscv" => Target::RISCV,
-            "xtensa-lx" => Target::XtensaLX,
+            "xtensa-lx" | "xtensa" => Target::XtensaLX,
             "mips" => Target::Mips,
             "none" => Target::None,
-            _ => bail!("unknown target {}", s),
+            _ => bail!(
+                "unknown target `{}`, expected cortex-m, msp430, riscv, xtensa-lx (or xtensa), mips or none",
+                s
+            ),
+        })
+    }
+
+    /// Guesses the target from the `name` of the `<cpu>` of the SVD, e.g. `CM4` or `RV32IMC`
+    pub fn from_cpu(cpu: &Cpu) -> Option<Self> {
+        let name = cpu.name.to_uppercase();
+        Some(if cpu.is_cortex_m() || name.starts_with("SC") {
+            Target::CortexM
+        } else if name.starts_with("RV") || name.contains("RISC") {
+            Target::RISCV
+        } else if name.contains("MSP430") {
+            Target::Msp430
+        } else if name.contains("XTENSA") {
+            Target::XtensaLX
+        } else if name.contains("MIPS") {
+            Target::Mips
+        } else {
+            return None;
         })
     }
 }
@@ -79,6 +175,55 @@ impl Default for Target {
     }
 }
 
+/// Inline attribute of the methods of registers, readers and writers
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Inline {
+    /// `#[inline(always)]`
+    Always,
+    /// `#[inline]`
+    Auto,
+    /// No attribute
+    Never,
+}
+
+impl Inline {
+    pub fn parse(s: &str) -> Result<Self> {
+        Ok(match s {
+            "always" => Inline::Always,
+            "auto" => Inline::Auto,
+            "never" => Inline::Never,
+            _ => bail!("unknown inline mode {}", s),
+        })
+    }
+
+    pub fn attr(self) -> TokenStream {
+        match self {
+            Inline::Always => quote! { #[inline(always)] },
+            Inline::Auto => quote! { #[inline] },
+            Inline::Never => quote! {},
+        }
+    }
+
+    /// Replaces the `#[inline(always)]` attributes of the source of the generic runtime
+    pub fn apply(self, source: &str) -> String {
+        match self {
+            Inline::Always => source.into(),
+            Inline::Auto => source.replace("#[inline(always)]", "#[inline]"),
+            Inline::Never => source
+                .lines()
+                .filter(|line| line.trim() != "#[inline(always)]")
+                .collect::<Vec<_>>()
+                .join("\n"),
+        }
+    }
+}
+
+impl Default for Inline {
+    fn default() -> Self {
+        Self::Always
+    }
+}
+
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub enum SourceType {
     Xml,
@@ -111,23 +256,23 @@ impl SourceType {
 }
 
 pub trait ToSanitizedPascalCase {
-    fn to_sanitized_pascal_case(&self) -> Cow<str>;
+    fn to_sanitized_pascal_case(&self) -> Cow<'_, str>;
 }
 
 pub trait ToSanitizedUpperCase {
-    fn to_sanitized_upper_case(&self) -> Cow<str>;
+    fn to_sanitized_upper_case(&self) -> Cow<'_, str>;
 }
 
 pub trait ToSanitizedSnakeCase {
-    fn to_sanitized_not_keyword_snake_case(&self) -> Cow<str>;
-    fn to_sanitized_snake_case(&self) -> Cow<str> {
+    fn to_sanitized_not_keyword_snake_case(&self) -> Cow<'_, str>;
+    fn to_sanitized_snake_case(&self) -> Cow<'_, str> {
         let s = self.to_sanitized_not_keyword_snake_case();
         sanitize_keyword(s)
     }
 }
 
 impl ToSanitizedSnakeCase for str {
-    fn to_sanitized_not_keyword_snake_case(&self) -> Cow<str> {
+    fn to_sanitized_not_keyword_snake_case(&self) -> Cow<'_, str> {
         const INTERNALS: [&str; 4] = ["set_bit", "clear_bit", "bit", "bits"];
 
         let s = self.replace(BLACKLIST_CHARS, "");
@@ -147,7 +292,7 @@ impl ToSanitizedSnakeCase for str {
     }
 }
 
-pub fn sanitize_keyword(sc: Cow<str>) -> Cow<str> {
+pub fn sanitize_keyword(sc: Cow<'_, str>) -> Cow<'_, str> {
     const KEYWORDS: [&str; 54] = [
         "abstract", "alignof", "as", "async", "await", "become", "box", "break", "const",
         "continue", "crate", "do", "else", "enum", "extern", "false", "final", "fn", "for", "if",
@@ -164,7 +309,7 @@ pub fn sanitize_keyword(sc: Cow<str>) -> Cow<str> {
 }
 
 impl ToSanitizedUpperCase for str {
-    fn to_sanitized_upper_case(&self) -> Cow<str> {
+    fn to_sanitized_upper_case(&self) -> Cow<'_, str> {
         let s = self.replace(BLACKLIST_CHARS, "");
 
         match s.chars().next().unwrap_or('\0') {
@@ -177,7 +322,7 @@ impl ToSanitizedUpperCase for str {
 }
 
 impl ToSanitizedPascalCase for str {
-    fn to_sanitized_pascal_case(&self) -> Cow<str> {
+    fn to_sanitized_pascal_case(&self) -> Cow<'_, str> {
         let s = self.replace(BLACKLIST_CHARS, "");
 
         match s.chars().next().unwrap_or('\0') {
@@ -196,6 +341,19 @@ pub fn respace(s: &str) -> String {
         .replace(r"\n", "\n")
 }
 
+/// `#[doc(alias)]` with the SVD `name` of an item whose Rust identifier is `ident`, so that the
+/// rustdoc search finds it by its name in the datasheet. Empty if the search finds it anyway, as
+/// it ignores the case, or if `name` is not a valid alias
+pub fn doc_alias(name: &str, ident: &str) -> TokenStream {
+    let ident = ident.trim_start_matches("r#");
+    let valid = !name.is_empty() && !name.contains(|c: char| c.is_whitespace() || c == '"');
+    if valid && !name.eq_ignore_ascii_case(ident) {
+        quote! { #[doc(alias = #name)] }
+    } else {
+        quote! {}
+    }
+}
+
 pub fn escape_brackets(s: &str) -> String {
     s.split('[')
         .fold("".to_string(), |acc, x| {
@@ -219,13 +377,21 @@ pub fn escape_brackets(s: &str) -> String {
         })
 }
 
-pub fn name_of(register: &Register, ignore_group: bool) -> Cow<str> {
+pub fn name_of(register: &Register, ignore_group: bool) -> Cow<'_, str> {
     match register {
         Register::Single(info) => info.fullname(ignore_group),
         Register::Array(info, _) => replace_suffix(&info.fullname(ignore_group), "").into(),
     }
 }
 
+/// Returns the name of a type given by the SVD file, like a `headerStructName`, without the `%s`
+/// of arrays and the characters which can't be in identifiers but keeping its case, or `None` if
+/// it still isn't a valid identifier, e.g. a keyword
+pub fn to_type_name(name: &str) -> Option<String> {
+    let name = replace_suffix(name, "").replace(BLACKLIST_CHARS, "");
+    syn::parse_str::<syn::Ident>(&name).ok().map(|_| name)
+}
+
 pub fn replace_suffix(name: &str, suffix: &str) -> String {
     if name.contains("[%s]") {
         name.replace("[%s]", suffix)
@@ -234,30 +400,61 @@ pub fn replace_suffix(name: &str, suffix: &str) -> String {
     }
 }
 
-pub fn access_of(properties: &RegisterProperties, fields: Option<&[Field]>) -> Access {
-    properties.access.unwrap_or_else(|| {
-        if let Some(fields) = fields {
-            if fields.iter().all(|f| f.access == Some(Access::ReadOnly)) {
-                Access::ReadOnly
-            } else if fields.iter().all(|f| f.access == Some(Access::WriteOnce)) {
-                Access::WriteOnce
-            } else if fields
-                .iter()
-                .all(|f| f.access == Some(Access::ReadWriteOnce))
-            {
-                Access::ReadWriteOnce
-            } else if fields
-                .iter()
-                .all(|f| f.access == Some(Access::WriteOnly) || f.access == Some(Access::WriteOnce))
-            {
-                Access::WriteOnly
-            } else {
-                Access::ReadWrite
-            }
-        } else {
-            Access::ReadWrite
+/// Fills the register properties missing from `properties` with the ones of `defaults`, the
+/// properties of the enclosing cluster, peripheral or device as resolved so far.
+///
+/// The `resetValue` and `resetMask` taken from `defaults` are truncated to the `size` of
+/// `properties`, since they may come from a level with wider registers.
+pub fn inherit(
+    properties: &RegisterProperties,
+    defaults: &RegisterProperties,
+) -> RegisterProperties {
+    let mut inherited = properties.derive_from(defaults);
+    if let Some(size) = inherited.size.filter(|&size| size < 64) {
+        let size_mask = !(u64::MAX << size);
+        if properties.reset_value.is_none() {
+            inherited.reset_value = inherited.reset_value.map(|value| value & size_mask);
+        }
+        if properties.reset_mask.is_none() {
+            inherited.reset_mask = inherited.reset_mask.map(|mask| mask & size_mask);
         }
-    })
+    }
+    inherited
+}
+
+/// Returns the access of a register from its own `access`, else the one all its `fields` agree
+/// on, else the `default` access of its cluster, peripheral or device, else read-write
+pub fn access_of(
+    access: Option<Access>,
+    fields: Option<&[Field]>,
+    default: Option<Access>,
+) -> Access {
+    access
+        .or_else(|| fields.and_then(fields_access))
+        .or(default)
+        .unwrap_or(Access::ReadWrite)
+}
+
+/// Returns the access of a register implied by its `fields`, if they all have an explicit one
+/// and those are compatible
+fn fields_access(fields: &[Field]) -> Option<Access> {
+    let all = |accesses: &[Access]| {
+        !fields.is_empty()
+            && fields
+                .iter()
+                .all(|f| f.access.map_or(false, |a| accesses.contains(&a)))
+    };
+    if all(&[Access::ReadOnly]) {
+        Some(Access::ReadOnly)
+    } else if all(&[Access::WriteOnce]) {
+        Some(Access::WriteOnce)
+    } else if all(&[Access::ReadWriteOnce]) {
+        Some(Access::ReadWriteOnce)
+    } else if all(&[Access::WriteOnly, Access::WriteOnce]) {
+        Some(Access::WriteOnly)
+    } else {
+        None
+    }
 }
 
 /// Turns `n` into an unsuffixed separated hex token
@@ -342,6 +539,46 @@ impl U32Ext for u32 {
     }
 }
 
+/// Fails if one of the `--signed_field` paths `fields` isn't a field of `device`
+pub fn check_signed_fields(device: &Device, fields: &[(String, String, String)]) -> Result<()> {
+    for (peripheral, register, field) in fields {
+        let p = device
+            .peripherals
+            .iter()
+            .find(|p| p.name == *peripheral)
+            .ok_or_else(|| {
+                anyhow!(
+                    "Can't sign field `{}`, as there is no peripheral `{}`",
+                    field,
+                    peripheral
+                )
+            })?;
+        let found = registers_in(p.registers.as_deref().unwrap_or(&[]))
+            .into_iter()
+            .filter(|r| r.name == *register)
+            .any(|r| r.fields().any(|f| f.name == *field));
+        if !found {
+            bail!(
+                "Can't sign field `{}`, as there is none in register `{}` of peripheral `{}`",
+                field,
+                register,
+                peripheral
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Returns the registers of `ercs` and of their clusters
+fn registers_in(ercs: &[RegisterCluster]) -> Vec<&Register> {
+    ercs.iter()
+        .flat_map(|erc| match erc {
+            RegisterCluster::Register(r) => vec![r],
+            RegisterCluster::Cluster(c) => registers_in(&c.children),
+        })
+        .collect()
+}
+
 /// Return the name of either register or cluster.
 pub fn erc_name(erc: &RegisterCluster) -> &String {
     match erc {
@@ -407,6 +644,17 @@ pub fn build_rs() -> TokenStream {
     }
 }
 
+/// `Cargo.toml` fragment of the device crate for the `serde` feature of `--serde`: an optional
+/// dependency without `std`, which defines the feature
+pub fn serde_manifest() -> &'static str {
+    r#"[dependencies.serde]
+version = "1"
+default-features = false
+features = ["derive"]
+optional = true
+"#
+}
+
 pub fn handle_reg_error<T>(msg: &str, reg: &Register, res: Result<T>) -> Result<T> {
     let reg_name = &reg.name;
     let descrip = reg.description.as_deref().unwrap_or("No description");
@@ -424,11 +672,11 @@ fn handle_erc_error<T>(msg: &str, name: &str, descrip: &str, res: Result<T>) ->
 }
 
 pub trait FullName {
-    fn fullname(&self, ignore_group: bool) -> Cow<str>;
+    fn fullname(&self, ignore_group: bool) -> Cow<'_, str>;
 }
 
 impl FullName for RegisterInfo {
-    fn fullname(&self, ignore_group: bool) -> Cow<str> {
+    fn fullname(&self, ignore_group: bool) -> Cow<'_, str> {
         match &self.alternate_group {
             Some(group) if !ignore_group => format!("{}_{}", group, self.name).into(),
             _ => self.name.as_str().into(),
@@ -0,0 +1,151 @@
+//! `<vendorExtensions>` and register-level `<enumeratedValues>` of SVD files, which the SVD parser
+//! skips
+
+use crate::svd::{Device, EnumeratedValues, RegisterCluster};
+use anyhow::{Context, Result};
+use svd_parser::Parse;
+
+/// A `<vendorExtensions>` element of an SVD file
+#[derive(Clone, Debug, PartialEq)]
+pub struct VendorExtension {
+    /// Names of the peripheral, clusters, register and field containing the element, as they are
+    /// in the SVD file. Empty for the `<vendorExtensions>` of the device
+    pub path: Vec<String>,
+    /// XML of the element, including its own tags
+    pub xml: String,
+}
+
+/// Returns the `<vendorExtensions>` elements of the SVD file `input`, in the order of the file
+pub fn extensions(input: &str) -> Result<Vec<VendorExtension>> {
+    // spares parsing the file a second time for most of them
+    if !input.contains("<vendorExtensions") {
+        return Ok(Vec::new());
+    }
+    let doc = roxmltree::Document::parse(input).context("Error parsing SVD XML file")?;
+    Ok(doc
+        .descendants()
+        .filter(|n| n.has_tag_name("vendorExtensions"))
+        .map(|n| {
+            let mut path: Vec<_> = n
+                .ancestors()
+                .filter(|a| {
+                    matches!(
+                        a.tag_name().name(),
+                        "peripheral" | "cluster" | "register" | "field"
+                    )
+                })
+                .filter_map(|a| a.children().find(|c| c.has_tag_name("name"))?.text())
+                .map(|name| name.trim().to_string())
+                .collect();
+            path.reverse();
+            VendorExtension {
+                path,
+                xml: input[n.range()].to_string(),
+            }
+        })
+        .collect())
+}
+
+/// Returns the `<enumeratedValues>` of the registers of the SVD file `input`, with the names of
+/// their peripheral and register, in the order of the file
+pub fn register_enums(input: &str) -> Result<Vec<(String, String, EnumeratedValues)>> {
+    if !input.contains("<enumeratedValues") {
+        return Ok(Vec::new());
+    }
+    let doc = roxmltree::Document::parse(input).context("Error parsing SVD XML file")?;
+    let name = |n: roxmltree::Node| {
+        n.children()
+            .find(|c| c.has_tag_name("name"))
+            .and_then(|c| c.text())
+            .map(|name| name.trim().to_string())
+    };
+    let mut enums = Vec::new();
+    for register in doc.descendants().filter(|n| n.has_tag_name("register")) {
+        let peripheral = match register.ancestors().find(|a| a.has_tag_name("peripheral")) {
+            Some(peripheral) => peripheral,
+            None => continue,
+        };
+        let (peripheral, register_name) = match (name(peripheral), name(register)) {
+            (Some(p), Some(r)) => (p, r),
+            _ => continue,
+        };
+        for values in register
+            .children()
+            .filter(|c| c.has_tag_name("enumeratedValues"))
+        {
+            let values = EnumeratedValues::parse(&values, &svd_parser::Config::default())
+                .with_context(|| {
+                    format!(
+                        "Error parsing the enumeratedValues of register `{}` of peripheral `{}`",
+                        register_name, peripheral
+                    )
+                })?;
+            enums.push((peripheral.clone(), register_name.clone(), values));
+        }
+    }
+    Ok(enums)
+}
+
+/// Appends `doc` to the description of the item at `path` of `device`. Returns `false` if there
+/// is no such item
+pub(crate) fn append_doc(device: &mut Device, path: &[String], doc: &str) -> bool {
+    let (name, rest) = match path.split_first() {
+        Some(first) => first,
+        None => return false,
+    };
+    match device.peripherals.iter_mut().find(|p| p.name == *name) {
+        Some(p) if rest.is_empty() => {
+            append(&mut p.description, doc);
+            true
+        }
+        Some(p) => match &mut p.registers {
+            Some(registers) => append_doc_in(registers, rest, doc),
+            None => false,
+        },
+        None => false,
+    }
+}
+
+fn append_doc_in(ercs: &mut [RegisterCluster], path: &[String], doc: &str) -> bool {
+    let (name, rest) = match path.split_first() {
+        Some(first) => first,
+        None => return false,
+    };
+    for erc in ercs {
+        match erc {
+            RegisterCluster::Cluster(c) if c.name == *name => {
+                if rest.is_empty() {
+                    append(&mut c.description, doc);
+                    return true;
+                }
+                return append_doc_in(&mut c.children, rest, doc);
+            }
+            RegisterCluster::Register(r) if r.name == *name => {
+                return match rest {
+                    [] => {
+                        append(&mut r.description, doc);
+                        true
+                    }
+                    [field] => match r.fields_mut().find(|f| f.name == *field) {
+                        Some(f) => {
+                            append(&mut f.description, doc);
+                            true
+                        }
+                        None => false,
+                    },
+                    _ => false,
+                };
+            }
+            _ => {}
+        }
+    }
+    false
+}
+
+/// Appends a paragraph, with the `\n` line breaks of SVD descriptions
+fn append(description: &mut Option<String>, doc: &str) {
+    *description = Some(match description.take() {
+        Some(d) if !d.trim().is_empty() => format!("{}\\n\\n{}", d, doc),
+        _ => doc.to_string(),
+    });
+}
@@ -0,0 +1,662 @@
+//! Generates the code of the SVD files of `tests/svd` and checks the parts they are about
+
+use std::{env, fs, process::Command};
+
+use svd2rust::{Config, Target};
+use svd_rs::{Device, Field, Peripheral};
+
+fn read(name: &str) -> String {
+    let path = format!("{}/tests/svd/{}.svd", env!("CARGO_MANIFEST_DIR"), name);
+    fs::read_to_string(&path).unwrap_or_else(|e| panic!("Error reading {}: {}", path, e))
+}
+
+/// The code generated for `name` without whitespace, to look for items regardless of formatting
+fn generate(name: &str, config: &Config) -> String {
+    let generation = svd2rust::generate(&read(name), config).unwrap();
+    generation.lib_rs.split_whitespace().collect()
+}
+
+/// Compiles the `lib.rs` generated for `svd`, followed by `code`, on the host with the registers
+/// in memory of the "mock" feature, and returns the errors of `rustc` if it fails
+fn compile(name: &str, svd: &str, config: &Config, code: &str) -> Result<(), String> {
+    let config = Config {
+        target: Target::None,
+        ..config.clone()
+    };
+    let generation = svd2rust::generate(svd, &config).unwrap();
+    let dir = env::temp_dir().join(format!("svd2rust-{}-{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("lib.rs"), generation.lib_rs + code).unwrap();
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".into());
+    let output = Command::new(rustc)
+        .args(&[
+            "--edition",
+            "2018",
+            "--crate-type",
+            "lib",
+            "--cfg",
+            "feature=\"mock\"",
+        ])
+        .arg("--out-dir")
+        .arg(&dir)
+        .arg(dir.join("lib.rs"))
+        .output()
+        .unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).into_owned())
+    }
+}
+
+fn count(code: &str, item: &str) -> usize {
+    let item: String = item.split_whitespace().collect();
+    code.matches(&item).count()
+}
+
+/// The items of the module `name` of the compact `code`, up to the next module
+fn module<'a>(code: &'a str, name: &str) -> &'a str {
+    let start = format!("pubmod{}{{", name);
+    let start = code
+        .find(&start)
+        .unwrap_or_else(|| panic!("No module `{}`", name));
+    let code = &code[start + 1..];
+    &code[..code.find("pubmod").unwrap_or(code.len())]
+}
+
+fn peripheral<'a>(device: &'a Device, name: &str) -> &'a Peripheral {
+    device
+        .get_peripheral(name)
+        .unwrap_or_else(|| panic!("No peripheral `{}`", name))
+}
+
+fn field<'a>(p: &'a Peripheral, register: &str, name: &str) -> &'a Field {
+    let register = p
+        .get_register(register)
+        .unwrap_or_else(|| panic!("No register `{}` in `{}`", register, p.name));
+    register
+        .fields()
+        .find(|f| f.name == name)
+        .unwrap_or_else(|| panic!("No field `{}` in `{}`", name, register.name))
+}
+
+fn values_derived_from(f: &Field) -> Option<&str> {
+    f.enumerated_values[0].derived_from.as_deref()
+}
+
+#[test]
+fn collisions_are_renamed_with_their_derived_from() {
+    let svd = read("collisions");
+    let config = Config::default();
+    let device = svd2rust::load_from(&svd, &config).unwrap();
+
+    let names: Vec<_> = device.peripherals.iter().map(|p| p.name.as_str()).collect();
+    assert_eq!(names, ["GPIOA", "GPIO-A_1", "GPIOB", "TIMER"]);
+    let gpiob = peripheral(&device, "GPIOB");
+    assert_eq!(gpiob.derived_from.as_deref(), Some("GPIO-A_1"));
+
+    let gpioa = peripheral(&device, "GPIOA");
+    let registers: Vec<_> = gpioa.registers().map(|r| r.name.as_str()).collect();
+    assert_eq!(registers, ["MODE", "Mode_1", "CFG", "CH%s", "Ch_1%s"]);
+    let cfg = gpioa.get_register("CFG").unwrap();
+    assert_eq!(cfg.derived_from.as_deref(), Some("Mode_1"));
+
+    field(gpioa, "MODE", "en_1");
+    let x = field(gpioa, "MODE", "X");
+    assert_eq!(values_derived_from(x), Some("en_1.en_EV"));
+    let mode = field(peripheral(&device, "TIMER"), "CTRL", "MODE");
+    assert_eq!(values_derived_from(mode), Some("GPIO-A_1.MODE.en_1.en_EV"));
+    let timer = peripheral(&device, "TIMER");
+    let fields: Vec<_> = timer
+        .get_register("CTRL")
+        .unwrap()
+        .fields()
+        .map(|f| f.name.as_str())
+        .collect();
+    assert_eq!(fields, ["MODE", "CH0_1", "CH%s"]);
+
+    compile("collisions", &svd, &config, "").unwrap();
+}
+
+#[test]
+fn registers_inherit_the_size_of_the_device() {
+    let code = generate("size", &Config::default());
+    let spec = |name, ty| {
+        format!(
+            "impl crate::RegisterSpec for {} {{ type Ux = {}; }}",
+            name, ty
+        )
+    };
+
+    assert_eq!(count(&code, &spec("CTRL_SPEC", "u16")), 1);
+    assert_eq!(count(&code, &spec("DATA_SPEC", "u16")), 1);
+    assert_eq!(count(&code, &spec("BYTE_SPEC", "u8")), 2);
+    assert_eq!(count(&code, &spec("WORD_SPEC", "u32")), 1);
+    // the reset value of the device is truncated to the 8-bit registers
+    assert_eq!(count(&code, "const RESET_VALUE: Self::Ux = 0x1234;"), 3);
+    assert_eq!(count(&code, "const RESET_VALUE: Self::Ux = 0x34;"), 2);
+}
+
+#[test]
+fn registers_implement_the_traits_of_their_access() {
+    let code = generate("access", &Config::default());
+    // the register, and whether it is readable, writable and can be written once
+    let registers = [
+        ("ro", true, false, false),
+        ("wo", false, true, false),
+        ("rw", true, true, false),
+        ("wo1", false, true, true),
+        ("rw1", true, true, true),
+        ("fro", true, false, false),
+        ("fwo", false, true, false),
+        ("fmix", true, true, false),
+    ];
+    for &(name, read, write, once) in &registers {
+        let m = module(&code, name);
+        let spec = format!("{}_SPEC", name.to_uppercase());
+        let has = |item: String| count(m, &item) > 0;
+        let method = |method| has(format!("[`{0}`](crate::generic::Reg::{0})", method));
+        assert_eq!(
+            has(format!("impl crate::Readable for {}", spec)),
+            read,
+            "{}",
+            name
+        );
+        assert_eq!(
+            has(format!("impl crate::Writable for {}", spec)),
+            write,
+            "{}",
+            name
+        );
+        assert_eq!(
+            has(format!("impl crate::WriteOnce for {}", spec)),
+            once,
+            "{}",
+            name
+        );
+        assert_eq!(method("read"), read, "{}", name);
+        assert_eq!(method("write"), write, "{}", name);
+        assert_eq!(method("write_once"), once, "{}", name);
+        assert_eq!(method("modify"), read && write, "{}", name);
+    }
+
+    // fields only get the reader or the writer of their access
+    let fmix = module(&code, "fmix");
+    assert_eq!(count(fmix, "pub fn f0(&self) -> F0_R"), 1);
+    assert_eq!(count(fmix, "pub fn f1(&self) -> F1_R"), 0);
+    assert_eq!(count(fmix, "pub fn f0(&mut self) -> F0_W"), 0);
+    assert_eq!(count(fmix, "pub fn f1(&mut self) -> F1_W"), 1);
+}
+
+#[test]
+fn reflect_lists_the_values_read_or_else_written() {
+    let config = Config {
+        reflect: true,
+        ..Config::default()
+    };
+    let code = generate("reflect", &config);
+    let info = |name, offset, values| {
+        format!(
+            "crate::FieldInfo {{ name: \"{}\", offset: {}, width: 2, values: &[{}], }}",
+            name, offset, values
+        )
+    };
+
+    assert_eq!(count(&code, &info("BOTH", 0, "\"Idle\", \"Busy\"")), 1);
+    assert_eq!(count(&code, &info("WRITTEN", 2, "\"Keep\", \"Clear\"")), 1);
+    assert_eq!(count(&code, &info("WO", 4, "\"Off\", \"On\"")), 1);
+    assert_eq!(count(&code, &info("RO", 6, "\"Empty\", \"Full\"")), 1);
+}
+
+#[test]
+fn layout_check_pads_blocks_to_their_alignment() {
+    let config = Config {
+        check_layout: true,
+        ..Config::default()
+    };
+    let code = generate("layout", &config);
+    let size = |name, size| {
+        format!(
+            "const _: crate::BlockSize<{0}, {1}> = crate::BlockSize::<{0}, {{ core::mem::size_of::<{0}>() }}>::SIZE;",
+            name, size
+        )
+    };
+
+    // the channels end at 0x16 in the SVD, but are 8 bytes long like their `u32` register
+    assert_eq!(count(&code, &size("CH", "0x08")), 1);
+    assert_eq!(count(&code, "#[doc(hidden)] _reserved2: [u8; 0x02],"), 1);
+    assert_eq!(count(&code, &size("RegisterBlock", "0x24")), 1);
+    assert_eq!(count(&code, "pub ch: [CH; 2],"), 1);
+    assert_eq!(count(&code, "#[doc(hidden)] _reserved3: [u8; 0x02],"), 1);
+    compile("layout", &read("layout"), &config, "").unwrap();
+
+    // channels overlapping each other fail to compile, with both sizes
+    let svd = read("layout").replace("<dimIncrement>8", "<dimIncrement>6");
+    let error = compile("layout-overlap", &svd, &config, "").unwrap_err();
+    assert!(error.contains("expected `6`, found `8`"), "{}", error);
+    assert!(error.contains("BlockSize :: < CH"), "{}", error);
+}
+
+#[test]
+fn const_index_out_of_bounds_is_rejected_at_build_time() {
+    let svd = read("layout");
+    let config = Config::default();
+    let element = |i| {
+        format!(
+            "///Element\npub fn element(timer: &timer::RegisterBlock) -> &timer::CH {{ timer.ch::<{}>() }}",
+            i
+        )
+    };
+
+    compile("const-index", &svd, &config, &element(1)).unwrap();
+    let error = compile("const-index-oob", &svd, &config, &element(2)).unwrap_err();
+    assert!(
+        error.contains("attempt to compute `1_usize - 2_usize`"),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn derived_peripherals_share_the_block_unless_they_list_registers() {
+    let code = generate("derived", &Config::default());
+    let ptr = |block, address| {
+        format!(
+            "pub const PTR: *const {}::RegisterBlock = {} as *const _;",
+            block, address
+        )
+    };
+
+    // USART2 only has a base address and interrupt of its own
+    assert_eq!(count(&code, &ptr("usart1", "0x4000_4400")), 1);
+    assert_eq!(count(&code, "pub use usart1 as usart2;"), 1);
+    assert_eq!(count(&code, "pub mod usart2 {"), 0);
+    assert_eq!(count(&code, "USART2 = 38,"), 1);
+
+    // UART4 lists the registers of USART1 and one more, so it gets a block of its own
+    assert_eq!(count(&code, &ptr("uart4", "0x4000_4c00")), 1);
+    assert_eq!(count(&code, "pub use usart1 as uart4;"), 0);
+    let uart4 = module(&code, "uart4");
+    assert_eq!(count(uart4, "pub brr: crate::Reg<brr::BRR_SPEC>,"), 1);
+    assert_eq!(count(module(&code, "usart1"), "pub brr:"), 0);
+    assert_eq!(count(&code, "UART4 = 52,"), 1);
+
+    let svd = read("derived");
+    compile("derived", &svd, &Config::default(), "").unwrap();
+}
+
+#[test]
+fn alternates_share_the_address_of_their_register() {
+    let config = Config {
+        check_layout: true,
+        ..Config::default()
+    };
+    let code = generate("alternate", &config);
+    let accessor = |doc, name: &str, offset| {
+        format!(
+            "#[doc = \"{}\"] #[inline(always)] pub fn {1}(&self) -> &crate::Reg<{1}::{2}_SPEC> {{ unsafe {{ &*(((self as *const Self) as *const u8).add({3}usize) as *const crate::Reg<{1}::{2}_SPEC>) }} }}",
+            doc,
+            name,
+            name.to_uppercase(),
+            offset
+        )
+    };
+
+    let docs = [
+        ("0x18 - Capture/compare mode (output mode)", "ccmr1", 24),
+        (
+            "0x18 - Capture/compare mode (input mode) (`Input` group alternate of `ccmr1`)",
+            "input_ccmr1",
+            24,
+        ),
+        ("0x24 - Counter", "cnt", 36),
+        (
+            "0x24 - Counter, signed view (alternate of `cnt`)",
+            "cnt_signed",
+            36,
+        ),
+    ];
+    for &(doc, name, offset) in &docs {
+        assert_eq!(count(&code, &accessor(doc, name, offset)), 1, "{}", name);
+    }
+
+    // each address is only padded once, and the register after them stays at its offset
+    assert_eq!(count(&code, "_reserved_0_ccmr1: [u8; 0x04],"), 1);
+    assert_eq!(count(&code, "_reserved_1_cnt: [u8; 0x04],"), 1);
+    assert_eq!(
+        count(
+            &code,
+            "#[doc = \"0x28 - Auto-reload, right after the counter\"] pub arr:"
+        ),
+        1
+    );
+    compile("alternate", &read("alternate"), &config, "").unwrap();
+}
+
+#[test]
+fn registers_of_64_bits_use_u64_for_fields_crossing_bit_32() {
+    let code = generate("wide", &Config::default());
+    let writer = |name, spec, ty, safety, width, offset| {
+        format!(
+            "pub type {}_W<'a> = crate::WProxy<'a, u64, {}_SPEC, {}, (), crate::{}, {}, {}>;",
+            name, spec, ty, safety, width, offset
+        )
+    };
+
+    assert_eq!(
+        count(
+            &code,
+            "impl crate::RegisterSpec for VAL_SPEC { type Ux = u64; }"
+        ),
+        1
+    );
+    assert_eq!(
+        count(
+            &code,
+            "impl crate::RegisterSpec for ALL_SPEC { type Ux = u64; }"
+        ),
+        1
+    );
+    assert_eq!(
+        count(
+            &code,
+            "pub unsafe fn bits(&mut self, bits: u64) -> &mut Self"
+        ),
+        2
+    );
+    assert_eq!(
+        count(&code, &writer("FLAG", "VAL", "bool", "Safe", 1, 0)),
+        1
+    );
+    assert_eq!(
+        count(&code, &writer("BYTE", "VAL", "u8", "Unsafe", 8, 4)),
+        1
+    );
+    assert_eq!(
+        count(&code, &writer("HALF", "VAL", "u16", "Unsafe", 16, 12)),
+        1
+    );
+    assert_eq!(
+        count(&code, &writer("CROSS", "VAL", "u8", "Unsafe", 4, 30)),
+        1
+    );
+    assert_eq!(
+        count(&code, &writer("WORD", "VAL", "u32", "Unsafe", 20, 34)),
+        1
+    );
+    assert_eq!(
+        count(&code, &writer("TOP", "VAL", "bool", "Safe", 1, 63)),
+        1
+    );
+    assert_eq!(
+        count(&code, &writer("ALL", "ALL", "u64", "Unsafe", 64, 0)),
+        1
+    );
+    assert_eq!(
+        count(&code, "CROSS_R::new(((self.bits >> 30) & 0x0f) as u8)"),
+        1
+    );
+    assert_eq!(
+        count(
+            &code,
+            "WORD_R::new(((self.bits >> 34) & 0x000f_ffff) as u32)"
+        ),
+        1
+    );
+    assert_eq!(
+        count(&code, "pub type ALL_R = crate::FieldReader<u64, u64>;"),
+        1
+    );
+
+    // the writers of all the field types exist for `u64` registers
+    let write = "///Write
+pub fn write(wide: &wide::RegisterBlock) {
+    wide.val.write(|w| unsafe {
+        w.flag().set_bit().byte().bits(1).half().bits(2).cross().bits(3).word().bits(4).top().set_bit()
+    });
+    wide.all.write(|w| unsafe { w.all().bits(u64::MAX) });
+}";
+    compile("wide", &read("wide"), &Config::default(), write).unwrap();
+}
+
+#[test]
+fn read_and_write_values_get_an_enum_each() {
+    let code = generate("usage", &Config::default());
+    let dir = module(&code, "dir");
+
+    // the reader uses the read values, and the writer the write ones
+    let variants = |name, variants: &[(&str, &str)]| {
+        let variants: String = variants
+            .iter()
+            .map(|(doc, variant)| format!("#[doc = \"{}\"] {},", doc, variant))
+            .collect();
+        format!("pub enum {} {{ {} }}", name, variants)
+    };
+    let read_values = [("0: Input", "IN = 0"), ("1: Output", "OUT = 1")];
+    let write_values = [
+        ("0: Make an input", "SETIN = 0"),
+        ("1: Make an output", "SETOUT = 1"),
+        ("2: Give to the alternate function", "SETALT = 2"),
+    ];
+    assert_eq!(count(dir, &variants("MODE_A", &read_values)), 1);
+    assert_eq!(count(dir, &variants("MODE_AW", &write_values)), 1);
+    assert_eq!(
+        count(dir, "pub type MODE_R = crate::FieldReader<u8, MODE_A>;"),
+        1
+    );
+    assert_eq!(
+        count(dir, "pub fn variant(&self) -> crate::Variant<u8, MODE_A>"),
+        1
+    );
+    assert_eq!(
+        count(dir, "pub type MODE_W<'a> = crate::WProxy<'a, u32, DIR_SPEC, u8, MODE_AW, crate::Unsafe, 2, 0>;"),
+        1
+    );
+    assert_eq!(
+        count(
+            dir,
+            "pub fn set_alt(self) -> &'a mut W { self.variant(MODE_AW::SETALT) }"
+        ),
+        1
+    );
+
+    // `read-write` values are shared by both
+    assert_eq!(
+        count(dir, "pub type SPEED_R = crate::FieldReader<bool, SPEED_A>;"),
+        1
+    );
+    assert_eq!(
+        count(dir, "pub type SPEED_W<'a> = crate::WProxy<'a, u32, DIR_SPEC, bool, SPEED_A, crate::Safe, 1, 2>;"),
+        1
+    );
+    assert_eq!(count(dir, "SPEED_AW"), 0);
+
+    let usage = "///Usage
+pub fn usage(port: &port::RegisterBlock) -> bool {
+    use port::dir::{MODE_A, MODE_AW};
+    port.dir.write(|w| w.mode().variant(MODE_AW::SETALT));
+    port.dir.read().mode().variant() == generic::Variant::Val(MODE_A::OUT)
+}";
+    compile("usage", &read("usage"), &Config::default(), usage).unwrap();
+}
+
+#[test]
+fn clusters_nest_with_the_offsets_of_their_parent() {
+    let config = Config {
+        check_layout: true,
+        ..Config::default()
+    };
+    let code = generate("nested", &config);
+    let block = |name, fields| {
+        format!(
+            "#[doc = r\"Register block\"] #[repr(C)] pub struct {} {{ {} }}",
+            name, fields
+        )
+    };
+
+    let dma = "#[doc = \"0x00 - Interrupt status\"] pub isr: crate::Reg<isr::ISR_SPEC>,
+        #[doc(hidden)] _reserved1: [u8; 0x0c],
+        #[doc = \"0x10..0x38 - Outer cluster\"] pub outer: OUTER,";
+    let outer =
+        "#[doc = \"0x00 - Outer control\"] pub octl: crate::Reg<self::outer::octl::OCTL_SPEC>,
+        #[doc(hidden)] _reserved1: [u8; 0x04],
+        #[doc = \"0x08..0x28 - Inner cluster\"] pub inner: [self::outer::INNER; 2],";
+    let inner = "#[doc(hidden)] _reserved0: [u8; 0x04],
+        #[doc = \"0x04 - Inner control\"] pub ictl: crate::Reg<self::inner::ictl::ICTL_SPEC>,
+        #[doc(hidden)] _reserved1: [u8; 0x04],
+        #[doc = \"0x0c - Inner data\"] pub idat: crate::Reg<self::inner::idat::IDAT_SPEC>,";
+    assert_eq!(count(&code, &block("RegisterBlock", dma)), 1);
+    assert_eq!(count(&code, &block("OUTER", outer)), 1);
+    assert_eq!(count(&code, &block("INNER", inner)), 1);
+    // the registers of each cluster are in the module of the cluster, in the one of its parent
+    assert_eq!(
+        count(&code, "pub mod outer { #[doc = \"OCTL register accessor"),
+        1
+    );
+    assert_eq!(
+        count(&code, "pub mod inner { #[doc = \"ICTL register accessor"),
+        1
+    );
+
+    let inner = "///Inner data
+pub fn inner(dma: &dma::RegisterBlock) -> &dma::outer::inner::IDAT {
+    &dma.outer.inner[1].idat
+}";
+    compile("nested", &read("nested"), &config, inner).unwrap();
+}
+
+#[test]
+fn field_masks_are_only_generated_with_the_flag() {
+    let masks = |name, mask| {
+        format!(
+            "pub const {0}_MASK: u64 = {1}; #[doc = \"Mask of the bits of the register outside of the `{0}` field, to clear it with `&`\"] pub const {0}_CLEAR: u64 = !{0}_MASK;",
+            name, mask
+        )
+    };
+
+    let code = generate("wide", &Config::default());
+    assert_eq!(count(&code, "_MASK: u64 ="), 0);
+    assert_eq!(count(&code, "_CLEAR: u64 ="), 0);
+
+    let config = Config {
+        field_masks: true,
+        ..Config::default()
+    };
+    let code = generate("wide", &config);
+    assert_eq!(count(&code, "_MASK: u64 ="), 7);
+    assert_eq!(count(&code, &masks("FLAG", "0x01")), 1);
+    assert_eq!(count(&code, &masks("CROSS", "0x0003_c000_0000")), 1);
+    assert_eq!(count(&code, &masks("TOP", "0x8000_0000_0000_0000")), 1);
+    compile("field-masks", &read("wide"), &config, "").unwrap();
+}
+
+#[test]
+fn registers_with_enumerated_values_get_an_enum_of_their_own() {
+    let code = generate("register_values", &Config::default());
+    let kr = module(&code, "kr");
+    assert_eq!(count(kr, "pub enum KR_A {"), 1);
+    assert_eq!(
+        count(kr, "#[doc = \"43690: Reload the counter\"] RELOAD = 43690,"),
+        1
+    );
+    assert_eq!(
+        count(
+            kr,
+            "pub fn write_variant(&mut self, variant: KR_A) -> &mut Self"
+        ),
+        1
+    );
+    // write-only
+    assert_eq!(count(kr, "pub fn variant(&self)"), 0);
+    let sr = module(&code, "sr");
+    assert_eq!(
+        count(
+            sr,
+            "pub fn variant(&self) -> crate::Variant<u8, SR_A> { use core::convert::TryFrom; match SR_A::try_from(self.bits())"
+        ),
+        1
+    );
+    assert_eq!(count(sr, "pub fn run(&self) -> RUN_R"), 1);
+
+    let code = "
+        /// Reloads the watchdog if it is running
+        pub fn reload(iwdg: &iwdg::RegisterBlock) {
+            if iwdg.sr.read().variant() == crate::Variant::Val(iwdg::sr::SR_A::RUNNING) {
+                iwdg.kr.write(|w| w.write_variant(iwdg::kr::KR_A::RELOAD));
+            }
+        }
+    ";
+    let svd = read("register_values");
+    compile("register-values", &svd, &Config::default(), code).unwrap();
+}
+
+#[test]
+fn report_lists_the_items_skipped_by_the_generation() {
+    let svd = read("skipped");
+    let config = Config {
+        max_field_width: Some(16),
+        ..Config::default()
+    };
+    let generation = svd2rust::generate(&svd, &config).unwrap();
+    let skipped: Vec<_> = generation
+        .skipped
+        .iter()
+        .map(|s| (s.item.as_str(), s.reason.as_str()))
+        .collect();
+    assert_eq!(
+        skipped,
+        [
+            ("TIMER.CH.CCR.RESERVED", "reserved field"),
+            (
+                "TIMER.CH.CCR.VALUE.enumeratedValues",
+                "enumerated values of a field wider than `--max_field_width`"
+            ),
+            ("EMPTY", "no registers"),
+            ("TIMER2", "derived from the missing peripheral MISSING"),
+        ]
+    );
+
+    let device = svd2rust::load_from(&svd, &config).unwrap();
+    let report = svd2rust::report::render(&device, &generation.skipped, &[]);
+    assert_eq!(report["counts"]["peripherals"], 1);
+    assert_eq!(report["counts"]["skipped"], 4);
+    let registers = &report["peripherals"][0]["registers"];
+    assert_eq!(registers[0]["name"], "CH.CCR");
+    assert_eq!(registers[0]["offset"], 0x10);
+    assert_eq!(registers[0]["fields"].as_array().unwrap().len(), 1);
+}
+
+#[test]
+fn cpu_picks_the_target_unless_it_is_explicit() {
+    let config = Config {
+        target_from_cpu: true,
+        ..Config::default()
+    };
+    let code = generate("cpu", &config);
+    assert_eq!(count(&code, "cortex_m::interrupt::free"), 0);
+    assert!(count(&code, "riscv::interrupt::free") > 0);
+
+    let code = generate("cpu", &Config::default());
+    assert!(count(&code, "cortex_m::interrupt::free") > 0);
+    assert_eq!(count(&code, "riscv::interrupt::free"), 0);
+}
+
+#[test]
+fn unknown_targets_are_rejected() {
+    assert_eq!(Target::parse("xtensa").unwrap(), Target::XtensaLX);
+    assert_eq!(Target::parse("xtensa-lx").unwrap(), Target::XtensaLX);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_svd2rust"))
+        .args(&["--target", "esp32"])
+        .current_dir(env::temp_dir())
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("unknown target `esp32`, expected cortex-m, msp430, riscv, xtensa-lx (or xtensa), mips or none"),
+        "{}",
+        stderr
+    );
+}